@@ -9,6 +9,7 @@ use thesis::{
     examples as ex,
     semantics::{
         self,
+        cache::ResultCache,
         options::{self, EvalOptions},
     },
     syntax::{database::Database, query::Query},
@@ -28,6 +29,8 @@ enum Commands {
     Parse(Parse),
     Lubm(Lubm),
     Explore(Explore),
+    Qerror(Qerror),
+    Stats(Stats),
 }
 
 #[derive(Args)]
@@ -38,15 +41,8 @@ struct Example {
     /// Whether to print full results
     #[arg(short, long)]
     print: bool,
-    /// Whether to use the optimizer
-    #[arg(short, long, value_enum, default_value_t = options::Optimizer::Arqpf)]
-    optimizer: options::Optimizer,
-    /// Whether to abort just before query execution
-    #[arg(short, long)]
-    dryrun: bool,
-    /// Whether to examine FILTER conditions
-    #[arg(short, long)]
-    condition: bool,
+    #[command(flatten)]
+    eval: EvalArgs,
 }
 
 #[derive(Args)]
@@ -59,15 +55,8 @@ struct Lubm {
     /// Whether to print full results
     #[arg(short, long)]
     print: bool,
-    /// Whether to use the optimizer
-    #[arg(short, long, value_enum, default_value_t = options::Optimizer::Arqpf)]
-    optimizer: options::Optimizer,
-    /// Whether to abort just before query execution
-    #[arg(short, long)]
-    dryrun: bool,
-    /// Whether to examine FILTER conditions
-    #[arg(short, long)]
-    condition: bool,
+    #[command(flatten)]
+    eval: EvalArgs,
 }
 
 #[derive(Args)]
@@ -82,15 +71,91 @@ struct Parse {
     /// Whether to print full results
     #[arg(short, long)]
     print: bool,
+    #[command(flatten)]
+    eval: EvalArgs,
+}
+
+// Flags of the commands that evaluate queries, a doc comment would become their description
+#[derive(Args)]
+struct EvalArgs {
     /// Whether to use the optimizer
     #[arg(short, long, value_enum, default_value_t = options::Optimizer::Arqpf)]
     optimizer: options::Optimizer,
+    /// The name of a registered estimator to order joins by instead of the optimizer's
+    #[arg(long)]
+    estimator: Option<String>,
+    /// How to order the joins of a basic graph pattern
+    #[arg(long, value_enum, default_value_t = options::JoinOrder::Greedy)]
+    join_order: options::JoinOrder,
+    /// The rewrite rules to apply to the optimized plan, separated by commas
+    #[arg(long, value_enum, value_delimiter = ',')]
+    rewrite: Vec<options::Rewrite>,
     /// Whether to abort just before query execution
     #[arg(short, long)]
     dryrun: bool,
     /// Whether to examine FILTER conditions
     #[arg(short, long)]
     condition: bool,
+    /// The number of threads used to evaluate a query
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+    /// Whether to evaluate a query a batch of rows at a time
+    #[arg(short, long)]
+    batch: bool,
+    /// Whether joins should return results before reading their inputs completely
+    #[arg(long)]
+    pipelined: bool,
+    /// The number of megabytes each join, minus or sort may keep in memory before spilling to disk,
+    /// joins then use a single thread
+    #[arg(short, long)]
+    memory: Option<usize>,
+    /// The number of megabytes of results of subtrees to cache across the queries
+    #[arg(long)]
+    cache: Option<usize>,
+    /// The number of seconds after which the evaluation of a query is stopped
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Whether to print the evaluated plan with estimated and actual results per operation
+    #[arg(long)]
+    explain: bool,
+    /// Print the evaluated plan with its estimates and results per operation as DOT or JSON
+    #[arg(long, value_enum)]
+    export: Option<options::PlanFormat>,
+    /// Plan the remaining joins again once an estimate is off by more than this q-error
+    #[arg(long)]
+    adaptive: Option<f64>,
+    /// The number of triples per scan and random walks per join the sampling optimizer samples
+    #[arg(long, default_value_t = 100)]
+    samples: usize,
+    /// The number of milliseconds each estimate of the sampling optimizer may take
+    #[arg(long)]
+    sample_budget: Option<u64>,
+    /// The seed of every random decision of the statistics and the optimizer
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl From<&EvalArgs> for EvalOptions {
+    fn from(args: &EvalArgs) -> Self {
+        EvalOptions::default()
+            .with_optimizer(args.optimizer)
+            .with_estimator(args.estimator.clone())
+            .with_join_order(args.join_order)
+            .with_rewrites(args.rewrite.clone())
+            .with_explain(args.explain)
+            .with_export(args.export)
+            .with_adaptive(args.adaptive)
+            .with_sampling(args.samples, args.sample_budget.map(Duration::from_millis))
+            .with_seed(args.seed)
+            .with_dryrun(args.dryrun)
+            .with_condition(args.condition)
+            .with_threads(args.threads)
+            .with_batch(args.batch)
+            .with_pipelined(args.pipelined)
+            .with_memory_budget(args.memory.map(|mb| mb * 1024 * 1024))
+            .with_cache(args.cache.map(|mb| ResultCache::new(mb * 1024 * 1024)))
+            .with_timeout(args.timeout.map(Duration::from_secs))
+    }
 }
 
 #[derive(Args)]
@@ -104,6 +169,50 @@ struct Explore {
     number: Option<usize>,
     #[arg(short, long)]
     dryrun: bool,
+    /// The number of seconds after which the evaluation of a single plan is stopped
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// The number of megabytes of results of subtrees to cache across the plans of a query
+    #[arg(long)]
+    cache: Option<usize>,
+    /// The seed of every random decision while building the statistics
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct Qerror {
+    /// The queries file to parse
+    queries_path: PathBuf,
+    /// The database file to parse
+    database_path: PathBuf,
+    /// The number of the query to evaluate
+    #[arg(short, long)]
+    number: Option<usize>,
+    /// The largest number of triple patterns to estimate together
+    #[arg(short, long, default_value_t = 3)]
+    max_size: usize,
+    /// The number of seconds after which the evaluation of a set of triple patterns is stopped
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// The seed of every random decision of the statistics and the estimators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct Stats {
+    /// The database file to parse
+    database_path: PathBuf,
+    /// The number of most frequent objects, prefixes and largest joins to report
+    #[arg(short, long, default_value_t = 10)]
+    top: usize,
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+    /// The seed of every random decision while building the statistics
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
 }
 
 type ExitResult = Result<(), Box<dyn Error>>;
@@ -118,6 +227,8 @@ fn main() -> ExitResult {
         Commands::Lubm(args) => lubm(args),
         Commands::Parse(args) => parse(args),
         Commands::Explore(args) => explore(args),
+        Commands::Qerror(args) => qerror(args),
+        Commands::Stats(args) => stats(args),
     }
 }
 
@@ -155,17 +266,12 @@ fn lubm(args: &Lubm) -> ExitResult {
         })
         .collect::<Vec<(String, Query)>>();
 
-    let database = parse_database(&args.database_path)?;
+    let database = parse_database(&args.database_path, args.eval.seed)?;
 
     run_queries_on_db(
         filtered,
         database,
-        Some(
-            EvalOptions::default()
-                .with_optimizer(args.optimizer)
-                .with_dryrun(args.dryrun)
-                .with_condition(args.condition),
-        ),
+        Some(EvalOptions::from(&args.eval)),
         args.print,
     )
 }
@@ -196,13 +302,8 @@ fn parse(args: &Parse) -> ExitResult {
 
     run_queries_on_db(
         filtered,
-        parse_database(&args.database_path)?,
-        Some(
-            EvalOptions::default()
-                .with_optimizer(args.optimizer)
-                .with_dryrun(args.dryrun)
-                .with_condition(args.condition),
-        ),
+        parse_database(&args.database_path, args.eval.seed)?,
+        Some(EvalOptions::from(&args.eval)),
         args.print,
     )
 }
@@ -238,13 +339,8 @@ fn example(args: &Example) -> ExitResult {
 
     run_queries_on_db(
         filtered,
-        ex::databases::example1(),
-        Some(
-            EvalOptions::default()
-                .with_optimizer(args.optimizer)
-                .with_dryrun(args.dryrun)
-                .with_condition(args.condition),
-        ),
+        ex::databases::example1().with_seed(args.eval.seed),
+        Some(EvalOptions::from(&args.eval)),
         args.print,
     )
 }
@@ -273,14 +369,28 @@ fn explore(args: &Explore) -> ExitResult {
         })
         .collect::<Vec<(String, Query)>>();
 
-    let database = parse_database(&args.database_path)?;
+    let database = parse_database(&args.database_path, args.seed)?;
 
     for (number, query) in filtered {
-        match semantics::explore::explore(query, &database) {
+        // Plans of different queries rarely share subtrees
+        let cache = args.cache.map(|mb| ResultCache::new(mb * 1024 * 1024));
+
+        let explored = semantics::explore::explore(
+            query,
+            &database,
+            args.timeout.map(Duration::from_secs),
+            cache.as_ref(),
+        );
+
+        if let Some(cache) = &cache {
+            log::info!("{} for query {number}", cache.stats());
+        }
+
+        match explored {
             Ok(results) => {
                 for (i, result) in results.iter().enumerate() {
                     println!(
-                        "explore,{},{:?},{},{:.8},{:?},{},{:?},{},\"{:?}\",{},{},{},{}",
+                        "explore,{},{:?},{},{:.8},{:?},{},{:?},{},\"{:?}\",{},{},{},{},{},{}",
                         number,
                         i,
                         result.size(),
@@ -294,6 +404,8 @@ fn explore(args: &Explore) -> ExitResult {
                         result.operations().as_ref().unwrap().scans,
                         result.operations().as_ref().unwrap().disjunct_joins,
                         result.operations().as_ref().unwrap().filters,
+                        result.is_timed_out(),
+                        args.seed,
                     );
                 }
             }
@@ -309,7 +421,85 @@ fn explore(args: &Explore) -> ExitResult {
     Ok(())
 }
 
-fn parse_database(path: &PathBuf) -> Result<Database, Box<dyn Error>> {
+fn qerror(args: &Qerror) -> ExitResult {
+    let queries = fs::read_to_string(&args.queries_path)
+        .expect("Should have been able to read this file")
+        .split("\n\n")
+        .map(|c| c.parse::<Query>())
+        .collect::<Result<Vec<Query>, Box<dyn Error>>>()?;
+
+    let database = parse_database(&args.database_path, args.seed)?;
+
+    println!("query,bgp,patterns,size,optimizer,actual,estimate,qerror,seed");
+
+    for (i, query) in queries.iter().enumerate() {
+        if args
+            .number
+            .is_some_and(|n| n.clamp(1, queries.len()) != i + 1)
+        {
+            continue;
+        }
+
+        let estimates = match semantics::qerror::qerror(
+            query.clone(),
+            &database,
+            args.max_size,
+            args.timeout.map(Duration::from_secs),
+            args.seed,
+        ) {
+            Ok(estimates) => estimates,
+            Err(e) => {
+                log::error!("Failed to estimate query {}", i + 1);
+                log::error!("{e}");
+
+                continue;
+            }
+        };
+
+        for estimate in estimates {
+            println!(
+                "{},{},{},{},{},{},{},{},{}",
+                i + 1,
+                estimate.bgp,
+                estimate
+                    .patterns
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                estimate.patterns.len(),
+                estimate.estimator,
+                estimate.actual.map(|a| a.to_string()).unwrap_or_default(),
+                estimate
+                    .estimate
+                    .map(|e| format!("{e:.2}"))
+                    .unwrap_or_default(),
+                estimate
+                    .q_error()
+                    .map(|q| format!("{q:.4}"))
+                    .unwrap_or_default(),
+                args.seed,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn stats(args: &Stats) -> ExitResult {
+    let database = parse_database(&args.database_path, args.seed)?;
+    let report = database.summary().report(args.top);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+
+    Ok(())
+}
+
+fn parse_database(path: &PathBuf, seed: u64) -> Result<Database, Box<dyn Error>> {
     let mut db = match path.extension().and_then(OsStr::to_str) {
         Some("nt") => Database::from_ntriples_str(
             &fs::read_to_string(path).expect("Should have been able to read this file"),
@@ -320,6 +510,7 @@ fn parse_database(path: &PathBuf) -> Result<Database, Box<dyn Error>> {
         _ => panic!("Cannot parse database"),
     };
 
+    db = db.with_seed(seed);
     db.build_statistics(path)?;
 
     Ok(db)
@@ -341,12 +532,14 @@ fn run_queries_on_db(
     let now = Instant::now();
     let mut duration = Duration::default();
     let mut optimizing = Duration::default();
+    let mut reoptimizations = 0;
 
     for (name, query) in queries {
         let results = semantics::evaluate(&db, query, opts.to_owned())?;
 
         duration += results.run_duration().unwrap_or_default();
         optimizing += results.opt_duration().unwrap_or_default();
+        reoptimizations += results.reoptimizations();
 
         println!(
             "Total rows for {}: {}{}",
@@ -354,11 +547,21 @@ fn run_queries_on_db(
             results.size(),
             if results.is_dryrun() {
                 " (Dry-Run)"
+            } else if results.is_timed_out() {
+                " (timed out)"
             } else {
                 ""
             }
         );
 
+        if let Some(explain) = results.explain() {
+            println!("{explain}");
+        }
+
+        if let Some(plan) = results.plan() {
+            println!("{plan}");
+        }
+
         if print {
             println!("{results}");
         }
@@ -366,7 +569,13 @@ fn run_queries_on_db(
 
     println!("Finished in {:.2?}", now.elapsed());
     println!("Total time spent optimizing {optimizing:.2?}");
+    if opts.as_ref().is_some_and(|o| o.adaptive.is_some()) {
+        println!("Total adaptive re-optimizations {reoptimizations}");
+    }
     println!("Without optimizations {}", duration.as_secs_f64());
+    if let Some(cache) = opts.as_ref().and_then(|o| o.cache.as_ref()) {
+        println!("{}", cache.stats());
+    }
 
     Ok(())
 }
@@ -3,8 +3,8 @@ use tree_sitter::{Node, Tree};
 
 use crate::syntax::{
     query::{
-        Condition, Expression, Object, Predicate, SolutionModifier, Subject, Type, Variable,
-        Variables,
+        Condition, Expression, Object, OrderCondition, Predicate, SolutionModifier, Subject, Type,
+        Variable, Variables,
     },
     Iri,
 };
@@ -170,7 +170,7 @@ impl FromStr for Query {
         let mut query_cursor = tree_sitter::QueryCursor::new();
         let mut selects = query_cursor.matches(&query, tree.root_node(), bytes);
 
-        let modifier = if let Some(m) = selects.next() {
+        let mut modifier = if let Some(m) = selects.next() {
             let capture = m.captures.iter().next().unwrap();
 
             match capture.node.kind() {
@@ -185,6 +185,18 @@ impl FromStr for Query {
             SolutionModifier::default()
         };
 
+        let query = tree_sitter::Query::new(tree.language(), "(order_clause) @order")
+            .expect("should be able to parse query");
+
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        let mut orders = query_cursor.matches(&query, tree.root_node(), bytes);
+
+        if let Some(m) = orders.next() {
+            let capture = m.captures.iter().next().unwrap();
+
+            order_clause(capture.node, &tree, bytes, &mut modifier)?;
+        }
+
         log::trace!("Done with parsing query!");
 
         Ok(Query {
@@ -553,6 +565,42 @@ fn iri_reference(node: Node, _tree: &Tree, bytes: &[u8]) -> ParseResult<Iri> {
     Ok(Iri::IRIREF(node.text(bytes)))
 }
 
+fn order_clause(
+    node: Node,
+    tree: &Tree,
+    bytes: &[u8],
+    modifier: &mut SolutionModifier,
+) -> ParseResult<()> {
+    debug!("Parsing order clause {}", node.kind());
+
+    for condition in node.named_children(&mut tree.walk()) {
+        let descending = condition
+            .child(0)
+            .is_some_and(|c| c.text(bytes).eq_ignore_ascii_case("DESC"));
+
+        // Only variables are supported, also within ASC() and DESC()
+        let variable = match condition.kind() {
+            "var" => condition,
+            _ => first_var(condition)
+                .ok_or_else(|| ParseQueryError::ParseNodeError(format!("{condition:#?}")))?,
+        };
+
+        modifier.with_order(OrderCondition::new(var(variable, tree, bytes)?, descending));
+    }
+
+    Ok(())
+}
+
+fn first_var(node: Node) -> Option<Node> {
+    if node.kind() == "var" {
+        return Some(node);
+    }
+
+    let children: Vec<Node> = node.named_children(&mut node.walk()).collect();
+
+    children.into_iter().find_map(first_var)
+}
+
 fn limit_offset_clauses(node: Node, tree: &Tree, bytes: &[u8]) -> ParseResult<SolutionModifier> {
     debug!("Parsing limit offset clauses {}", node.kind());
 
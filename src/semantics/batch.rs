@@ -0,0 +1,239 @@
+//! Batch-at-a-time evaluation of operations.
+//!
+//! Instead of producing one [`Mapping`] at a time, operations implementing [`BatchIterator`]
+//! produce a [`Batch`] of rows. A batch stores one column of dictionary encoded terms per
+//! variable, so that scanning, joining and filtering compare term identifiers and do not need to
+//! allocate a map for every row. The row-at-a-time interface is recovered with the [`Rows`]
+//! adapter.
+
+use std::collections::HashMap;
+
+use crate::syntax::{
+    database,
+    dictionary::{Dictionary, TermId, UNBOUND},
+    query::{self, Variable},
+};
+
+use super::mapping::{self, Mapping, MappingSet};
+
+/// Number of rows an operation aims to put into one batch
+pub(crate) const BATCH_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Batch {
+    vars: Vec<Variable>,
+    columns: Vec<Vec<TermId>>,
+    len: usize,
+}
+
+impl Batch {
+    pub(crate) fn new(vars: Vec<Variable>) -> Self {
+        Self {
+            columns: vec![Vec::new(); vars.len()],
+            vars,
+            len: 0,
+        }
+    }
+
+    /// Encode a set of mappings into a batch with one column per variable of any of the mappings
+    pub(crate) fn from_mappings(mappings: &[Mapping], dictionary: &Dictionary) -> Self {
+        let mut vars: Vec<Variable> = Vec::new();
+        for mapping in mappings {
+            for var in mapping.keys() {
+                if !vars.contains(var) {
+                    vars.push(var.to_owned());
+                }
+            }
+        }
+
+        let mut batch = Self::new(vars);
+
+        for mapping in mappings {
+            for (var, column) in batch.vars.iter().zip(batch.columns.iter_mut()) {
+                column.push(
+                    mapping
+                        .get(var)
+                        .and_then(|term| dictionary.id(term))
+                        .unwrap_or(UNBOUND),
+                );
+            }
+        }
+
+        batch.len = mappings.len();
+
+        batch
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn vars(&self) -> &Vec<Variable> {
+        &self.vars
+    }
+
+    pub(crate) fn column(&self, var: &Variable) -> Option<&Vec<TermId>> {
+        self.vars
+            .iter()
+            .position(|v| v == var)
+            .map(|i| &self.columns[i])
+    }
+
+    /// Term of a variable in a row, `UNBOUND` if the variable is not bound
+    pub(crate) fn get(&self, var: &Variable, row: usize) -> TermId {
+        self.column(var).map(|c| c[row]).unwrap_or(UNBOUND)
+    }
+
+    /// Append a row, given as one term for each variable of this batch
+    pub(crate) fn push(&mut self, row: impl IntoIterator<Item = TermId>) {
+        for (column, term) in self.columns.iter_mut().zip(row) {
+            column.push(term);
+        }
+
+        self.len += 1;
+    }
+
+    /// Append all rows of another batch, adding unbound columns for variables missing on either side
+    pub(crate) fn append(&mut self, other: Batch) {
+        for var in other.vars.iter() {
+            if !self.vars.contains(var) {
+                self.vars.push(var.to_owned());
+                self.columns.push(vec![UNBOUND; self.len]);
+            }
+        }
+
+        for (var, column) in self.vars.iter().zip(self.columns.iter_mut()) {
+            match other.column(var) {
+                Some(c) => column.extend_from_slice(c),
+                None => column.extend(std::iter::repeat_n(UNBOUND, other.len)),
+            }
+        }
+
+        self.len += other.len;
+    }
+
+    /// Copy of this batch with only the rows for which `keep` returns true
+    pub(crate) fn select(&self, keep: impl Fn(usize) -> bool) -> Self {
+        let rows: Vec<usize> = (0..self.len).filter(|row| keep(*row)).collect();
+
+        Self {
+            columns: self
+                .columns
+                .iter()
+                .map(|column| rows.iter().map(|row| column[*row]).collect())
+                .collect(),
+            vars: self.vars.clone(),
+            len: rows.len(),
+        }
+    }
+
+    /// Check whether a condition holds for a row without decoding the row into a mapping
+    pub(crate) fn satisfies(
+        &self,
+        condition: &query::Condition,
+        row: usize,
+        dictionary: &Dictionary,
+    ) -> bool {
+        mapping::satisfies(condition, &|var| match self.get(var, row) {
+            UNBOUND => None,
+            term => dictionary.term(term),
+        })
+    }
+
+    /// Decode all rows of this batch, leaving out unbound variables
+    pub(crate) fn to_mappings(&self, dictionary: &Dictionary) -> MappingSet {
+        (0..self.len)
+            .map(|row| {
+                self.vars
+                    .iter()
+                    .zip(self.columns.iter())
+                    .filter(|(_, column)| column[row] != UNBOUND)
+                    .map(|(var, column)| {
+                        (
+                            var.to_owned(),
+                            dictionary
+                                .term(column[row])
+                                .cloned()
+                                .unwrap_or(database::Object::B),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Key of a row in a hash table, made up of the terms of the given variables
+pub(crate) fn key(batch: &Batch, vars: &query::Variables, row: usize) -> Vec<TermId> {
+    vars.iter().map(|var| batch.get(var, row)).collect()
+}
+
+/// A batch together with a hash table from the keys of its rows to the row numbers
+#[derive(Debug, Clone)]
+pub(crate) struct HashedBatch {
+    pub(crate) batch: Batch,
+    pub(crate) table: HashMap<Vec<TermId>, Vec<usize>>,
+}
+
+impl HashedBatch {
+    pub(crate) fn new(batch: Batch, vars: &query::Variables) -> Self {
+        let mut table: HashMap<Vec<TermId>, Vec<usize>> = HashMap::new();
+
+        for row in 0..batch.len() {
+            table.entry(key(&batch, vars, row)).or_default().push(row);
+        }
+
+        Self { batch, table }
+    }
+}
+
+/// Operations that can produce their results a batch at a time
+pub(crate) trait BatchIterator {
+    /// Returns the next non-empty batch, or `None` once all results have been produced
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch>;
+}
+
+/// Adapter to consume an operation in batches while producing one mapping at a time
+pub(crate) struct Rows<'d, I> {
+    inner: I,
+    dictionary: &'d Dictionary,
+    current: std::vec::IntoIter<Mapping>,
+}
+
+impl<'d, I: BatchIterator> Rows<'d, I> {
+    pub(crate) fn new(inner: I, dictionary: &'d Dictionary) -> Self {
+        Self {
+            inner,
+            dictionary,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'d, I: BatchIterator> Iterator for Rows<'d, I> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.current.next() {
+                return Some(next);
+            }
+
+            let batch = self.inner.next_batch(self.dictionary)?;
+
+            log::trace!("Rows next() decodes batch of {} rows", batch.len());
+
+            self.current = batch.to_mappings(self.dictionary).into_iter();
+        }
+    }
+}
+
+impl<I: BatchIterator + ?Sized> BatchIterator for &mut I {
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        (**self).next_batch(dictionary)
+    }
+}
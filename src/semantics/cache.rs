@@ -0,0 +1,192 @@
+//! The cache module keeps the results of evaluated subtrees of query plans, so that evaluating
+//! plans that share subtrees, or the same plan again, reads their results instead.
+//!
+//! Subtrees are looked up by a canonical form of the operation, in which variables are numbered
+//! in the order they occur. Subtrees that only differ in the names of their variables share their
+//! results.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::syntax::database;
+
+/// Values of a result, by the number of their variable in the canonical form of the subtree
+pub(crate) type Row = Vec<(usize, database::Object)>;
+
+/**
+ * Results of subtrees of query plans, shared by all clones of the cache.
+ *
+ * The cache keeps at most `budget` bytes of results, dropping the least recently used ones first.
+ * Results are only valid for the database they were evaluated on, so a cache must not be shared
+ * by evaluations on different databases.
+ */
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    budget: usize,
+    results: HashMap<String, Entry>,
+    /// Incremented on every lookup, entries last used at a lower tick are dropped first
+    tick: u64,
+    stats: CacheStats,
+}
+
+#[derive(Debug)]
+struct Entry {
+    rows: Arc<Vec<Row>>,
+    size: usize,
+    used: u64,
+}
+
+/// How often the cache was used and what it holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Number of results dropped to stay within the budget
+    pub evictions: usize,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl CacheStats {
+    /// Fraction of the lookups that found their results, 0 without lookups
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "Result cache: {} hits, {} misses ({:.2}% hit rate), {} evictions, {} entries in {} bytes",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions,
+            self.entries,
+            self.bytes
+        ))
+    }
+}
+
+impl ResultCache {
+    /// Cache keeping at most `budget` bytes of results
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries {
+                budget,
+                ..Entries::default()
+            })),
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.lock().budget
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Drop all results, keeping the statistics of earlier lookups
+    pub fn clear(&self) {
+        let mut entries = self.lock();
+
+        entries.results.clear();
+        entries.stats.entries = 0;
+        entries.stats.bytes = 0;
+    }
+
+    /// Results of the subtree with the canonical form `key`, if they are cached
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<Vec<Row>>> {
+        let mut entries = self.lock();
+        entries.tick += 1;
+
+        let tick = entries.tick;
+        let rows = entries.results.get_mut(key).map(|entry| {
+            entry.used = tick;
+            entry.rows.clone()
+        });
+
+        match rows {
+            Some(_) => entries.stats.hits += 1,
+            None => entries.stats.misses += 1,
+        }
+
+        rows
+    }
+
+    /**
+     * Keep the results of the subtree with the canonical form `key`, dropping the least recently
+     * used results until they fit into the budget.
+     *
+     * Results larger than the whole budget are not kept.
+     */
+    pub(crate) fn insert(&self, key: String, rows: Vec<Row>) {
+        let size = key.len() + rows.iter().map(row_size).sum::<usize>();
+
+        let mut entries = self.lock();
+
+        if size > entries.budget {
+            return;
+        }
+
+        if let Some(previous) = entries.results.remove(&key) {
+            entries.stats.bytes -= previous.size;
+        }
+
+        while entries.stats.bytes + size > entries.budget {
+            let Some(oldest) = entries
+                .results
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.to_owned())
+            else {
+                break;
+            };
+
+            if let Some(entry) = entries.results.remove(&oldest) {
+                entries.stats.bytes -= entry.size;
+                entries.stats.evictions += 1;
+            }
+        }
+
+        let used = entries.tick;
+        entries.results.insert(
+            key,
+            Entry {
+                rows: Arc::new(rows),
+                size,
+                used,
+            },
+        );
+        entries.stats.bytes += size;
+        entries.stats.entries = entries.results.len();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // The entries stay consistent even if a thread panicked while holding the lock
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Estimate of the number of bytes a row occupies in memory
+fn row_size(row: &Row) -> usize {
+    std::mem::size_of::<Row>()
+        + row
+            .iter()
+            .map(|(_, value)| std::mem::size_of::<(usize, database::Object)>() + value.heap_size())
+            .sum::<usize>()
+}
@@ -0,0 +1,243 @@
+//! The estimator module decides how the optimizer estimates the number of results of the parts of
+//! a query plan it orders.
+//!
+//! Estimators are registered by name, and the evaluation options select one of them by that name.
+//! The built-in estimators are registered under the names of the optimizers, so that an estimator
+//! can be added without changing the optimizer or the operations:
+//!
+//! ```
+//! use thesis::semantics::estimator::{self, EstimationContext, EstimationResult, Estimator, SubPlan};
+//!
+//! struct Scans;
+//!
+//! impl Estimator for Scans {
+//!     fn cardinality(&self, plan: &SubPlan, ctx: &EstimationContext) -> EstimationResult {
+//!         Ok(ctx.triples() as f64 * plan.scans() as f64)
+//!     }
+//! }
+//!
+//! estimator::register("scans", Scans);
+//! assert!(estimator::names().contains(&"scans".to_owned()));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    sync::{Arc, OnceLock, RwLock},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+
+use crate::syntax::database::Database;
+
+use super::{
+    operations::{visitors::condition::ConditionInfo, Operation},
+    options::Optimizer,
+    sampling::{seed_for, Sampler},
+    selectivity::Selectivity,
+};
+
+pub use super::selectivity::{
+    SelectivityError as EstimationError, SelectivityResult as EstimationResult,
+};
+
+/// Estimates the number of results of parts of a query plan
+pub trait Estimator: Send + Sync {
+    /// Estimated number of results of `plan` on the database of `ctx`
+    fn cardinality(&self, plan: &SubPlan, ctx: &EstimationContext) -> EstimationResult;
+}
+
+/// Part of a query plan whose number of results is estimated
+pub struct SubPlan<'p, 'a> {
+    pub(crate) operation: &'p Operation<'a>,
+}
+
+impl<'p, 'a> SubPlan<'p, 'a> {
+    pub(crate) fn new(operation: &'p Operation<'a>) -> Self {
+        Self { operation }
+    }
+
+    /// Name of the operation at the root, as in explained plans
+    pub fn operator(&self) -> &'static str {
+        self.operation.name()
+    }
+
+    /// Inputs of the operation at the root
+    pub fn children(&self) -> Vec<SubPlan<'p, 'a>> {
+        self.operation
+            .inputs()
+            .into_iter()
+            .map(SubPlan::new)
+            .collect()
+    }
+
+    /// Subject, predicate and object of the triple pattern of a scan as in queries, `None` for
+    /// other operations
+    pub fn pattern(&self) -> Option<[String; 3]> {
+        match self.operation {
+            Operation::Scan(s) => {
+                let (subject, predicate, object) = s.pattern();
+
+                Some([
+                    subject.to_string(),
+                    predicate.to_string(),
+                    object.to_string(),
+                ])
+            }
+            _ => None,
+        }
+    }
+
+    /// Names of the variables every result of the plan binds, in alphabetical order
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = self
+            .operation
+            .bound_vars()
+            .into_iter()
+            .map(|v| v.name)
+            .collect();
+        variables.sort();
+
+        variables
+    }
+
+    /// Number of scans in the plan
+    pub fn scans(&self) -> usize {
+        self.operation.meta().scans
+    }
+}
+
+/// What an estimator may use besides the plan
+#[derive(Clone)]
+pub struct EstimationContext<'e> {
+    pub database: &'e Database,
+    /// Number of triples or random walks the sampling estimator samples per estimate
+    pub samples: usize,
+    /// Time each estimate of the sampling estimator may take
+    pub sample_budget: Option<Duration>,
+    /// Seed of every random decision of the estimators, for reproducible estimates
+    pub seed: u64,
+    pub(crate) info: &'e ConditionInfo,
+}
+
+impl<'e> EstimationContext<'e> {
+    pub(crate) fn new(database: &'e Database, info: &'e ConditionInfo) -> Self {
+        Self {
+            database,
+            samples: 0,
+            sample_budget: None,
+            seed: 0,
+            info,
+        }
+    }
+
+    pub(crate) fn with_sampling(self, samples: usize, sample_budget: Option<Duration>) -> Self {
+        Self {
+            samples,
+            sample_budget,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Number of triples in the database
+    pub fn triples(&self) -> usize {
+        self.database.triples().len()
+    }
+}
+
+/// One of the estimators of the probabilistic framework, variable counting or sampling
+struct Builtin(Optimizer);
+
+impl Estimator for Builtin {
+    fn cardinality(&self, plan: &SubPlan, ctx: &EstimationContext) -> EstimationResult {
+        let o = plan.operation;
+
+        let selectivity = match self.0 {
+            Optimizer::Off => panic!("No selectivity for OFF Optimizer"),
+            Optimizer::Random => o.sel_random(seed_for(ctx.seed, o)),
+            Optimizer::Fixed => o.sel_fixed(),
+            Optimizer::Arqpf => o.sel_pf(ctx.database.summary()),
+            Optimizer::Arqpfc => o.sel_pfc(ctx.database.summary(), ctx.info),
+            Optimizer::Arqpfj => o.sel_pfj(ctx.database.summary()),
+            Optimizer::Arqpfjc => o.sel_pfjc(ctx.database.summary(), ctx.info),
+            Optimizer::Arqvc => o.sel_vc(),
+            Optimizer::Arqvcp => o.sel_vcp(),
+            Optimizer::Arqcs => o.sel_cs(ctx.database.summary()),
+            Optimizer::Sampling => o.sel_sampling(&Sampler::new(
+                ctx.database,
+                ctx.samples,
+                ctx.sample_budget,
+                ctx.seed,
+            )),
+        }?;
+
+        // Selectivities are relative to all combinations of triples the scans could match
+        Ok(selectivity * (ctx.triples() as f64).powi(plan.scans() as i32))
+    }
+}
+
+type Registry = RwLock<BTreeMap<String, Arc<dyn Estimator>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let builtins = Optimizer::value_variants()
+            .iter()
+            .filter(|o| **o != Optimizer::Off)
+            .map(|o| (o.name(), Arc::new(Builtin(*o)) as Arc<dyn Estimator>));
+
+        RwLock::new(builtins.collect())
+    })
+}
+
+/// Register `estimator` under `name`, replacing and returning an estimator of the same name
+pub fn register(name: &str, estimator: impl Estimator + 'static) -> Option<Arc<dyn Estimator>> {
+    registry()
+        .write()
+        .expect("Estimator registry poisoned")
+        .insert(name.to_owned(), Arc::new(estimator))
+}
+
+/// Names of all registered estimators, in alphabetical order
+pub fn names() -> Vec<String> {
+    registry()
+        .read()
+        .expect("Estimator registry poisoned")
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// The estimator registered under `name`
+pub fn get(name: &str) -> Result<Arc<dyn Estimator>, UnknownEstimator> {
+    registry()
+        .read()
+        .expect("Estimator registry poisoned")
+        .get(name)
+        .cloned()
+        .ok_or_else(|| UnknownEstimator(name.to_owned()))
+}
+
+/// No estimator is registered under the name
+#[derive(Debug)]
+pub struct UnknownEstimator(pub String);
+
+impl Display for UnknownEstimator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown estimator {}, registered are: {}",
+            self.0,
+            names().join(", ")
+        )
+    }
+}
+
+impl Error for UnknownEstimator {}
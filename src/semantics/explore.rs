@@ -6,21 +6,27 @@
 //! given set of nodes. Parts of the algorithm used have been taken from
 //! [here](https://leetcode.com/problems/all-possible-full-binary-trees/solutions/2970045/just-a-runnable-solution/?languageTags=rust).
 
-use std::{collections::HashMap, error::Error, time::Instant};
+use std::{
+    collections::HashMap,
+    error::Error,
+    time::{Duration, Instant},
+};
 
 use iter_progress::ProgressableIter;
 use itertools::Itertools;
 
 use crate::{
     semantics::{
+        cache::ResultCache,
         operations::{
+            context::Context,
             visitors::{
                 condition::ConditionAnalyzer, insert_filter::FilterInserter, optimize::Optimize,
+                prepare::Prepare, reuse::Reuse,
             },
             Operation,
         },
-        options::Optimizer,
-        selectivity::SelectivityEstimator,
+        options::{EvalOptions, Optimizer},
     },
     syntax::{
         database::Database,
@@ -29,17 +35,30 @@ use crate::{
 };
 
 use super::{
+    estimator,
     operations::{
-        visitors::{all_plans::AllPlans, condition::ConditionInfo, planner::Planner},
+        visitors::{
+            all_plans::AllPlans, condition::ConditionInfo, planner::Planner, unnest::Unnest,
+        },
         OperationVisitor,
     },
     results::QueryResult,
 };
 
-pub fn explore(query: Query, database: &Database) -> Result<Vec<QueryResult>, Box<dyn Error>> {
+/**
+ * Evaluate all query plans for a query, stopping each plan that runs for longer than `timeout`
+ *
+ * With a `cache`, the plans read the results of the subtrees they share with earlier plans from it.
+ */
+pub fn explore(
+    query: Query,
+    database: &Database,
+    timeout: Option<Duration>,
+    cache: Option<&ResultCache>,
+) -> Result<Vec<QueryResult>, Box<dyn Error>> {
     let expanded = &query.expand()?;
 
-    let initial = Planner::new(database).visit(expanded);
+    let initial = Unnest::new().visit(&Planner::new(database).visit(expanded));
     let mut plans = AllPlans::new().visit(&initial)?;
 
     let info = ConditionAnalyzer::new().visit(expanded);
@@ -81,8 +100,26 @@ pub fn explore(query: Query, database: &Database) -> Result<Vec<QueryResult>, Bo
 
             let meta = plan.meta();
 
+            let ctx = Context::new().with_timeout(timeout);
+
             let now = Instant::now();
-            QueryResult::select(plan.collect())
+            let plan = match cache {
+                Some(cache) => Reuse::new(cache.clone(), ctx.clone()).visit(&plan),
+                None => plan,
+            };
+            let plan = Prepare::new(ctx.clone()).visit(&plan);
+
+            let results = plan.collect();
+
+            let result = if ctx.timed_out() {
+                log::warn!("Plan with ID {i} timed out after {:.2?}", now.elapsed());
+
+                QueryResult::timed_out()
+            } else {
+                QueryResult::select(results)
+            };
+
+            result
                 .with_run_duration(now.elapsed())
                 .with_optimizers(optimizers)
                 .with_meta(Some(meta))
@@ -100,88 +137,33 @@ fn special_plans<'a>(
 
     let with_filter_pushing = initial.meta().filters > 0;
 
-    special.insert(
-        (Optimizer::Off, false),
-        Optimize::new(SelectivityEstimator::Off).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Fixed, false),
-        Optimize::new(SelectivityEstimator::Fixed).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqpf, false),
-        Optimize::new(SelectivityEstimator::Arqpf(database.summary())).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqpfc, false),
-        Optimize::new(SelectivityEstimator::Arqpfc(database.summary(), info)).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqpfj, false),
-        Optimize::new(SelectivityEstimator::Arqpfj(database.summary())).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqpfjc, false),
-        Optimize::new(SelectivityEstimator::Arqpfjc(database.summary(), info)).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqvc, false),
-        Optimize::new(SelectivityEstimator::Arqvc).visit(initial)?,
-    );
-    special.insert(
-        (Optimizer::Arqvcp, false),
-        Optimize::new(SelectivityEstimator::Arqvcp).visit(initial)?,
-    );
-
-    if with_filter_pushing {
-        special.insert(
-            (Optimizer::Off, true),
-            Optimize::new(SelectivityEstimator::Off)
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Fixed, true),
-            Optimize::new(SelectivityEstimator::Fixed)
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Arqpf, true),
-            Optimize::new(SelectivityEstimator::Arqpf(database.summary()))
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Arqpfc, true),
-            Optimize::new(SelectivityEstimator::Arqpfc(database.summary(), info))
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Arqpfj, true),
-            Optimize::new(SelectivityEstimator::Arqpfj(database.summary()))
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Arqpfjc, true),
-            Optimize::new(SelectivityEstimator::Arqpfjc(database.summary(), info))
-                .with_condition(true)
-                .visit(initial)?,
-        );
-        special.insert(
-            (Optimizer::Arqvc, true),
-            Optimize::new(SelectivityEstimator::Arqvc)
-                .with_condition(true)
-                .visit(initial)?,
-        );
+    // Random and sampling estimators do not produce the same plan twice
+    for optimizer in [
+        Optimizer::Off,
+        Optimizer::Fixed,
+        Optimizer::Arqpf,
+        Optimizer::Arqpfc,
+        Optimizer::Arqpfj,
+        Optimizer::Arqpfjc,
+        Optimizer::Arqvc,
+        Optimizer::Arqvcp,
+        Optimizer::Arqcs,
+    ] {
+        let opts = EvalOptions::default().with_optimizer(optimizer);
+
         special.insert(
-            (Optimizer::Arqvcp, true),
-            Optimize::new(SelectivityEstimator::Arqvcp)
-                .with_condition(true)
-                .visit(initial)?,
+            (optimizer, false),
+            Optimize::new(estimator(database, &opts, info)?).visit(initial)?,
         );
+
+        if with_filter_pushing {
+            special.insert(
+                (optimizer, true),
+                Optimize::new(estimator(database, &opts, info)?)
+                    .with_condition(true)
+                    .visit(initial)?,
+            );
+        }
     }
 
     Ok(special)
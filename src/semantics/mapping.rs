@@ -2,13 +2,14 @@ use core::fmt;
 use std::collections::{btree_map::Keys, BTreeMap};
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::syntax::{database, query};
 
 pub(crate) type MappingSet = Vec<Mapping>;
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub(crate) struct Mapping {
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Mapping {
     pub(crate) items: BTreeMap<query::Variable, database::Object>,
 }
 
@@ -39,6 +40,21 @@ impl Mapping {
         self.items.keys()
     }
 
+    /// Estimate of the number of bytes this mapping occupies in memory
+    pub(crate) fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .items
+                .iter()
+                .map(|(k, v)| {
+                    std::mem::size_of::<query::Variable>()
+                        + std::mem::size_of::<database::Object>()
+                        + k.name.capacity()
+                        + v.heap_size()
+                })
+                .sum::<usize>()
+    }
+
     pub(crate) fn hash_map_key(&self, vars: &query::Variables) -> String {
         vars.iter().filter_map(|v| self.get(v)).join("")
     }
@@ -65,73 +81,85 @@ impl Mapping {
     }
 
     pub(crate) fn satisfies(&self, r: &query::Condition) -> bool {
-        match r {
-            query::Condition::Equals(o1, o2) => match o1 {
-                query::Object::L(l1) => match o2 {
-                    query::Object::L(l2) => l1 == l2,
-                    query::Object::I(_) => false,
-                    query::Object::V(_) => false,
+        satisfies(r, &|v| self.get(v))
+    }
+}
+
+/**
+ * Check whether a condition holds for the variable bindings returned by `get`.
+ *
+ * Unbound variables are those for which `get` returns `None`.
+ */
+pub(crate) fn satisfies<'o, F>(r: &query::Condition, get: &F) -> bool
+where
+    F: Fn(&query::Variable) -> Option<&'o database::Object>,
+{
+    match r {
+        query::Condition::Equals(o1, o2) => match o1 {
+            query::Object::L(l1) => match o2 {
+                query::Object::L(l2) => l1 == l2,
+                query::Object::I(_) => false,
+                query::Object::V(_) => false,
+            },
+            query::Object::I(u1) => match o2 {
+                query::Object::L(_) => false,
+                query::Object::I(u2) => u1 == u2,
+                query::Object::V(_) => false,
+            },
+            query::Object::V(v1) => match o2 {
+                query::Object::L(l) => get(v1) == Some(&database::Object::L(l.to_owned())),
+                query::Object::I(u) => get(v1) == Some(&database::Object::I(u.to_owned())),
+                query::Object::V(v2) => get(v1) == get(v2),
+            },
+        },
+        query::Condition::LT(o1, o2) => match o1 {
+            query::Object::L(l1) => match o2 {
+                query::Object::L(l2) => l1 < l2,
+                query::Object::I(_) => false,
+                query::Object::V(v2) => match &get(v2) {
+                    Some(database::Object::L(l2)) => l1 < l2,
+                    _ => false,
                 },
-                query::Object::I(u1) => match o2 {
-                    query::Object::L(_) => false,
-                    query::Object::I(u2) => u1 == u2,
-                    query::Object::V(_) => false,
+            },
+            query::Object::I(_) => false,
+            query::Object::V(v1) => match o2 {
+                query::Object::L(l2) => match &get(v1) {
+                    Some(database::Object::L(l1)) => l1 < l2,
+                    _ => false,
                 },
-                query::Object::V(v1) => match o2 {
-                    query::Object::L(l) => self.get(v1) == Some(&database::Object::L(l.to_owned())),
-                    query::Object::I(u) => self.get(v1) == Some(&database::Object::I(u.to_owned())),
-                    query::Object::V(v2) => self.get(v1) == self.get(v2),
+                query::Object::I(_) => false,
+                query::Object::V(v2) => match (&get(v1), &get(v2)) {
+                    (Some(database::Object::L(l1)), Some(database::Object::L(l2))) => l1 < l2,
+                    _ => false,
                 },
             },
-            query::Condition::LT(o1, o2) => match o1 {
-                query::Object::L(l1) => match o2 {
-                    query::Object::L(l2) => l1 < l2,
-                    query::Object::I(_) => false,
-                    query::Object::V(v2) => match &self.get(v2) {
-                        Some(database::Object::L(l2)) => l1 < l2,
-                        _ => false,
-                    },
-                },
+        },
+        query::Condition::GT(o1, o2) => match o1 {
+            query::Object::L(l1) => match o2 {
+                query::Object::L(l2) => l1 > l2,
                 query::Object::I(_) => false,
-                query::Object::V(v1) => match o2 {
-                    query::Object::L(l2) => match &self.get(v1) {
-                        Some(database::Object::L(l1)) => l1 < l2,
-                        _ => false,
-                    },
-                    query::Object::I(_) => false,
-                    query::Object::V(v2) => match (&self.get(v1), &self.get(v2)) {
-                        (Some(database::Object::L(l1)), Some(database::Object::L(l2))) => l1 < l2,
-                        _ => false,
-                    },
+                query::Object::V(v2) => match &get(v2) {
+                    Some(database::Object::L(l2)) => l1 > l2,
+                    _ => false,
                 },
             },
-            query::Condition::GT(o1, o2) => match o1 {
-                query::Object::L(l1) => match o2 {
-                    query::Object::L(l2) => l1 > l2,
-                    query::Object::I(_) => false,
-                    query::Object::V(v2) => match &self.get(v2) {
-                        Some(database::Object::L(l2)) => l1 > l2,
-                        _ => false,
-                    },
+            query::Object::I(_) => false,
+            query::Object::V(v1) => match o2 {
+                query::Object::L(l2) => match &get(v1) {
+                    Some(database::Object::L(l1)) => l1 > l2,
+                    _ => false,
                 },
                 query::Object::I(_) => false,
-                query::Object::V(v1) => match o2 {
-                    query::Object::L(l2) => match &self.get(v1) {
-                        Some(database::Object::L(l1)) => l1 > l2,
-                        _ => false,
-                    },
-                    query::Object::I(_) => false,
-                    query::Object::V(v2) => match (&self.get(v1), &self.get(v2)) {
-                        (Some(database::Object::L(l1)), Some(database::Object::L(l2))) => l1 > l2,
-                        _ => false,
-                    },
+                query::Object::V(v2) => match (&get(v1), &get(v2)) {
+                    (Some(database::Object::L(l1)), Some(database::Object::L(l2))) => l1 > l2,
+                    _ => false,
                 },
             },
-            query::Condition::Bound(v) => self.contains_key(&v),
-            query::Condition::Not(c) => !self.satisfies(c),
-            query::Condition::And(c1, c2) => self.satisfies(c1) && self.satisfies(c2),
-            query::Condition::Or(c1, c2) => self.satisfies(c1) || self.satisfies(c2),
-        }
+        },
+        query::Condition::Bound(v) => get(v).is_some(),
+        query::Condition::Not(c) => !satisfies(c, get),
+        query::Condition::And(c1, c2) => satisfies(c1, get) && satisfies(c2, get),
+        query::Condition::Or(c1, c2) => satisfies(c1, get) || satisfies(c2, get),
     }
 }
 
@@ -1,9 +1,15 @@
+mod batch;
+pub mod cache;
+pub mod estimator;
 pub mod explore;
 mod mapping;
 mod operations;
 pub mod options;
+pub mod qerror;
 mod results;
+mod sampling;
 mod selectivity;
+mod spill;
 
 #[cfg(test)]
 mod tests;
@@ -11,10 +17,23 @@ mod tests;
 use crate::{
     semantics::{
         operations::{
-            visitors::{optimize::Optimize, planner::Planner},
-            OperationVisitor,
+            context::Context,
+            visitors::{
+                condition::ConditionInfo,
+                dp::{Adaptation, DpOptimize},
+                explain::Explain,
+                export::Export,
+                optimize::Optimize,
+                planner::Planner,
+                prepare::Prepare,
+                pushdown::Pushdown,
+                reuse::Reuse,
+                rewrite::Rewriter,
+                unnest::Unnest,
+            },
+            Operation, OperationVisitor,
         },
-        options::Optimizer,
+        options::{JoinOrder, Optimizer},
     },
     syntax::expand::Expand,
 };
@@ -24,12 +43,14 @@ use crate::syntax::{
     query::{self, QueryVisitor},
 };
 
+use clap::ValueEnum;
+
 use std::error::Error;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use self::{
-    operations::visitors::condition::ConditionAnalyzer, options::EvalOptions, results::QueryResult,
-};
+use self::{operations::visitors::condition::ConditionAnalyzer, options::EvalOptions};
+
+pub use self::results::{QueryResult, Solutions, TimeoutError};
 
 /**
 * Evaluate a query on a database
@@ -41,6 +62,116 @@ pub fn evaluate(
 ) -> Result<QueryResult, Box<dyn Error>> {
     let opts = opts.unwrap_or_default();
 
+    let (plan, ctx, optimization_duration, adaptation) = plan(db, &query, &opts)?;
+
+    if opts.batch {
+        // Encode the database up front, so that it does not count towards the run duration
+        db.encoded();
+    }
+
+    let now = Instant::now();
+
+    let result = if opts.dryrun {
+        QueryResult::dryrun()
+    } else {
+        // Clones share the statistics of the evaluated plan
+        let analyzed = (opts.explain || opts.export.is_some()).then(|| plan.clone());
+
+        let mut solutions = Solutions::new(db, plan, ctx, opts.batch);
+
+        let result = match &query.kind {
+            query::Type::SelectQuery(_, _, _) => QueryResult::select(solutions.by_ref().collect()),
+            query::Type::AskQuery(_, _) => QueryResult::ask(solutions.next().is_some()),
+        };
+
+        solutions.check_failure()?;
+
+        let result = match solutions.check_timeout() {
+            Ok(()) => result,
+            Err(e) => {
+                log::warn!("{e} after {:.2?}", now.elapsed());
+
+                QueryResult::timed_out()
+            }
+        };
+
+        match analyzed {
+            Some(plan) => {
+                let info = ConditionAnalyzer::new().visit(&query);
+                let estimator = estimator(db, &opts, &info)?;
+
+                let explain = opts
+                    .explain
+                    .then(|| Explain::new(estimator.clone()).visit(&plan));
+                let export = opts
+                    .export
+                    .map(|format| Export::new(estimator.clone()).node(&plan).render(format));
+
+                result.with_explain(explain).with_plan(export)
+            }
+            None => result,
+        }
+    };
+
+    Ok(result
+        .with_run_duration(now.elapsed() + adaptation.materializing)
+        .with_optimization_duration(optimization_duration)
+        .with_reoptimizations(adaptation.reoptimizations))
+}
+
+/**
+* Evaluate a query on a database lazily, producing one solution at a time
+*
+* Only as much of the query is evaluated as is needed for the solutions consumed so far.
+*/
+pub fn stream<'a>(
+    db: &'a database::Database,
+    query: &query::Query,
+    opts: Option<EvalOptions>,
+) -> Result<Solutions<'a>, Box<dyn Error>> {
+    let opts = opts.unwrap_or_default();
+
+    let (plan, ctx, _, _) = plan(db, query, &opts)?;
+
+    Ok(Solutions::new(db, plan, ctx, opts.batch))
+}
+
+/**
+* Selectivity estimator for the estimator or optimizer options
+*/
+fn estimator<'a>(
+    db: &'a database::Database,
+    opts: &EvalOptions,
+    info: &'a ConditionInfo,
+) -> Result<selectivity::SelectivityEstimator<'a>, estimator::UnknownEstimator> {
+    let ctx = estimator::EstimationContext::new(db, info)
+        .with_sampling(opts.samples, opts.sample_budget)
+        .with_seed(opts.seed);
+
+    Ok(match (&opts.estimator, opts.optimizer) {
+        (Some(name), _) => selectivity::SelectivityEstimator::new(
+            // Built-in estimators are named like their optimizers
+            Optimizer::from_str(name, false).map_or(name.to_owned(), |o| o.to_string()),
+            estimator::get(name)?,
+            ctx,
+        ),
+        (None, Optimizer::Off) => selectivity::SelectivityEstimator::off(ctx),
+        (None, optimizer) => selectivity::SelectivityEstimator::new(
+            optimizer.to_string(),
+            estimator::get(&optimizer.name())?,
+            ctx,
+        ),
+    })
+}
+
+/**
+* Plan, optimize and prepare a query for evaluation
+*/
+fn plan<'a>(
+    db: &'a database::Database,
+    query: &query::Query,
+    opts: &EvalOptions,
+) -> Result<(Operation<'a>, Context, Duration, Adaptation), Box<dyn Error>> {
     if opts.log {
         log::warn!(
             "--- Evaluating query ---\n{} on {} triples",
@@ -49,20 +180,18 @@ pub fn evaluate(
         );
     }
 
-    let info = ConditionAnalyzer::new().visit(&query);
-    let optimizer = match opts.optimizer {
-        Optimizer::Off => selectivity::SelectivityEstimator::Off,
-        Optimizer::Random => selectivity::SelectivityEstimator::Random,
-        Optimizer::Fixed => selectivity::SelectivityEstimator::Fixed,
-        Optimizer::Arqpf => selectivity::SelectivityEstimator::Arqpf(db.summary()),
-        Optimizer::Arqpfc => selectivity::SelectivityEstimator::Arqpfc(db.summary(), &info),
-        Optimizer::Arqpfj => selectivity::SelectivityEstimator::Arqpfj(db.summary()),
-        Optimizer::Arqpfjc => selectivity::SelectivityEstimator::Arqpfjc(db.summary(), &info),
-        Optimizer::Arqvc => selectivity::SelectivityEstimator::Arqvc,
-        Optimizer::Arqvcp => selectivity::SelectivityEstimator::Arqvcp,
-    };
+    if opts.batch && opts.memory_budget.is_some() {
+        return Err("Batch evaluation does not support a memory budget".into());
+    }
+
+    if opts.threads > 1 && opts.memory_budget.is_some() {
+        log::warn!("Joins are evaluated on a single thread to keep to the memory budget");
+    }
+
+    let info = ConditionAnalyzer::new().visit(query);
+    let optimizer = estimator(db, opts, &info)?;
 
-    let expanded = Expand::new(query.prologue.clone()).visit(&query)?;
+    let expanded = Expand::new(query.prologue.clone()).visit(query)?;
 
     let plan = Planner::new(db).visit(&expanded);
 
@@ -70,30 +199,76 @@ pub fn evaluate(
         log::warn!("--- Initial Query Plan ---\n{}\n", plan);
     }
 
+    // The order of results does not matter for ASK queries, so they can always stop at the first
+    let pipelined = opts.pipelined || matches!(query.kind, query::Type::AskQuery(_, _));
+
+    let ctx = Context::new()
+        .with_threads(opts.threads)
+        .with_pipelined(pipelined)
+        .with_memory_budget(opts.memory_budget)
+        .with_timeout(opts.timeout)
+        .with_analyze(opts.explain || opts.export.is_some());
+
     let now = Instant::now();
 
-    let mut optimized = Optimize::new(optimizer.clone())
-        .with_condition(opts.condition)
-        .visit(&plan)?;
+    // Joins are moved below optional and union operations, so that they can be reordered
+    let plan = if optimizer.is_off() {
+        plan
+    } else {
+        Unnest::new().visit(&plan)
+    };
+
+    let adaptive = opts.adaptive.filter(|_| !opts.dryrun);
+
+    let (optimized, mut adaptation) = match opts.join_order {
+        JoinOrder::Greedy if adaptive.is_none() => (
+            Optimize::new(optimizer.clone())
+                .with_condition(opts.condition)
+                .visit(&plan)?,
+            Adaptation::default(),
+        ),
+        JoinOrder::Greedy | JoinOrder::Dp => {
+            let mut dp = DpOptimize::new(optimizer.clone(), db.triples().len())
+                .with_condition(opts.condition)
+                .with_adaptive(adaptive, ctx.clone());
+
+            (dp.visit(&plan)?, dp.adaptation())
+        }
+    };
+
+    let optimized = Rewriter::new(opts.rewrites.clone()).rewrite(&optimized);
+
+    // Filters next to scans are evaluated by the scans themselves
+    let optimized = if opts.condition {
+        Pushdown::new().visit(&optimized)
+    } else {
+        optimized
+    };
 
     if opts.log {
         log::warn!("--- Optimized Query Plan ---\n{}\n", optimized);
     }
 
-    let optimization_duration = now.elapsed();
-
-    let now = Instant::now();
+    // Subtrees are evaluated up front to cache their results, which is part of evaluating the query
+    let optimized = match &opts.cache {
+        Some(cache) if !opts.dryrun => {
+            let mut reuse = Reuse::new(cache.clone(), ctx.clone())
+                .with_limited(matches!(query.kind, query::Type::AskQuery(_, _)));
+            let reused = reuse.visit(&optimized);
+            adaptation.materializing += reuse.materializing();
 
-    let result = if opts.dryrun {
-        QueryResult::dryrun()
-    } else {
-        match query.kind {
-            query::Type::SelectQuery(_, _, _) => QueryResult::select(optimized.collect()),
-            query::Type::AskQuery(_, _) => QueryResult::ask(optimized.next().is_some()),
+            reused
         }
+        _ => optimized,
     };
 
-    Ok(result
-        .with_run_duration(now.elapsed())
-        .with_optimization_duration(optimization_duration))
+    // Materializing joins is part of evaluating the query rather than optimizing it
+    let optimization_duration = now.elapsed().saturating_sub(adaptation.materializing);
+
+    Ok((
+        Prepare::new(ctx.clone()).visit(&optimized),
+        ctx,
+        optimization_duration,
+        adaptation,
+    ))
 }
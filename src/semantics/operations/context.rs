@@ -0,0 +1,217 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use dpc_pariter::IteratorExt;
+
+/// Runtime settings shared by the operations of a query plan during evaluation.
+#[derive(Debug, Clone)]
+pub(crate) struct Context {
+    /// Number of worker threads the operations of a plan share, 1 means sequential evaluation
+    pub(crate) threads: usize,
+    /// Worker threads no operation is using, shared by all clones
+    workers: Arc<AtomicUsize>,
+    /// Whether joins produce results while reading both inputs instead of building a hash table first
+    pub(crate) pipelined: bool,
+    /// Number of bytes a hash join, minus or sort may keep in memory before spilling to disk
+    ///
+    /// Joins with a budget are evaluated on a single thread, even if the context asks for parallel
+    /// joins.
+    pub(crate) memory_budget: Option<usize>,
+    /// Whether operations record the number of mappings they produce
+    pub(crate) analyze: bool,
+    /// Time operations may take to produce results
+    timeout: Option<Duration>,
+    /// Point in time after which operations stop producing results, shared by all clones
+    deadline: Arc<OnceLock<Instant>>,
+    /// Set by the first operation that notices the deadline has passed, shared by all clones
+    timed_out: Arc<AtomicBool>,
+    /// First error an operation could not recover from, shared by all clones
+    failure: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl Context {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with_threads(self, threads: usize) -> Self {
+        let threads = threads.max(1);
+
+        Self {
+            threads,
+            workers: Arc::new(AtomicUsize::new(threads)),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_pipelined(self, pipelined: bool) -> Self {
+        Self { pipelined, ..self }
+    }
+
+    pub(crate) fn with_memory_budget(self, memory_budget: Option<usize>) -> Self {
+        Self {
+            memory_budget,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_analyze(self, analyze: bool) -> Self {
+        Self { analyze, ..self }
+    }
+
+    /**
+     * Stop evaluation once `timeout` has passed since it started.
+     *
+     * The clock starts when an operation first checks whether the deadline has passed, so that
+     * planning and optimizing the query do not count towards the timeout.
+     */
+    pub(crate) fn with_timeout(self, timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            deadline: Arc::new(OnceLock::new()),
+            ..self
+        }
+    }
+
+    /// Whether the deadline has passed, in which case operations should return no more results
+    pub(crate) fn is_expired(&self) -> bool {
+        let Some(timeout) = self.timeout else {
+            return false;
+        };
+
+        let deadline = self.deadline.get_or_init(|| Instant::now() + timeout);
+
+        if Instant::now() >= *deadline {
+            self.timed_out.store(true, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether any operation sharing this context stopped early because of the deadline
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// Stop evaluation because of `error`, which is kept if it is the first one
+    pub(crate) fn fail(&self, error: io::Error) {
+        log::error!("Evaluation failed: {error}");
+
+        let mut failure = self.failure.lock().unwrap_or_else(|e| e.into_inner());
+        failure.get_or_insert(error);
+    }
+
+    /// Whether an operation sharing this context failed, keeping its error
+    pub(crate) fn failed(&self) -> bool {
+        self.failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Take the error that stopped the evaluation, if any
+    pub(crate) fn take_failure(&self) -> Option<io::Error> {
+        self.failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Whether `used` bytes exceed the memory budget
+    pub(crate) fn exceeds_budget(&self, used: usize) -> bool {
+        self.memory_budget.is_some_and(|budget| used > budget)
+    }
+
+    pub(crate) fn is_parallel(&self) -> bool {
+        self.threads > 1
+    }
+
+    /// Size of the chunks to split `len` items into so that each thread gets one chunk
+    pub(crate) fn chunk_size(&self, len: usize) -> usize {
+        len.div_ceil(self.threads).max(1)
+    }
+
+    /// Take up to `wanted` of the worker threads no operation is using
+    fn take_workers(&self, wanted: usize) -> Workers {
+        let mut count = 0;
+
+        let _ = self
+            .workers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |free| {
+                count = free.min(wanted);
+                Some(free - count)
+            });
+
+        Workers {
+            count,
+            workers: self.workers.clone(),
+        }
+    }
+
+    /**
+     * Apply `f` to each of `items` on worker threads, keeping their order.
+     *
+     * All operations of a plan share the number of threads of the context, so that operations
+     * evaluated on worker threads of other operations only use the threads that are left. If
+     * fewer than two are left, the items are mapped on the current thread. A worker thread that
+     * panics is reported as an error.
+     */
+    pub(crate) fn map_parallel<T, R, F>(&self, items: Vec<T>, f: F) -> io::Result<Vec<R>>
+    where
+        T: Send,
+        R: Send,
+        F: FnMut(T) -> R + Send + Clone,
+    {
+        let workers = self.take_workers(items.len());
+
+        if workers.count < 2 {
+            return Ok(items.into_iter().map(f).collect());
+        }
+
+        log::debug!("Mapping {} items on {} threads", items.len(), workers.count);
+
+        dpc_pariter::scope(|scope| {
+            items
+                .into_iter()
+                .parallel_map_scoped(scope, f)
+                .threads(workers.count)
+                .collect()
+        })
+        .map_err(|_| io::Error::other("Worker thread panicked"))
+    }
+}
+
+/// Worker threads taken by an operation, given back once it no longer uses them
+struct Workers {
+    count: usize,
+    workers: Arc<AtomicUsize>,
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.workers.fetch_add(self.count, Ordering::SeqCst);
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            workers: Arc::new(AtomicUsize::new(1)),
+            pipelined: false,
+            memory_budget: None,
+            analyze: false,
+            timeout: None,
+            deadline: Arc::new(OnceLock::new()),
+            timed_out: Arc::new(AtomicBool::new(false)),
+            failure: Arc::new(Mutex::new(None)),
+        }
+    }
+}
@@ -2,13 +2,16 @@ use std::fmt::{self, Display};
 
 use crate::{
     semantics::{
+        batch::{Batch, BatchIterator},
         mapping::Mapping,
+        sampling::Sampler,
         selectivity::{Selectivity, SelectivityResult},
     },
-    syntax::{database::Summary, query},
+    syntax::{database::Summary, dictionary::Dictionary, query},
 };
 
 use super::{
+    stats::Stats,
     visitors::{condition::ConditionInfo, printer::Printer},
     Operation, OperationVisitor,
 };
@@ -17,6 +20,7 @@ use super::{
 pub(crate) struct Filter<O> {
     pub(crate) operation: Box<O>,
     pub(crate) condition: Box<query::Condition>,
+    pub(super) stats: Stats,
 }
 
 impl<'a> Filter<Operation<'a>> {
@@ -24,6 +28,7 @@ impl<'a> Filter<Operation<'a>> {
         Self {
             operation: Box::new(operation),
             condition: Box::new(condition),
+            stats: Stats::default(),
         }
     }
 }
@@ -42,6 +47,20 @@ impl<O: Iterator<Item = Mapping>> Iterator for Filter<O> {
     }
 }
 
+impl<O: BatchIterator> BatchIterator for Filter<O> {
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        while let Some(batch) = self.operation.next_batch(dictionary) {
+            let result = batch.select(|row| batch.satisfies(&self.condition, row, dictionary));
+
+            if !result.is_empty() {
+                return Some(result);
+            }
+        }
+
+        None
+    }
+}
+
 impl<O: Selectivity> Selectivity for Filter<O> {
     fn sel_vc(&self) -> SelectivityResult {
         self.operation.sel_vc()
@@ -66,4 +85,12 @@ impl<O: Selectivity> Selectivity for Filter<O> {
     fn sel_pfjc(&self, s: &Summary, i: &ConditionInfo) -> SelectivityResult {
         self.operation.sel_pfjc(s, i)
     }
+
+    fn sel_cs(&self, s: &Summary) -> SelectivityResult {
+        self.operation.sel_cs(s)
+    }
+
+    fn sel_sampling(&self, s: &Sampler) -> SelectivityResult {
+        self.operation.sel_sampling(s)
+    }
 }
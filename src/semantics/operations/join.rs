@@ -1,19 +1,31 @@
 use core::fmt;
-use std::{collections::HashMap, fmt::Display, hash::Hash};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    io,
+};
 
 use crate::{
     semantics::{
+        batch::{self, Batch, BatchIterator, HashedBatch},
         mapping::{Mapping, MappingSet},
+        sampling::Sampler,
         selectivity::{Selectivity, SelectivityError, SelectivityResult},
+        spill::{SpillFile, SpillReader, SpillWriter},
     },
     syntax::{
         database,
+        dictionary::{Dictionary, UNBOUND},
         query::{self, Object, Predicate, Subject},
     },
 };
 use iter_progress::ProgressableIter;
 
 use super::{
+    context::Context,
+    scan::Scan,
+    stats::Stats,
     visitors::{condition::ConditionInfo, printer::Printer},
     Operation, OperationVisitor,
 };
@@ -25,6 +37,15 @@ pub(crate) struct Join<O: Display> {
     pub(super) join_vars: query::Variables,
     hashes: HashMap<String, MappingSet>,
     current_bucket: MappingSet,
+    ctx: Context,
+    build: Option<HashedBatch>,
+    right_hashes: HashMap<String, MappingSet>,
+    left_done: bool,
+    right_done: bool,
+    from_left: bool,
+    used: usize,
+    grace: Option<GraceJoin>,
+    pub(super) stats: Stats,
 }
 
 impl<O: Hash + Display> Hash for Join<O> {
@@ -54,8 +75,21 @@ impl<'a> Join<Operation<'a>> {
             join_vars,
             hashes: HashMap::new(),
             current_bucket: Vec::new(),
+            ctx: Context::new(),
+            build: None,
+            right_hashes: HashMap::new(),
+            left_done: false,
+            right_done: false,
+            from_left: true,
+            used: 0,
+            grace: None,
+            stats: Stats::default(),
         }
     }
+
+    pub(crate) fn with_context(self, ctx: Context) -> Self {
+        Self { ctx, ..self }
+    }
 }
 
 impl<O: Display + Eq> Eq for Join<O> {}
@@ -71,6 +105,463 @@ impl<'a> fmt::Display for Join<Operation<'a>> {
     }
 }
 
+fn merge(left: &Mapping, right: &Mapping) -> Mapping {
+    let mut next = Mapping::new();
+    for (k, v) in left.items.iter() {
+        next.insert(k.clone(), v.clone());
+    }
+    for (k, v) in right.items.iter() {
+        next.insert(k.clone(), v.clone());
+    }
+    next
+}
+
+/// Split a set of mappings into partitions of at most `size` mappings, keeping their order
+fn partition(mappings: MappingSet, size: usize) -> Vec<MappingSet> {
+    let mut partitions = Vec::new();
+    let mut iter = mappings.into_iter();
+
+    loop {
+        let partition: MappingSet = iter.by_ref().take(size).collect();
+
+        if partition.is_empty() {
+            break partitions;
+        }
+
+        partitions.push(partition);
+    }
+}
+
+/// Number of partitions a join is split into when it exceeds the memory budget
+const PARTITIONS: usize = 16;
+
+/// Number of times a partition that still exceeds the memory budget is split again
+const MAX_SPLITS: u32 = 4;
+
+/// Partition of a join key, hashed differently for each number of times it was split before
+fn partition_of(key: &str, splits: u32) -> usize {
+    let mut hasher = DefaultHasher::new();
+    splits.hash(&mut hasher);
+    key.hash(&mut hasher);
+    (hasher.finish() % PARTITIONS as u64) as usize
+}
+
+/// Spill mappings to disk, split into partitions by the hash of their join key
+fn spill_partitioned(
+    mappings: impl Iterator<Item = io::Result<Mapping>>,
+    join_vars: &query::Variables,
+    splits: u32,
+) -> io::Result<Vec<SpillFile>> {
+    let mut writers = (0..PARTITIONS)
+        .map(|_| SpillWriter::new())
+        .collect::<io::Result<Vec<SpillWriter>>>()?;
+
+    for m in mappings {
+        let m = m?;
+        writers[partition_of(&m.hash_map_key(join_vars), splits)].write(&m)?;
+    }
+
+    writers.into_iter().map(SpillWriter::finish).collect()
+}
+
+/// A pair of partitions of both inputs of a grace hash join, with the same join keys
+#[derive(Debug, Clone)]
+struct Partition {
+    left: SpillFile,
+    right: SpillFile,
+    /// Number of times the mappings of the partition were split into partitions before
+    splits: u32,
+}
+
+/**
+ * State of a grace hash join.
+ *
+ * Both inputs are split into partitions by the hash of their join key and spilled to disk.
+ * Matching mappings always end up in partitions with the same number, so the partitions are
+ * joined pair by pair with only one partition of the left input in memory at a time.
+ *
+ * A pair whose left partition still exceeds the memory budget is split again with another hash.
+ * Once that no longer makes it smaller, because most of its mappings share one join key, the left
+ * partition is read in blocks that fit into the budget and the right partition is read once per
+ * block.
+ */
+#[derive(Debug, Clone)]
+struct GraceJoin {
+    join_vars: query::Variables,
+    budget: usize,
+    /// Pairs of partitions that are still to be joined
+    pending: Vec<Partition>,
+    hashes: HashMap<String, MappingSet>,
+    probe: Option<SpillReader>,
+    /// Rest of a left partition read in blocks, with the right partition to join each block with
+    blocks: Option<(SpillReader, SpillFile)>,
+    bucket: MappingSet,
+}
+
+impl GraceJoin {
+    fn new(join_vars: query::Variables, budget: usize) -> Self {
+        Self {
+            join_vars,
+            budget,
+            pending: Vec::new(),
+            hashes: HashMap::new(),
+            probe: None,
+            blocks: None,
+            bucket: Vec::new(),
+        }
+    }
+
+    /// Spill the mappings of two inputs to join, which are joined after those spilled before
+    fn add(
+        &mut self,
+        left: impl Iterator<Item = io::Result<Mapping>>,
+        right: impl Iterator<Item = io::Result<Mapping>>,
+    ) -> io::Result<()> {
+        let left = spill_partitioned(left, &self.join_vars, 0)?;
+        let right = spill_partitioned(right, &self.join_vars, 0)?;
+
+        let partitions = left
+            .into_iter()
+            .zip(right)
+            .map(|(left, right)| Partition {
+                left,
+                right,
+                splits: 0,
+            })
+            .rev()
+            .collect::<Vec<_>>();
+
+        self.pending.splice(0..0, partitions);
+
+        Ok(())
+    }
+
+    /**
+     * Read mappings into the hash table until they exceed the memory budget.
+     *
+     * Returns whether the reader has mappings left.
+     */
+    fn load(&mut self, reader: &mut SpillReader) -> io::Result<bool> {
+        self.hashes = HashMap::new();
+        let mut used = 0;
+
+        while let Some(m) = reader.next().transpose()? {
+            // Only stop once another mapping is left, all others are already in memory
+            let exceeded = used > self.budget;
+            used += m.estimated_size();
+
+            self.hashes
+                .entry(m.hash_map_key(&self.join_vars))
+                .or_default()
+                .push(m);
+
+            if exceeded {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Split both partitions of a pair again, with another hash of the join keys
+    fn split(&mut self, partition: Partition) -> io::Result<()> {
+        let splits = partition.splits + 1;
+
+        log::debug!(
+            "Splitting partition with {} left mappings again",
+            partition.left.len()
+        );
+
+        let left = spill_partitioned(partition.left.reader(), &self.join_vars, splits)?;
+        let right = spill_partitioned(partition.right.reader(), &self.join_vars, splits)?;
+
+        for (left, right) in left.into_iter().zip(right).rev() {
+            // Splitting again does not help if all mappings ended up in the same partition
+            let splits = if left.len() == partition.left.len() {
+                MAX_SPLITS
+            } else {
+                splits
+            };
+
+            self.pending.push(Partition {
+                left,
+                right,
+                splits,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn next_result(&mut self) -> io::Result<Option<Mapping>> {
+        loop {
+            if let Some(result) = self.bucket.pop() {
+                return Ok(Some(result));
+            }
+
+            if let Some(m) = self.probe.as_mut().and_then(|probe| probe.next()) {
+                let m = m?;
+
+                if let Some(bucket) = self.hashes.get(&m.hash_map_key(&self.join_vars)) {
+                    self.bucket = bucket.iter().map(|other| merge(&m, other)).collect();
+                }
+
+                continue;
+            }
+
+            self.probe = None;
+
+            if let Some((mut left, right)) = self.blocks.take() {
+                let remaining = self.load(&mut left)?;
+
+                if !self.hashes.is_empty() {
+                    self.probe = Some(right.reader());
+                }
+
+                if remaining {
+                    self.blocks = Some((left, right));
+                }
+
+                continue;
+            }
+
+            let Some(partition) = self.pending.pop() else {
+                return Ok(None);
+            };
+
+            if partition.left.len() == 0 || partition.right.len() == 0 {
+                continue;
+            }
+
+            log::debug!(
+                "Joining partition with {} left and {} right mappings",
+                partition.left.len(),
+                partition.right.len()
+            );
+
+            let mut left = partition.left.reader();
+
+            if !self.load(&mut left)? {
+                self.probe = Some(partition.right.reader());
+            } else if partition.splits < MAX_SPLITS {
+                self.hashes = HashMap::new();
+                self.split(partition)?;
+            } else {
+                log::debug!("Joining partition in blocks that fit into the memory budget");
+
+                self.probe = Some(partition.right.reader());
+                self.blocks = Some((left, partition.right));
+            }
+        }
+    }
+}
+
+impl<O> Join<O>
+where
+    O: Iterator<Item = Mapping>,
+    O: Display,
+{
+    /**
+     * Evaluate the whole join with multiple threads.
+     *
+     * The left input is split into partitions which are hashed in parallel and merged into one
+     * hash table afterwards. The right input is then split into partitions as well and probed
+     * against the hash table in parallel. Results are produced in the same order as the
+     * sequential join would produce them.
+     */
+    fn join_parallel(&mut self) -> io::Result<()> {
+        let join_vars = &self.join_vars;
+
+        let left: MappingSet = self.left.by_ref().collect();
+        let size = self.ctx.chunk_size(left.len());
+
+        log::debug!(
+            "Building hash table for {} mappings in parallel",
+            left.len()
+        );
+
+        let tables = self.ctx.map_parallel(partition(left, size), |partition| {
+            let mut table: HashMap<String, MappingSet> = HashMap::new();
+
+            for m in partition {
+                table.entry(m.hash_map_key(join_vars)).or_default().push(m);
+            }
+
+            table
+        })?;
+
+        let mut hashes: HashMap<String, MappingSet> = HashMap::new();
+        for table in tables {
+            for (key, mut bucket) in table {
+                hashes.entry(key).or_default().append(&mut bucket);
+            }
+        }
+
+        log::debug!("Hash table has {} entries", hashes.len());
+
+        let right: MappingSet = self.right.by_ref().collect();
+        let size = self.ctx.chunk_size(right.len());
+
+        log::debug!(
+            "Probing hash table with {} mappings in parallel",
+            right.len()
+        );
+
+        let results = self.ctx.map_parallel(partition(right, size), |partition| {
+            let mut results = MappingSet::new();
+
+            for m in partition {
+                if let Some(bucket) = hashes.get(&m.hash_map_key(join_vars)) {
+                    // The sequential join yields each bucket back to front
+                    results.extend(bucket.iter().rev().map(|other| merge(&m, other)));
+                }
+            }
+
+            results
+        })?;
+
+        self.current_bucket = results.into_iter().flatten().collect();
+        self.current_bucket.reverse();
+        self.hashes = hashes;
+
+        Ok(())
+    }
+
+    /// Produce the next result of a join spilled to disk
+    fn next_spilled(&mut self) -> Option<Mapping> {
+        let grace = self.grace.as_mut()?;
+
+        grace.next_result().unwrap_or_else(|e| {
+            self.ctx.fail(e);
+            None
+        })
+    }
+
+    /**
+     * Continue a symmetric hash join whose hash tables exceed the memory budget as a grace hash
+     * join.
+     *
+     * The results of the mappings read so far were already produced, so the mappings read from
+     * each input are only joined with the rest of the other input. The rest of the left input
+     * is spilled to disk first, as it is joined with both parts of the right input.
+     */
+    fn spill_pipelined(&mut self) -> io::Result<GraceJoin> {
+        let mut writer = SpillWriter::new()?;
+
+        for m in self.left.by_ref() {
+            writer.write(&m)?;
+        }
+
+        let rest = writer.finish()?;
+
+        let mut grace = GraceJoin::new(
+            self.join_vars.clone(),
+            self.ctx.memory_budget.unwrap_or_default(),
+        );
+
+        let left = std::mem::take(&mut self.hashes)
+            .into_values()
+            .flatten()
+            .map(Ok)
+            .chain(rest.reader());
+
+        grace.add(left, self.right.by_ref().map(Ok))?;
+
+        let right = std::mem::take(&mut self.right_hashes)
+            .into_values()
+            .flatten()
+            .map(Ok);
+
+        grace.add(rest.reader(), right)?;
+
+        Ok(grace)
+    }
+
+    /**
+     * Produce the next result of a symmetric hash join.
+     *
+     * Mappings are read from both inputs in turn. Each mapping is probed against the hash table
+     * of the other input and then added to the hash table of its own input, so that a result is
+     * returned as soon as both of its mappings have been read. Once one input is exhausted, the
+     * hash table of the other input is no longer needed and dropped. Once both hash tables exceed
+     * the memory budget, the rest of the join is spilled to disk.
+     */
+    fn next_pipelined(&mut self) -> Option<Mapping> {
+        loop {
+            if let Some(result) = self.current_bucket.pop() {
+                return Some(result);
+            }
+
+            if self.grace.is_some() {
+                return self.next_spilled();
+            }
+
+            if self.left_done && self.right_done {
+                return None;
+            }
+
+            let from_left = !self.left_done && (self.right_done || self.from_left);
+            self.from_left = !from_left;
+
+            if from_left {
+                if let Some(m) = self.left.next() {
+                    let key = m.hash_map_key(&self.join_vars);
+
+                    if let Some(bucket) = self.right_hashes.get(&key) {
+                        self.current_bucket = bucket.iter().map(|other| merge(other, &m)).collect();
+                    }
+
+                    if !self.right_done {
+                        if self.ctx.memory_budget.is_some() {
+                            self.used += m.estimated_size();
+                        }
+
+                        self.hashes.entry(key).or_default().push(m);
+                    }
+                } else {
+                    log::debug!("Left input of pipelined join exhausted");
+
+                    self.left_done = true;
+                    self.right_hashes = HashMap::new();
+                }
+            } else if let Some(m) = self.right.next() {
+                let key = m.hash_map_key(&self.join_vars);
+
+                if let Some(bucket) = self.hashes.get(&key) {
+                    self.current_bucket = bucket.iter().map(|other| merge(&m, other)).collect();
+                }
+
+                if !self.left_done {
+                    if self.ctx.memory_budget.is_some() {
+                        self.used += m.estimated_size();
+                    }
+
+                    self.right_hashes.entry(key).or_default().push(m);
+                }
+            } else {
+                log::debug!("Right input of pipelined join exhausted");
+
+                self.right_done = true;
+                self.hashes = HashMap::new();
+            }
+
+            if self.ctx.exceeds_budget(self.used) {
+                log::info!("Hash tables exceed memory budget, spilling pipelined join to disk");
+
+                self.used = 0;
+
+                match self.spill_pipelined() {
+                    Ok(grace) => self.grace = Some(grace),
+                    Err(e) => {
+                        self.ctx.fail(e);
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
 impl<O> Iterator for Join<O>
 where
     O: Iterator<Item = Mapping>,
@@ -81,10 +572,65 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         log::trace!("Join next()");
 
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        if self.ctx.pipelined {
+            return self.next_pipelined();
+        }
+
+        // The parallel join does not keep to the memory budget
+        if self.ctx.is_parallel() && self.ctx.memory_budget.is_none() {
+            if self.hashes.is_empty() && self.current_bucket.is_empty() {
+                if let Err(e) = self.join_parallel() {
+                    self.ctx.fail(e);
+                    return None;
+                }
+            }
+
+            return self.current_bucket.pop();
+        }
+
+        if self.grace.is_some() {
+            return self.next_spilled();
+        }
+
         if self.hashes.is_empty() {
             log::debug!("Building hash table");
 
-            for m in self.left.by_ref() {
+            let mut used = 0;
+
+            while let Some(m) = self.left.next() {
+                if self.ctx.memory_budget.is_some() {
+                    used += m.estimated_size();
+                }
+
+                if self.ctx.exceeds_budget(used) {
+                    log::info!("Hash table exceeds memory budget, spilling join to disk");
+
+                    let left = std::mem::take(&mut self.hashes)
+                        .into_values()
+                        .flatten()
+                        .chain(std::iter::once(m))
+                        .chain(self.left.by_ref());
+
+                    let mut grace = GraceJoin::new(
+                        self.join_vars.clone(),
+                        self.ctx.memory_budget.unwrap_or_default(),
+                    );
+
+                    match grace.add(left.map(Ok), self.right.by_ref().map(Ok)) {
+                        Ok(()) => self.grace = Some(grace),
+                        Err(e) => {
+                            self.ctx.fail(e);
+                            return None;
+                        }
+                    }
+
+                    return self.next();
+                }
+
                 let key = m.hash_map_key(&self.join_vars);
 
                 if let Some(v) = self.hashes.get_mut(&key) {
@@ -121,14 +667,7 @@ where
                             );
                         });
 
-                        let mut next = Mapping::new();
-                        for (k, v) in m.items.iter() {
-                            next.insert(k.clone(), v.clone());
-                        }
-                        for (k, v) in other.items.iter() {
-                            next.insert(k.clone(), v.clone());
-                        }
-                        next
+                        merge(&m, other)
                     })
                     .collect::<MappingSet>();
             } else {
@@ -148,6 +687,67 @@ where
     }
 }
 
+impl<O> BatchIterator for Join<O>
+where
+    O: BatchIterator,
+    O: Display,
+{
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        if self.build.is_none() {
+            let mut left = Batch::default();
+            while let Some(batch) = self.left.next_batch(dictionary) {
+                left.append(batch);
+            }
+
+            log::debug!("Building hash table for batch of {} rows", left.len());
+
+            self.build = Some(HashedBatch::new(left, &self.join_vars));
+        }
+
+        let build = self.build.as_ref()?;
+
+        while let Some(probe) = self.right.next_batch(dictionary) {
+            let mut vars = probe.vars().clone();
+            for var in build.batch.vars() {
+                if !vars.contains(var) {
+                    vars.push(var.to_owned());
+                }
+            }
+
+            let columns: Vec<_> = vars
+                .iter()
+                .map(|var| (build.batch.column(var), probe.column(var)))
+                .collect();
+
+            let mut result = Batch::new(vars);
+
+            for row in 0..probe.len() {
+                let key = batch::key(&probe, &self.join_vars, row);
+
+                if let Some(matches) = build.table.get(&key) {
+                    // Same order as the row-at-a-time join, which yields each bucket back to front
+                    for other in matches.iter().rev() {
+                        result.push(columns.iter().map(|(left, right)| {
+                            match left.map(|c| c[*other]).unwrap_or(UNBOUND) {
+                                UNBOUND => right.map(|c| c[row]).unwrap_or(UNBOUND),
+                                term => term,
+                            }
+                        }));
+                    }
+                }
+            }
+
+            log::trace!("Join next_batch() returns {} rows", result.len());
+
+            if !result.is_empty() {
+                return Some(result);
+            }
+        }
+
+        None
+    }
+}
+
 pub(crate) enum JoinType {
     SubjectSubject,
     SubjectObject,
@@ -209,43 +809,41 @@ impl<'a> Join<Operation<'a>> {
     }
 }
 
-impl<'a> Selectivity for Join<Operation<'a>> {
-    fn sel_vc(&self) -> SelectivityResult {
-        let factor: f64 = self
-            .join_type()
-            .iter()
-            .map(|t| match t {
-                JoinType::SubjectPredicate => 0.25,
-
-                JoinType::SubjectSubject => 0.5,
-
-                JoinType::ObjectObject => 0.75,
-                JoinType::SubjectObject => 0.75,
-
-                JoinType::UnboundOrHigherJoin => 1.0,
-            })
-            .fold(1.0, |a, b| a.min(b));
-
-        Ok(factor * self.left.sel_vc()? * self.right.sel_vc()?)
-    }
-
-    fn sel_vcp(&self) -> SelectivityResult {
-        if let (Operation::Scan(l), Operation::Scan(r)) = (self.left.as_ref(), self.right.as_ref())
-        {
-            if let (Subject::V(_), Subject::V(_)) = (&l.subject, &r.subject) {
-                if match l.object {
-                    Object::L(_) | Object::I(_) => matches!(r.object, Object::L(_) | Object::I(_)),
-                    _ => false,
-                } {
-                    return Ok(1.0);
+impl<'a> Join<Operation<'a>> {
+    /// Collect the scans of this join, looking through filters, if it only joins scans
+    fn conjunctive<'j>(&'j self, scans: &mut Vec<&'j Scan<'a>>) -> bool {
+        fn collect<'j, 'a>(o: &'j Operation<'a>, scans: &mut Vec<&'j Scan<'a>>) -> bool {
+            match o {
+                Operation::Scan(scan) => {
+                    scans.push(scan);
+                    true
                 }
+                Operation::Join(join) => join.conjunctive(scans),
+                Operation::Filter(filter) => collect(&filter.operation, scans),
+                _ => false,
             }
         }
 
-        self.sel_vc()
+        collect(&self.left, scans) && collect(&self.right, scans)
     }
 
-    fn sel_pf(&self, s: &database::Summary) -> SelectivityResult {
+    /// Collect the scans of this join if all of them share the same subject
+    fn star<'j>(&'j self, scans: &mut Vec<&'j Scan<'a>>) -> bool {
+        [&*self.left, &*self.right].into_iter().all(|o| match o {
+            Operation::Scan(scan) => {
+                scans.push(scan);
+                scans[0].subject == scan.subject
+            }
+            Operation::Join(join) => join.star(scans),
+            _ => false,
+        })
+    }
+}
+
+impl<'a> Join<Operation<'a>> {
+    /// Selectivity of joining two scans, using the joined triples for their join type if `typed`
+    /// is set and the subject-subject joined triples otherwise
+    fn pf(&self, s: &database::Summary, typed: bool) -> SelectivityResult {
         let leftscan = if let Operation::Scan(leftscan) = &*self.left {
             leftscan
         } else {
@@ -292,10 +890,16 @@ impl<'a> Selectivity for Join<Operation<'a>> {
             query::Subject::V(_) => 1.0,
         });
 
-        Ok(s.s_p(p1, p2) / (s.t() * s.t()) * factors.iter().product::<f64>())
+        let joined = if typed {
+            self.joined(s, leftscan, rightscan, p1, p2)
+        } else {
+            s.s_p(p1, p2)
+        };
+
+        Ok(joined / (s.t() * s.t()) * factors.iter().product::<f64>())
     }
 
-    fn sel_pfc(&self, s: &database::Summary, i: &ConditionInfo) -> SelectivityResult {
+    fn pfc(&self, s: &database::Summary, i: &ConditionInfo, typed: bool) -> SelectivityResult {
         let leftscan = if let Operation::Scan(leftscan) = &*self.left {
             leftscan
         } else {
@@ -333,10 +937,97 @@ impl<'a> Selectivity for Join<Operation<'a>> {
             }
             query::Object::V(_) => 1.0,
         });
-        factors.push(leftscan.sel_pfc(s, i)?);
-        factors.push(rightscan.sel_pfc(s, i)?);
 
-        Ok(s.s_p(p1, p2) / (s.t() * s.t()) * factors.iter().product::<f64>())
+        // Filters on both objects of a star are estimated together if their values are correlated
+        match leftscan.correlation(rightscan, s, i) {
+            Some(fraction) => {
+                factors.push(leftscan.sel_pf(s)?);
+                factors.push(rightscan.sel_pf(s)?);
+                factors.push(fraction);
+            }
+            None => {
+                factors.push(leftscan.sel_pfc(s, i)?);
+                factors.push(rightscan.sel_pfc(s, i)?);
+            }
+        }
+
+        let joined = if typed {
+            self.joined(s, leftscan, rightscan, p1, p2)
+        } else {
+            s.s_p(p1, p2)
+        };
+
+        Ok(joined / (s.t() * s.t()) * factors.iter().product::<f64>())
+    }
+
+    /// Number of joined triples for the join type of two scans with bound predicates, if a join
+    /// has more than one type the smallest estimate is used
+    fn joined(
+        &self,
+        s: &database::Summary,
+        left: &Scan<'a>,
+        right: &Scan<'a>,
+        p1: database::Predicate,
+        p2: database::Predicate,
+    ) -> f64 {
+        self.join_type()
+            .iter()
+            .map(|t| match t {
+                JoinType::SubjectObject => match (&left.subject, &right.object) {
+                    (Subject::V(l), Object::V(r)) if l == r => s.s_o(p1.clone(), p2.clone()),
+                    _ => s.s_o(p2.clone(), p1.clone()),
+                },
+                JoinType::ObjectObject => s.o_o(p1.clone(), p2.clone()),
+                JoinType::SubjectSubject
+                | JoinType::SubjectPredicate
+                | JoinType::UnboundOrHigherJoin => s.s_p(p1.clone(), p2.clone()),
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+impl<'a> Selectivity for Join<Operation<'a>> {
+    fn sel_vc(&self) -> SelectivityResult {
+        let factor: f64 = self
+            .join_type()
+            .iter()
+            .map(|t| match t {
+                JoinType::SubjectPredicate => 0.25,
+
+                JoinType::SubjectSubject => 0.5,
+
+                JoinType::ObjectObject => 0.75,
+                JoinType::SubjectObject => 0.75,
+
+                JoinType::UnboundOrHigherJoin => 1.0,
+            })
+            .fold(1.0, |a, b| a.min(b));
+
+        Ok(factor * self.left.sel_vc()? * self.right.sel_vc()?)
+    }
+
+    fn sel_vcp(&self) -> SelectivityResult {
+        if let (Operation::Scan(l), Operation::Scan(r)) = (self.left.as_ref(), self.right.as_ref())
+        {
+            if let (Subject::V(_), Subject::V(_)) = (&l.subject, &r.subject) {
+                if match l.object {
+                    Object::L(_) | Object::I(_) => matches!(r.object, Object::L(_) | Object::I(_)),
+                    _ => false,
+                } {
+                    return Ok(1.0);
+                }
+            }
+        }
+
+        self.sel_vc()
+    }
+
+    fn sel_pf(&self, s: &database::Summary) -> SelectivityResult {
+        self.pf(s, false)
+    }
+
+    fn sel_pfc(&self, s: &database::Summary, i: &ConditionInfo) -> SelectivityResult {
+        self.pfc(s, i, false)
     }
 
     fn sel_pfj(&self, s: &database::Summary) -> SelectivityResult {
@@ -352,7 +1043,7 @@ impl<'a> Selectivity for Join<Operation<'a>> {
                 }
             }
 
-            let join = self.sel_pf(s)?;
+            let join = self.pf(s, true)?;
             let left = l.sel_pfj(s)?;
             let right = r.sel_pfj(s)?;
 
@@ -377,7 +1068,7 @@ impl<'a> Selectivity for Join<Operation<'a>> {
                 }
             }
 
-            let join = self.sel_pfc(s, i)?;
+            let join = self.pfc(s, i, true)?;
             let left = l.sel_pfjc(s, i)?;
             let right = r.sel_pfjc(s, i)?;
 
@@ -388,4 +1079,47 @@ impl<'a> Selectivity for Join<Operation<'a>> {
 
         Err(SelectivityError::NoSelectivityForJoin)
     }
+
+    fn sel_cs(&self, s: &database::Summary) -> SelectivityResult {
+        let mut scans = Vec::new();
+
+        if !s.has_c_s() || !self.star(&mut scans) {
+            return self.sel_pf(s);
+        }
+
+        let mut predicates = Vec::new();
+        let mut factors = Vec::new();
+
+        for scan in scans.iter() {
+            match &scan.predicate {
+                Predicate::I(i) => predicates.push(database::Predicate::I(i.to_owned())),
+                Predicate::V(_) => return self.sel_pf(s),
+            }
+
+            factors.push((&scan.predicate, &scan.object).sel_pf(s)?);
+        }
+
+        if let Subject::I(_) = scans[0].subject {
+            factors.push(1.0 / s.r());
+        }
+
+        let cardinality = s.c_s(&predicates) * factors.iter().product::<f64>();
+        let result = cardinality / s.t().powi(scans.len() as i32);
+
+        if !result.is_nan() {
+            Ok(result)
+        } else {
+            Err(SelectivityError::EncounteredNaNValue)
+        }
+    }
+
+    fn sel_sampling(&self, s: &Sampler) -> SelectivityResult {
+        let mut scans = Vec::new();
+
+        if !self.conjunctive(&mut scans) {
+            return Err(SelectivityError::NonConjunctiveStructure);
+        }
+
+        s.join(&scans)
+    }
 }
@@ -3,7 +3,8 @@ use std::fmt::{self, Display};
 use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
 
 use super::{
-    join::Join, minus::Minus, union::Union, visitors::printer::Printer, Operation, OperationVisitor,
+    join::Join, minus::Minus, stats::Stats, union::Union, visitors::printer::Printer, Operation,
+    OperationVisitor,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -11,6 +12,7 @@ pub(crate) struct LeftJoin<O> {
     pub(crate) operation: Box<O>,
     pub(crate) left: Box<O>,
     pub(crate) right: Box<O>,
+    pub(super) stats: Stats,
 }
 
 impl<'a> LeftJoin<Operation<'a>> {
@@ -24,6 +26,7 @@ impl<'a> LeftJoin<Operation<'a>> {
             operation,
             left: Box::new(left),
             right: Box::new(right),
+            stats: Stats::default(),
         }
     }
 }
@@ -2,13 +2,14 @@ use std::fmt;
 
 use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
 
-use super::{visitors::printer::Printer, Operation, OperationVisitor};
+use super::{stats::Stats, visitors::printer::Printer, Operation, OperationVisitor};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct Limit<O> {
     pub(crate) operation: Box<O>,
     pub(crate) limit: usize,
     current: usize,
+    pub(super) stats: Stats,
 }
 
 impl<O> Limit<O> {
@@ -17,6 +18,7 @@ impl<O> Limit<O> {
             operation: Box::new(operation),
             limit,
             current: 0,
+            stats: Stats::default(),
         }
     }
 }
@@ -0,0 +1,108 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
+
+use super::{stats::Stats, visitors::printer::Printer, Operation, OperationVisitor};
+
+/**
+ * Results of an operation that was evaluated while optimizing the plan.
+ *
+ * The evaluated operation is kept, with the statistics of its evaluation, so that the plan can
+ * still be printed and explained as a whole.
+ */
+#[derive(Debug, Clone)]
+pub(crate) struct Materialized<O> {
+    pub(crate) operation: Box<O>,
+    /// Number of results the optimizer estimated for the operation before evaluating it, if it
+    /// was materialized by the optimizer
+    pub(crate) estimated: Option<f64>,
+    rows: Arc<Vec<Mapping>>,
+    current: usize,
+    pub(super) stats: Stats,
+}
+
+impl<O> Materialized<O> {
+    pub(crate) fn new(operation: O, rows: Vec<Mapping>, estimated: Option<f64>) -> Self {
+        Self {
+            operation: Box::new(operation),
+            estimated,
+            rows: Arc::new(rows),
+            current: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+/**
+ * All results of `operation`, or `None` as soon as they take more than `budget` bytes together with
+ * the `used` bytes of results that were materialized before. `used` grows by the size of the
+ * results only if they are returned.
+ */
+pub(crate) fn collect_within(
+    operation: impl Iterator<Item = Mapping>,
+    budget: Option<usize>,
+    used: &mut usize,
+) -> Option<Vec<Mapping>> {
+    let mut rows = Vec::new();
+    let mut size = 0;
+
+    for m in operation {
+        if let Some(budget) = budget {
+            size += m.estimated_size();
+
+            if *used + size > budget {
+                return None;
+            }
+        }
+
+        rows.push(m);
+    }
+
+    *used += size;
+
+    Some(rows)
+}
+
+impl<O: PartialEq> Eq for Materialized<O> {}
+impl<O: PartialEq> PartialEq for Materialized<O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.operation == other.operation
+            && self.estimated.map(f64::to_bits) == other.estimated.map(f64::to_bits)
+            && self.rows == other.rows
+    }
+}
+
+impl<O: Hash> Hash for Materialized<O> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.operation.hash(state);
+        self.estimated.map(f64::to_bits).hash(state);
+        self.rows.len().hash(state);
+    }
+}
+
+impl<'a> fmt::Display for Materialized<Operation<'a>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Printer::new().visit_materialized(self))
+    }
+}
+
+impl<O> Iterator for Materialized<O> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.rows.get(self.current).cloned();
+        self.current += 1;
+
+        next
+    }
+}
+
+impl<O> Selectivity for Materialized<O> {}
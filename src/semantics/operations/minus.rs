@@ -1,15 +1,26 @@
 use std::fmt::{self, Display};
 use std::hash::Hash;
+use std::io;
 
-use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
+use crate::semantics::{
+    mapping::{Mapping, MappingSet},
+    selectivity::Selectivity,
+    spill::{SpillFile, SpillWriter},
+};
 
-use super::{visitors::printer::Printer, Operation, OperationVisitor};
+use super::{
+    context::Context, stats::Stats, visitors::printer::Printer, Operation, OperationVisitor,
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct Minus<O> {
     pub(super) left: Box<O>,
     pub(super) right: Box<O>,
     collected: Vec<Mapping>,
+    ctx: Context,
+    spilled: Option<SpillFile>,
+    block: std::vec::IntoIter<Mapping>,
+    pub(super) stats: Stats,
 }
 
 impl<'a> Minus<Operation<'a>> {
@@ -18,8 +29,16 @@ impl<'a> Minus<Operation<'a>> {
             left: Box::new(left),
             right: Box::new(right),
             collected: vec![],
+            ctx: Context::new(),
+            spilled: None,
+            block: Vec::new().into_iter(),
+            stats: Stats::default(),
         }
     }
+
+    pub(crate) fn with_context(self, ctx: Context) -> Self {
+        Self { ctx, ..self }
+    }
 }
 
 impl<'a> fmt::Display for Minus<Operation<'a>> {
@@ -42,18 +61,114 @@ impl<O: PartialEq> PartialEq for Minus<O> {
     }
 }
 
+impl<O: Iterator<Item = Mapping>> Minus<O> {
+    /// Write all mappings collected from the right operation so far and all remaining ones to disk
+    fn spill(&mut self) -> io::Result<()> {
+        log::info!("Rejection list exceeds memory budget, spilling minus to disk");
+
+        let mut writer = SpillWriter::new()?;
+
+        for m in self.collected.drain(..).chain(self.right.by_ref()) {
+            writer.write(&m)?;
+        }
+
+        self.spilled = Some(writer.finish()?);
+
+        Ok(())
+    }
+
+    /**
+     * Produce the next result with a spilled rejection list.
+     *
+     * The left operation is read in blocks that fit into the memory budget. For every block the
+     * spilled mappings are read once, marking each mapping of the block that is compatible with
+     * one of them.
+     */
+    fn next_spilled(&mut self) -> io::Result<Option<Mapping>> {
+        loop {
+            if let Some(next) = self.block.next() {
+                return Ok(Some(next));
+            }
+
+            let mut used = 0;
+            let mut block = MappingSet::new();
+
+            for m in self.left.by_ref() {
+                used += m.estimated_size();
+                block.push(m);
+
+                if self.ctx.exceeds_budget(used) {
+                    break;
+                }
+            }
+
+            if block.is_empty() {
+                return Ok(None);
+            }
+
+            let mut rejected = vec![false; block.len()];
+
+            let Some(spilled) = self.spilled.as_ref() else {
+                return Ok(None);
+            };
+
+            for mr in spilled.reader() {
+                let mr = mr?;
+
+                for (m, rejected) in block.iter().zip(rejected.iter_mut()) {
+                    *rejected = *rejected || m.compatible(&mr);
+                }
+            }
+
+            self.block = block
+                .into_iter()
+                .zip(rejected)
+                .filter(|(_, rejected)| !rejected)
+                .map(|(m, _)| m)
+                .collect::<MappingSet>()
+                .into_iter();
+        }
+    }
+}
+
 impl<O: Iterator<Item = Mapping>> Iterator for Minus<O> {
     type Item = Mapping;
 
     fn next(&mut self) -> Option<Self::Item> {
         log::trace!("Minus next()");
 
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        if self.spilled.is_some() {
+            return self.next_spilled().unwrap_or_else(|e| {
+                self.ctx.fail(e);
+                None
+            });
+        }
+
         if self.collected.is_empty() {
             log::debug!("Building rejection list");
         }
 
-        for next in self.right.by_ref() {
+        let mut used = 0;
+
+        while let Some(next) = self.right.next() {
+            if self.ctx.memory_budget.is_some() {
+                used += next.estimated_size();
+            }
+
             self.collected.push(next);
+
+            if self.ctx.exceeds_budget(used) {
+                if let Err(e) = self.spill() {
+                    self.ctx.fail(e);
+                    return None;
+                }
+
+                return self.next();
+            }
         }
 
         log::trace!("Minus next() with {} to filter", self.collected.len());
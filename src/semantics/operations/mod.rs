@@ -1,35 +1,44 @@
+pub(super) mod context;
 pub(super) mod filter;
 pub(super) mod join;
 pub(super) mod leftjoin;
 pub(super) mod limit;
+pub(super) mod materialized;
 pub(super) mod minus;
 pub(super) mod offset;
 pub(super) mod projection;
 pub(super) mod scan;
+pub(super) mod sort;
+pub(super) mod stats;
 pub(super) mod union;
 pub(super) mod visitors;
 
 use core::fmt;
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Instant};
 
-use crate::syntax::{database, query::Variable};
+use crate::syntax::{database, dictionary::Dictionary, query::Variable};
 
 use self::{
     filter::Filter,
     join::Join,
     leftjoin::LeftJoin,
     limit::Limit,
+    materialized::Materialized,
     minus::Minus,
     offset::Offset,
     projection::Projection,
     scan::Scan,
+    sort::Sort,
+    stats::Stats,
     union::Union,
     visitors::{bound::BoundVars, condition::ConditionInfo, meta::Meta, printer::Printer},
 };
 
 use super::{
-    mapping::Mapping,
+    batch::{Batch, BatchIterator, BATCH_SIZE},
+    mapping::{Mapping, MappingSet},
     results::OperationMeta,
+    sampling::Sampler,
     selectivity::{Selectivity, SelectivityResult},
 };
 
@@ -43,8 +52,10 @@ pub(crate) enum Operation<'a> {
     Filter(Filter<Self>),
     LeftJoin(LeftJoin<Self>),
     Minus(Minus<Self>),
+    Sort(Sort<Self>),
     Offset(Offset<Self>),
     Limit(Limit<Self>),
+    Materialized(Materialized<Self>),
 }
 
 impl<'a> Operation<'a> {
@@ -55,12 +66,76 @@ impl<'a> Operation<'a> {
     pub(crate) fn bound_vars(&self) -> HashSet<Variable> {
         BoundVars::new().visit(self)
     }
-}
 
-impl<'a> Iterator for Operation<'a> {
-    type Item = Mapping;
+    /// Name of the operation, as in explained plans
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Operation::Scan(_) => "SCAN",
+            Operation::Join(_) => "JOIN",
+            Operation::Projection(_) => "PROJECTION",
+            Operation::Union(_) => "UNION",
+            Operation::Filter(_) => "FILTER",
+            Operation::LeftJoin(_) => "LEFTJOIN",
+            Operation::Minus(_) => "MINUS",
+            Operation::Sort(_) => "SORT",
+            Operation::Offset(_) => "OFFSET",
+            Operation::Limit(_) => "LIMIT",
+            Operation::Materialized(_) => "MATERIALIZED",
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Operations whose results this operation consumes
+    pub(crate) fn inputs(&self) -> Vec<&Self> {
+        match self {
+            Operation::Scan(_) => vec![],
+            Operation::Join(j) => vec![&j.left, &j.right],
+            Operation::Projection(p) => vec![&p.operation],
+            Operation::Union(u) => vec![&u.left, &u.right],
+            Operation::Filter(f) => vec![&f.operation],
+            Operation::LeftJoin(l) => vec![&l.operation],
+            Operation::Minus(m) => vec![&m.left, &m.right],
+            Operation::Sort(s) => vec![&s.operation],
+            Operation::Offset(o) => vec![&o.operation],
+            Operation::Limit(l) => vec![&l.operation],
+            Operation::Materialized(m) => vec![&m.operation],
+        }
+    }
+
+    pub(crate) fn stats(&self) -> &Stats {
+        match self {
+            Operation::Scan(s) => &s.stats,
+            Operation::Join(j) => &j.stats,
+            Operation::Projection(p) => &p.stats,
+            Operation::Union(u) => &u.stats,
+            Operation::Filter(f) => &f.stats,
+            Operation::LeftJoin(l) => &l.stats,
+            Operation::Minus(m) => &m.stats,
+            Operation::Sort(s) => &s.stats,
+            Operation::Offset(o) => &o.stats,
+            Operation::Limit(l) => &l.stats,
+            Operation::Materialized(m) => &m.stats,
+        }
+    }
+
+    pub(crate) fn with_stats(mut self, stats: Stats) -> Self {
+        match &mut self {
+            Operation::Scan(s) => s.stats = stats,
+            Operation::Join(j) => j.stats = stats,
+            Operation::Projection(p) => p.stats = stats,
+            Operation::Union(u) => u.stats = stats,
+            Operation::Filter(f) => f.stats = stats,
+            Operation::LeftJoin(l) => l.stats = stats,
+            Operation::Minus(m) => m.stats = stats,
+            Operation::Sort(s) => s.stats = stats,
+            Operation::Offset(o) => o.stats = stats,
+            Operation::Limit(l) => l.stats = stats,
+            Operation::Materialized(m) => m.stats = stats,
+        }
+
+        self
+    }
+
+    fn next_row(&mut self) -> Option<Mapping> {
         match self {
             Operation::Scan(s) => s.next(),
             Operation::Join(j) => j.next(),
@@ -69,12 +144,69 @@ impl<'a> Iterator for Operation<'a> {
             Operation::Filter(f) => f.next(),
             Operation::LeftJoin(o) => o.next(),
             Operation::Minus(m) => m.next(),
+            Operation::Sort(s) => s.next(),
             Operation::Offset(o) => o.next(),
             Operation::Limit(l) => l.next(),
+            Operation::Materialized(m) => m.next(),
         }
     }
 }
 
+impl<'a> Iterator for Operation<'a> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.stats().is_enabled() {
+            return self.next_row();
+        }
+
+        let now = Instant::now();
+        let next = self.next_row();
+
+        self.stats()
+            .record(usize::from(next.is_some()), now.elapsed());
+
+        next
+    }
+}
+
+impl<'a> BatchIterator for Operation<'a> {
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        let now = Instant::now();
+
+        let batch = match self {
+            Operation::Scan(s) => s.next_batch(dictionary),
+            Operation::Join(j) => j.next_batch(dictionary),
+            Operation::Projection(p) => p.next_batch(dictionary),
+            Operation::Union(u) => u.next_batch(dictionary),
+            Operation::Filter(f) => f.next_batch(dictionary),
+            // The remaining operations are evaluated a row at a time and their results encoded
+            Operation::LeftJoin(_)
+            | Operation::Minus(_)
+            | Operation::Sort(_)
+            | Operation::Offset(_)
+            | Operation::Limit(_)
+            | Operation::Materialized(_) => {
+                let rows: MappingSet = self.by_ref().take(BATCH_SIZE).collect();
+
+                // The rows were already recorded by `next`
+                return if rows.is_empty() {
+                    None
+                } else {
+                    Some(Batch::from_mappings(&rows, dictionary))
+                };
+            }
+        };
+
+        self.stats().record(
+            batch.as_ref().map(|b| b.len()).unwrap_or_default(),
+            now.elapsed(),
+        );
+
+        batch
+    }
+}
+
 impl<'a> fmt::Display for Operation<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&Printer::new().visit(self))
@@ -91,8 +223,10 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_vc(),
             Operation::LeftJoin(l) => l.sel_vc(),
             Operation::Minus(m) => m.sel_vc(),
+            Operation::Sort(s) => s.sel_vc(),
             Operation::Offset(o) => o.sel_vc(),
             Operation::Limit(l) => l.sel_vc(),
+            Operation::Materialized(m) => m.sel_vc(),
         }
     }
 
@@ -105,8 +239,10 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_vcp(),
             Operation::LeftJoin(l) => l.sel_vcp(),
             Operation::Minus(m) => m.sel_vcp(),
+            Operation::Sort(s) => s.sel_vcp(),
             Operation::Offset(o) => o.sel_vcp(),
             Operation::Limit(l) => l.sel_vcp(),
+            Operation::Materialized(m) => m.sel_vcp(),
         }
     }
 
@@ -119,8 +255,10 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_pf(summary),
             Operation::LeftJoin(l) => l.sel_pf(summary),
             Operation::Minus(m) => m.sel_pf(summary),
+            Operation::Sort(s) => s.sel_pf(summary),
             Operation::Offset(o) => o.sel_pf(summary),
             Operation::Limit(l) => l.sel_pf(summary),
+            Operation::Materialized(m) => m.sel_pf(summary),
         }
     }
 
@@ -133,8 +271,10 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_pfc(summary, info),
             Operation::LeftJoin(l) => l.sel_pfc(summary, info),
             Operation::Minus(m) => m.sel_pfc(summary, info),
+            Operation::Sort(s) => s.sel_pfc(summary, info),
             Operation::Offset(o) => o.sel_pfc(summary, info),
             Operation::Limit(l) => l.sel_pfc(summary, info),
+            Operation::Materialized(m) => m.sel_pfc(summary, info),
         }
     }
 
@@ -147,8 +287,10 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_pfj(summary),
             Operation::LeftJoin(l) => l.sel_pfj(summary),
             Operation::Minus(m) => m.sel_pfj(summary),
+            Operation::Sort(s) => s.sel_pfj(summary),
             Operation::Offset(o) => o.sel_pfj(summary),
             Operation::Limit(l) => l.sel_pfj(summary),
+            Operation::Materialized(m) => m.sel_pfj(summary),
         }
     }
 
@@ -161,14 +303,48 @@ impl<'a> Selectivity for Operation<'a> {
             Operation::Filter(f) => f.sel_pfjc(summary, info),
             Operation::LeftJoin(l) => l.sel_pfjc(summary, info),
             Operation::Minus(m) => m.sel_pfjc(summary, info),
+            Operation::Sort(s) => s.sel_pfjc(summary, info),
             Operation::Offset(o) => o.sel_pfjc(summary, info),
             Operation::Limit(l) => l.sel_pfjc(summary, info),
+            Operation::Materialized(m) => m.sel_pfjc(summary, info),
+        }
+    }
+
+    fn sel_cs(&self, summary: &database::Summary) -> SelectivityResult {
+        match self {
+            Operation::Scan(s) => s.sel_cs(summary),
+            Operation::Join(j) => j.sel_cs(summary),
+            Operation::Projection(p) => p.sel_cs(summary),
+            Operation::Union(u) => u.sel_cs(summary),
+            Operation::Filter(f) => f.sel_cs(summary),
+            Operation::LeftJoin(l) => l.sel_cs(summary),
+            Operation::Minus(m) => m.sel_cs(summary),
+            Operation::Sort(s) => s.sel_cs(summary),
+            Operation::Offset(o) => o.sel_cs(summary),
+            Operation::Limit(l) => l.sel_cs(summary),
+            Operation::Materialized(m) => m.sel_cs(summary),
+        }
+    }
+
+    fn sel_sampling(&self, sampler: &Sampler) -> SelectivityResult {
+        match self {
+            Operation::Scan(s) => s.sel_sampling(sampler),
+            Operation::Join(j) => j.sel_sampling(sampler),
+            Operation::Projection(p) => p.sel_sampling(sampler),
+            Operation::Union(u) => u.sel_sampling(sampler),
+            Operation::Filter(f) => f.sel_sampling(sampler),
+            Operation::LeftJoin(l) => l.sel_sampling(sampler),
+            Operation::Minus(m) => m.sel_sampling(sampler),
+            Operation::Sort(s) => s.sel_sampling(sampler),
+            Operation::Offset(o) => o.sel_sampling(sampler),
+            Operation::Limit(l) => l.sel_sampling(sampler),
+            Operation::Materialized(m) => m.sel_sampling(sampler),
         }
     }
 }
 
 pub(super) trait OperationVisitor<'a, R> {
-    fn visit(&mut self, o: &'a Operation<'a>) -> R {
+    fn visit(&mut self, o: &Operation<'a>) -> R {
         match o {
             Operation::Scan(s) => self.visit_scan(s),
             Operation::Join(j) => self.visit_join(j),
@@ -177,18 +353,22 @@ pub(super) trait OperationVisitor<'a, R> {
             Operation::Filter(f) => self.visit_filter(f),
             Operation::LeftJoin(l) => self.visit_leftjoin(l),
             Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
             Operation::Offset(o) => self.visit_offset(o),
             Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
         }
     }
 
-    fn visit_scan(&mut self, o: &'a Scan) -> R;
-    fn visit_join(&mut self, o: &'a Join<Operation<'a>>) -> R;
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> R;
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> R;
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> R;
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> R;
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> R;
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> R;
-    fn visit_limit(&mut self, o: &'a Limit<Operation<'a>>) -> R;
+    fn visit_scan(&mut self, o: &Scan<'a>) -> R;
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> R;
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> R;
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> R;
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> R;
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> R;
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> R;
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> R;
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> R;
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> R;
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> R;
 }
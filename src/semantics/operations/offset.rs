@@ -2,12 +2,13 @@ use std::fmt;
 
 use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
 
-use super::{visitors::printer::Printer, Operation, OperationVisitor};
+use super::{stats::Stats, visitors::printer::Printer, Operation, OperationVisitor};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct Offset<O> {
     pub(crate) operation: Box<O>,
     pub(crate) offset: usize,
+    pub(super) stats: Stats,
 }
 
 impl<O> Offset<O> {
@@ -15,6 +16,7 @@ impl<O> Offset<O> {
         Self {
             operation: Box::new(operation),
             offset,
+            stats: Stats::default(),
         }
     }
 }
@@ -1,16 +1,28 @@
 use core::fmt::{self, Display};
 
 use crate::{
-    semantics::{mapping::Mapping, selectivity},
-    syntax::{database, query},
+    semantics::{
+        batch::{Batch, BatchIterator},
+        mapping::Mapping,
+        selectivity,
+    },
+    syntax::{
+        database,
+        dictionary::{Dictionary, BLANK, UNBOUND},
+        query,
+    },
 };
 
-use super::{visitors::printer::Printer, Operation, OperationVisitor};
+use super::{stats::Stats, visitors::printer::Printer, Operation, OperationVisitor};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct Projection<O: Display> {
     pub(super) operation: Box<O>,
     pub(super) vars: query::Variables,
+    /// Projections pushed below the root only drop variables, they neither number the remaining
+    /// variables nor bind unbound ones to blank nodes
+    pub(super) pushed: bool,
+    pub(super) stats: Stats,
 }
 
 impl<O: Display> Projection<O> {
@@ -18,8 +30,18 @@ impl<O: Display> Projection<O> {
         Self {
             operation: Box::new(operation),
             vars,
+            pushed: false,
+            stats: Stats::default(),
         }
     }
+
+    pub(crate) fn with_pushed(self, pushed: bool) -> Self {
+        Self { pushed, ..self }
+    }
+
+    fn keeps(&self, var: &query::Variable) -> bool {
+        self.vars.iter().any(|v| v.name == var.name)
+    }
 }
 
 impl<'a> Display for Projection<Operation<'a>> {
@@ -38,6 +60,16 @@ where
         log::trace!("Projection next()");
 
         if let Some(mapping) = self.operation.next() {
+            if self.pushed {
+                return Some(Mapping {
+                    items: mapping
+                        .items
+                        .into_iter()
+                        .filter(|(k, _)| self.keeps(k))
+                        .collect(),
+                });
+            }
+
             let mut result = Mapping::new();
 
             for (i, var) in self.vars.iter().enumerate() {
@@ -60,4 +92,55 @@ where
     }
 }
 
+impl<O> BatchIterator for Projection<O>
+where
+    O: BatchIterator + Display,
+{
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        let batch = self.operation.next_batch(dictionary)?;
+
+        if self.pushed {
+            let vars: Vec<query::Variable> = batch
+                .vars()
+                .iter()
+                .filter(|v| self.keeps(v))
+                .cloned()
+                .collect();
+            let columns: Vec<_> = vars.iter().filter_map(|v| batch.column(v)).collect();
+
+            let mut result = Batch::new(vars.clone());
+
+            for row in 0..batch.len() {
+                result.push(columns.iter().map(|column| column[row]));
+            }
+
+            return Some(result);
+        }
+
+        let columns: Vec<_> = self.vars.iter().map(|var| batch.column(var)).collect();
+
+        let mut result = Batch::new(
+            self.vars
+                .iter()
+                .enumerate()
+                .map(|(i, var)| var.to_owned().set_pos(i))
+                .collect(),
+        );
+
+        for row in 0..batch.len() {
+            // Unbound variables are projected to a blank node, as in the row-at-a-time projection
+            result.push(columns.iter().map(
+                |column| match column.map(|c| c[row]).unwrap_or(UNBOUND) {
+                    UNBOUND => BLANK,
+                    term => term,
+                },
+            ));
+        }
+
+        log::trace!("Projection next_batch() returns {} rows", result.len());
+
+        Some(result)
+    }
+}
+
 impl<O: Display> selectivity::Selectivity for Projection<O> {}
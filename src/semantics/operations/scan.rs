@@ -1,16 +1,28 @@
 use core::fmt;
 
-use std::hash::Hash;
+use std::{hash::Hash, io, ops::Bound};
 
 use crate::{
     semantics::{
-        mapping::Mapping,
+        batch::{Batch, BatchIterator, BATCH_SIZE},
+        mapping::{Mapping, MappingSet},
+        sampling::Sampler,
         selectivity::{Selectivity, SelectivityResult},
     },
-    syntax::{database, query},
+    syntax::{
+        database,
+        dictionary::{Dictionary, TermId, UNBOUND},
+        histogram::{Key, Restriction},
+        query,
+        terms::{Position, TermIndex},
+        values::ValueRange,
+        Literal,
+    },
 };
 
 use super::{
+    context::Context,
+    stats::Stats,
     visitors::{condition, printer::Printer},
     OperationVisitor,
 };
@@ -21,7 +33,16 @@ pub(crate) struct Scan<'a> {
     pub(super) subject: query::Subject,
     pub(super) predicate: query::Predicate,
     pub(super) object: query::Object,
+    /// Variables replaced by constants of pushed down equality filters, still bound in each result
+    pub(super) constants: Vec<(query::Variable, database::Object)>,
+    /// Values the object has to lie in, from pushed down range filters
+    pub(super) range: Option<ValueRange>,
     iter: std::iter::Cloned<std::slice::Iter<'a, database::Triple>>,
+    ctx: Context,
+    partitioned: Option<std::vec::IntoIter<Mapping>>,
+    positions: Option<std::vec::IntoIter<usize>>,
+    batch_pos: usize,
+    pub(super) stats: Stats,
 }
 
 impl<'a> Scan<'a> {
@@ -36,8 +57,161 @@ impl<'a> Scan<'a> {
             subject,
             predicate,
             object,
+            constants: Vec::new(),
+            range: None,
             iter: db.triples().iter().cloned(),
+            ctx: Context::new(),
+            partitioned: None,
+            positions: None,
+            batch_pos: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    pub(crate) fn with_context(self, ctx: Context) -> Self {
+        Self { ctx, ..self }
+    }
+
+    /**
+     * Replace every occurrence of a variable by a constant, which the variable is bound to in
+     * each result instead.
+     *
+     * Fails if the variable does not occur or the constant cannot take one of its positions.
+     */
+    pub(crate) fn with_constant(self, v: &query::Variable, value: &query::Object) -> Option<Self> {
+        let term = match value {
+            query::Object::I(i) => database::Object::I(i.to_owned()),
+            query::Object::L(l) => database::Object::L(l.to_owned()),
+            query::Object::V(_) => return None,
+        };
+
+        let subject = match (&self.subject, value) {
+            (query::Subject::V(s), query::Object::I(i)) if s == v => {
+                query::Subject::I(i.to_owned())
+            }
+            (query::Subject::V(s), _) if s == v => return None,
+            (subject, _) => subject.to_owned(),
+        };
+
+        let predicate = match (&self.predicate, value) {
+            (query::Predicate::V(p), query::Object::I(i)) if p == v => {
+                query::Predicate::I(i.to_owned())
+            }
+            (query::Predicate::V(p), _) if p == v => return None,
+            (predicate, _) => predicate.to_owned(),
+        };
+
+        let object = match &self.object {
+            query::Object::V(o) if o == v => value.to_owned(),
+            object => object.to_owned(),
+        };
+
+        if (&subject, &predicate, &object) == (&self.subject, &self.predicate, &self.object) {
+            return None;
+        }
+
+        let mut constants = self.constants.clone();
+        constants.push((v.to_owned(), term));
+
+        Some(Self {
+            subject,
+            predicate,
+            object,
+            constants,
+            ..self
+        })
+    }
+
+    /// Only match triples whose object lies in `range`, fails if the object is not the variable `v`
+    pub(crate) fn with_range(self, v: &query::Variable, range: ValueRange) -> Option<Self> {
+        match &self.object {
+            query::Object::V(o) if o == v => Some(Self {
+                range: Some(
+                    self.range
+                        .map_or(range, |r| r.above(range.lower).below(range.upper)),
+                ),
+                ..self
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn pattern(&self) -> (&query::Subject, &query::Predicate, &query::Object) {
+        (&self.subject, &self.predicate, &self.object)
+    }
+
+    /// Filters that were pushed down into this scan
+    pub(crate) fn restrictions(&self) -> Option<String> {
+        let mut restrictions: Vec<String> = self
+            .constants
+            .iter()
+            .map(|(v, term)| format!("{} = {term}", v.name))
+            .collect();
+
+        if let (Some(range), query::Object::V(v)) = (&self.range, &self.object) {
+            restrictions.push(format!("{} in {range}", v.name));
+        }
+
+        (!restrictions.is_empty()).then(|| restrictions.join(", "))
+    }
+
+    /**
+     * Positions of the triples that can match this scan once the variables of `bound` are
+     * replaced by their values, taken from the index of the most selective term.
+     *
+     * Returns `None` if no term of the scan is known, so that every triple can match.
+     */
+    pub(crate) fn lookup<'t>(&self, terms: &'t TermIndex, bound: &Mapping) -> Option<&'t [usize]> {
+        let subject = match &self.subject {
+            query::Subject::I(i) => Some(database::Object::I(i.to_owned())),
+            query::Subject::V(v) => bound.get(v).cloned(),
+        };
+        let predicate = match &self.predicate {
+            query::Predicate::I(i) => Some(database::Object::I(i.to_owned())),
+            query::Predicate::V(v) => bound.get(v).cloned(),
+        };
+        let object = match &self.object {
+            query::Object::I(i) => Some(database::Object::I(i.to_owned())),
+            query::Object::L(l) => Some(database::Object::L(l.to_owned())),
+            query::Object::V(v) => bound.get(v).cloned(),
+        };
+
+        [
+            (Position::Subject, subject),
+            (Position::Predicate, predicate),
+            (Position::Object, object),
+        ]
+        .into_iter()
+        .filter_map(|(position, term)| term.map(|t| terms.positions(position, &t)))
+        .min_by_key(|positions| positions.len())
+    }
+
+    /// Extend `bound` by the result of matching `triple`, if it matches and agrees with `bound`
+    pub(crate) fn extend(&self, triple: &database::Triple, bound: &Mapping) -> Option<Mapping> {
+        let mut result = self.triple_to_mapping(triple)?;
+
+        if !result.compatible(bound) {
+            return None;
+        }
+
+        for v in bound.keys() {
+            if !result.contains_key(&v) {
+                result.insert(v.to_owned(), bound.get(v)?.to_owned());
+            }
         }
+
+        Some(result)
+    }
+
+    /// Positions of the triples that can match the range of the object, if it has one
+    fn candidates(&self) -> Option<Vec<usize>> {
+        let predicate = match &self.predicate {
+            query::Predicate::I(i) => Some(database::Predicate::I(i.to_owned())),
+            query::Predicate::V(_) => None,
+        };
+
+        self.range
+            .map(|range| self.db.values().positions(predicate.as_ref(), &range))
     }
 }
 
@@ -48,7 +222,14 @@ impl<'a> Clone for Scan<'a> {
             subject: self.subject.clone(),
             predicate: self.predicate.clone(),
             object: self.object.clone(),
+            constants: self.constants.clone(),
+            range: self.range,
             iter: self.iter.clone(),
+            ctx: self.ctx.clone(),
+            partitioned: self.partitioned.clone(),
+            positions: self.positions.clone(),
+            batch_pos: self.batch_pos,
+            stats: self.stats.clone(),
         }
     }
 }
@@ -59,6 +240,8 @@ impl<'a> PartialEq for Scan<'a> {
         self.subject == other.subject
             && self.predicate == other.predicate
             && self.object == other.object
+            && self.constants == other.constants
+            && self.range == other.range
     }
 }
 
@@ -67,6 +250,8 @@ impl<'a> Hash for Scan<'a> {
         self.subject.hash(state);
         self.predicate.hash(state);
         self.object.hash(state);
+        self.constants.hash(state);
+        self.range.hash(state);
     }
 }
 
@@ -107,7 +292,9 @@ impl Scan<'_> {
             query::Object::V(_) => true, // Match all instances where the query object is a variable
         };
 
-        if subj && pred && obj {
+        let range = self.range.is_none_or(|r| r.matches(&triple.object));
+
+        if subj && pred && obj && range {
             let mut result: Mapping = Mapping::new();
 
             if let query::Subject::V(v) = &self.subject {
@@ -133,6 +320,10 @@ impl Scan<'_> {
                 result.insert(v.to_owned(), triple.object.to_owned());
             }
 
+            for (v, term) in self.constants.iter() {
+                result.insert(v.to_owned(), term.to_owned());
+            }
+
             log::trace!("Scan next() returns {result}");
 
             Some(result)
@@ -140,6 +331,37 @@ impl Scan<'_> {
             None
         }
     }
+
+    /**
+     * Split the triples into one partition per thread and match each partition in parallel.
+     *
+     * The order of the resulting mappings is the same as for a sequential scan.
+     */
+    fn scan_partitioned(&self) -> io::Result<MappingSet> {
+        let triples = self.db.triples();
+        let positions = self
+            .candidates()
+            .unwrap_or_else(|| (0..triples.len()).collect());
+        let chunk_size = self.ctx.chunk_size(positions.len());
+
+        log::debug!(
+            "Scanning {} triples in partitions of {chunk_size}",
+            positions.len()
+        );
+
+        let chunks = self
+            .ctx
+            .map_parallel(positions.chunks(chunk_size).collect(), |chunk| {
+                // Each partition stops on its own once the deadline has passed
+                chunk
+                    .iter()
+                    .take_while(|_| !self.ctx.is_expired())
+                    .filter_map(|i| self.triple_to_mapping(&triples[*i]))
+                    .collect::<MappingSet>()
+            })?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
 }
 
 impl<'a> Iterator for Scan<'a> {
@@ -153,6 +375,40 @@ impl<'a> Iterator for Scan<'a> {
             self.object
         );
 
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        if self.ctx.is_parallel() {
+            if self.partitioned.is_none() {
+                let partitioned = self.scan_partitioned().unwrap_or_else(|e| {
+                    self.ctx.fail(e);
+                    MappingSet::new()
+                });
+
+                self.partitioned = Some(partitioned.into_iter());
+            }
+
+            return self.partitioned.as_mut().and_then(|p| p.next());
+        }
+
+        // A range on the object only visits the triples the value index finds in that range
+        if self.range.is_some() {
+            if self.positions.is_none() {
+                self.positions = self.candidates().map(|p| p.into_iter());
+            }
+
+            let triples = self.db.triples();
+
+            while let Some(i) = self.positions.as_mut().and_then(|p| p.next()) {
+                if let Some(result) = self.triple_to_mapping(&triples[i]) {
+                    return Some(result);
+                }
+            }
+
+            return None;
+        }
+
         while let Some(triple) = self.iter.next() {
             if let Some(result) = self.triple_to_mapping(&triple) {
                 return Some(result);
@@ -165,6 +421,131 @@ impl<'a> Iterator for Scan<'a> {
     }
 }
 
+impl<'a> BatchIterator for Scan<'a> {
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        let triples = &self.db.encoded().triples;
+
+        // A constant that does not occur in the database cannot match any triple
+        let constants: [Option<TermId>; 3] = [
+            match &self.subject {
+                query::Subject::I(i) => Some(dictionary.id(&database::Object::I(i.to_owned()))?),
+                query::Subject::V(_) => None,
+            },
+            match &self.predicate {
+                query::Predicate::I(i) => Some(dictionary.id(&database::Object::I(i.to_owned()))?),
+                query::Predicate::V(_) => None,
+            },
+            match &self.object {
+                query::Object::L(l) => Some(dictionary.id(&database::Object::L(l.to_owned()))?),
+                query::Object::I(i) => Some(dictionary.id(&database::Object::I(i.to_owned()))?),
+                query::Object::V(_) => None,
+            },
+        ];
+
+        let variables = [
+            match &self.subject {
+                query::Subject::V(v) => Some(v),
+                query::Subject::I(_) => None,
+            },
+            match &self.predicate {
+                query::Predicate::V(v) => Some(v),
+                query::Predicate::I(_) => None,
+            },
+            match &self.object {
+                query::Object::V(v) => Some(v),
+                _ => None,
+            },
+        ];
+
+        // Constants of pushed down equality filters also occur in the triples they match
+        let bindings: Vec<(&query::Variable, TermId)> = self
+            .constants
+            .iter()
+            .map(|(v, term)| Some((v, dictionary.id(term)?)))
+            .collect::<Option<_>>()?;
+
+        if self.range.is_some() && self.positions.is_none() {
+            self.positions = self.candidates().map(|p| p.into_iter());
+        }
+
+        let mut vars: Vec<query::Variable> = Vec::new();
+        let columns: Vec<Option<usize>> = variables
+            .iter()
+            .map(|var| {
+                var.map(|v| match vars.iter().position(|other| other == v) {
+                    Some(i) => i,
+                    None => {
+                        vars.push(v.to_owned());
+                        vars.len() - 1
+                    }
+                })
+            })
+            .collect();
+
+        let constant_columns: Vec<(usize, TermId)> = bindings
+            .into_iter()
+            .map(|(v, id)| match vars.iter().position(|other| other == v) {
+                Some(i) => (i, id),
+                None => {
+                    vars.push(v.to_owned());
+                    (vars.len() - 1, id)
+                }
+            })
+            .collect();
+
+        let mut row = vec![UNBOUND; vars.len()];
+        for (i, id) in constant_columns {
+            row[i] = id;
+        }
+
+        let mut batch = Batch::new(vars);
+
+        while batch.len() < BATCH_SIZE {
+            let position = match self.positions.as_mut() {
+                Some(positions) => positions.next(),
+                None if self.batch_pos < triples.len() => {
+                    self.batch_pos += 1;
+                    Some(self.batch_pos - 1)
+                }
+                None => None,
+            };
+
+            let Some(position) = position else {
+                break;
+            };
+
+            let triple = &triples[position];
+
+            let matches = constants
+                .iter()
+                .zip(triple.iter())
+                .all(|(constant, term)| constant.is_none_or(|c| c == *term));
+
+            if matches {
+                for (column, term) in columns.iter().zip(triple.iter()) {
+                    if let Some(i) = column {
+                        row[*i] = *term;
+                    }
+                }
+
+                batch.push(row.iter().copied());
+            }
+        }
+
+        log::trace!("Scan next_batch() returns {} rows", batch.len());
+
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
 impl<'a> Selectivity for Scan<'a> {
     fn sel_vc(&self) -> SelectivityResult {
         let sub = self.subject.sel_vc()?;
@@ -197,47 +578,171 @@ impl<'a> Selectivity for Scan<'a> {
     fn sel_pfjc(&self, s: &database::Summary, i: &condition::ConditionInfo) -> SelectivityResult {
         Ok(self.sel_pfj(s)? * self.condition_factor(s, i))
     }
+
+    fn sel_cs(&self, s: &database::Summary) -> SelectivityResult {
+        self.sel_pf(s)
+    }
+
+    fn sel_sampling(&self, s: &Sampler) -> SelectivityResult {
+        s.scan(self)
+    }
+}
+
+/// Bounds of the values of an object from the filters on it, for each kind of literal
+#[derive(Default)]
+struct Bounds {
+    numbers: (Option<f64>, Option<f64>),
+    dates: (Option<f64>, Option<f64>),
+    strings: (Option<String>, Option<String>),
+}
+
+/// Narrow `bounds` to the values below `x` if `upper`, above `x` otherwise
+fn narrow<T: PartialOrd>(bounds: &mut (Option<T>, Option<T>), x: T, upper: bool) {
+    let bound = if upper { &mut bounds.1 } else { &mut bounds.0 };
+
+    *bound = match bound.take() {
+        Some(b) if (b < x) == upper => Some(b),
+        _ => Some(x),
+    };
+}
+
+impl Bounds {
+    /// Narrow the bounds of the kind of `l` to the values below `l` if `upper`, above otherwise
+    fn narrow(&mut self, l: &Literal, upper: bool) {
+        if let Some(x) = l.parsed {
+            narrow(&mut self.numbers, x, upper)
+        } else if let Some(x) = l.date() {
+            narrow(&mut self.dates, x, upper)
+        } else {
+            narrow(&mut self.strings, l.lexical().to_owned(), upper)
+        }
+    }
 }
 
 impl<'a> Scan<'a> {
+    /// Bounds of the object from the range pushed into the scan
+    fn bounds(&self) -> Bounds {
+        let bound = |b: Bound<f64>| match b {
+            Bound::Included(x) | Bound::Excluded(x) => Some(x),
+            Bound::Unbounded => None,
+        };
+
+        Bounds {
+            numbers: (
+                self.range.and_then(|r| bound(r.lower)),
+                self.range.and_then(|r| bound(r.upper)),
+            ),
+            ..Default::default()
+        }
+    }
+
     fn condition_factor(&self, s: &database::Summary, i: &condition::ConditionInfo) -> f64 {
-        let mut lower = None;
-        let mut upper = None;
+        let predicate = match &self.predicate {
+            query::Predicate::I(i) => database::Predicate::I(i.to_owned()),
+            query::Predicate::V(_) => return 1.0,
+        };
+
+        let mut bounds = self.bounds();
+
+        // Equality is estimated by the number of distinct objects rather than a range
+        let distinct = s.d_p(&predicate).max(1.0);
+        let mut factor = 1.0;
 
         if let query::Object::V(v) = &self.object {
-            if let Some(info) = i.get(v) {
-                for info in info.iter() {
-                    match info {
-                        condition::VariableInfo::Lt(l) => {
-                            upper = l.parsed.map(|x| x.min(upper.unwrap_or(f64::INFINITY)))
-                        }
-                        condition::VariableInfo::Gt(l) => {
-                            lower = l.parsed.map(|x| x.max(lower.unwrap_or(-f64::INFINITY)))
-                        }
-                        condition::VariableInfo::Lte(l) => {
-                            upper = l.parsed.map(|x| x.min(upper.unwrap_or(f64::INFINITY)))
-                        }
-                        condition::VariableInfo::Gte(l) => {
-                            lower = l.parsed.map(|x| x.max(lower.unwrap_or(-f64::INFINITY)))
-                        }
-                        condition::VariableInfo::EqualsLiteral(l) => {
-                            upper = l.parsed.map(|x| x.min(upper.unwrap_or(f64::INFINITY)));
-                            lower = l.parsed.map(|x| x.min(lower.unwrap_or(-f64::INFINITY)));
-                        }
-                        _ => {}
+            for info in i.get(v).unwrap_or_default().iter() {
+                match info {
+                    condition::VariableInfo::Lt(l) | condition::VariableInfo::Lte(l) => {
+                        bounds.narrow(l, true)
                     }
+                    condition::VariableInfo::Gt(l) | condition::VariableInfo::Gte(l) => {
+                        bounds.narrow(l, false)
+                    }
+                    condition::VariableInfo::EqualsLiteral(_) => factor *= 1.0 / distinct,
+                    condition::VariableInfo::NotEqualsLiteral(_) => factor *= 1.0 - 1.0 / distinct,
+                    _ => {}
                 }
             }
         }
 
-        let predicate = match &self.predicate {
-            query::Predicate::I(i) => Some(database::Predicate::I(i.to_owned())),
-            query::Predicate::V(_) => None,
+        let (numbers, dates, strings) = (bounds.numbers, bounds.dates, bounds.strings);
+
+        factor
+            * s.p_l(&predicate, numbers.0, numbers.1)
+            * s.p_d(&predicate, dates.0, dates.1)
+            * s.p_s(&predicate, strings.0.as_deref(), strings.1.as_deref())
+    }
+
+    /// Values the filters leave for the object as joint histograms see them, `None` if the
+    /// filters restrict it in a way joint histograms do not track
+    fn restriction(&self, i: &condition::ConditionInfo) -> Option<Restriction> {
+        let query::Object::V(v) = &self.object else {
+            return None;
+        };
+
+        let mut bounds = self.bounds();
+        let mut equals = None;
+
+        for info in i.get(v).unwrap_or_default().iter() {
+            match info {
+                condition::VariableInfo::Lt(l) | condition::VariableInfo::Lte(l) => {
+                    bounds.narrow(l, true)
+                }
+                condition::VariableInfo::Gt(l) | condition::VariableInfo::Gte(l) => {
+                    bounds.narrow(l, false)
+                }
+                condition::VariableInfo::EqualsLiteral(l) => equals = Some(Key::from(l)),
+                condition::VariableInfo::EqualsIri(iri) => equals = Some(Key::from(iri)),
+                condition::VariableInfo::Bound => {}
+                _ => return None,
+            }
+        }
+
+        if let Some(key) = equals {
+            return Some(Restriction::Equals(key));
+        }
+
+        match (bounds.numbers, bounds.dates, bounds.strings) {
+            (_, _, (Some(_), _) | (_, Some(_))) => None,
+            ((None, None), (None, None), _) => Some(Restriction::Any),
+            ((lower, upper), (None, None), _) | ((None, None), (lower, upper), _) => {
+                Some(Restriction::Range(lower, upper))
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Fraction of the results of a star of this scan and `other` that the filters on both objects
+     * leave, from the joint histogram of their predicates. `None` unless both scans share the
+     * subject variable, both objects are filtered and the summary has a joint histogram for them.
+     */
+    pub(crate) fn correlation(
+        &self,
+        other: &Scan,
+        s: &database::Summary,
+        i: &condition::ConditionInfo,
+    ) -> Option<f64> {
+        let (query::Subject::V(x), query::Subject::V(y)) = (&self.subject, &other.subject) else {
+            return None;
+        };
+
+        let (query::Predicate::I(p1), query::Predicate::I(p2)) =
+            (&self.predicate, &other.predicate)
+        else {
+            return None;
         };
 
-        match predicate {
-            Some(p) => s.p_l(&p, lower, upper),
-            None => 1.0,
+        let (r1, r2) = (self.restriction(i)?, other.restriction(i)?);
+
+        if x != y || r1 == Restriction::Any || r2 == Restriction::Any {
+            return None;
         }
+
+        s.p_j(
+            &database::Predicate::I(p1.to_owned()),
+            &database::Predicate::I(p2.to_owned()),
+            &r1,
+            &r2,
+        )
     }
 }
@@ -0,0 +1,249 @@
+use std::{cmp::Ordering, fmt, hash::Hash, io};
+
+use crate::{
+    semantics::{
+        mapping::{Mapping, MappingSet},
+        selectivity::Selectivity,
+        spill::{SpillReader, SpillWriter},
+    },
+    syntax::{database, query},
+};
+
+use super::{
+    context::Context, stats::Stats, visitors::printer::Printer, Operation, OperationVisitor,
+};
+
+/**
+ * Orders the results of an operation by the values of some of their variables.
+ *
+ * All results are read before the first one is returned. Once they exceed the memory budget, the
+ * results read so far are sorted and spilled to disk as a run, and the runs are merged at the end
+ * with one result of each run in memory.
+ */
+#[derive(Debug, Clone)]
+pub(crate) struct Sort<O> {
+    pub(crate) operation: Box<O>,
+    pub(crate) order: Vec<query::OrderCondition>,
+    ctx: Context,
+    sorted: Option<Sorted>,
+    pub(super) stats: Stats,
+}
+
+/// Sorted results of the input, in memory or in runs on disk
+#[derive(Debug, Clone)]
+enum Sorted {
+    Memory(std::vec::IntoIter<Mapping>),
+    /// Each run with its next result, in the order the runs were spilled
+    Runs(Vec<(SpillReader, Option<Mapping>)>),
+}
+
+impl<O> Sort<O> {
+    pub(crate) fn new(operation: O, order: Vec<query::OrderCondition>) -> Self {
+        Self {
+            operation: Box::new(operation),
+            order,
+            ctx: Context::new(),
+            sorted: None,
+            stats: Stats::default(),
+        }
+    }
+
+    pub(crate) fn with_context(self, ctx: Context) -> Self {
+        Self { ctx, ..self }
+    }
+
+    /// The conditions to order by, as in queries
+    pub(crate) fn order_by(&self) -> String {
+        self.order
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl<'a> fmt::Display for Sort<Operation<'a>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Printer::new().visit_sort(self))
+    }
+}
+
+impl<O: Hash> Hash for Sort<O> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.operation.hash(state);
+        self.order.hash(state);
+    }
+}
+
+impl<O: PartialEq> Eq for Sort<O> {}
+impl<O: PartialEq> PartialEq for Sort<O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.operation == other.operation && self.order == other.order
+    }
+}
+
+/// Unbound values come first, then blank nodes, IRIs and literals
+fn rank(value: Option<&database::Object>) -> u8 {
+    match value {
+        None => 0,
+        Some(database::Object::B) => 1,
+        Some(database::Object::I(_)) => 2,
+        Some(database::Object::L(_)) => 3,
+    }
+}
+
+/// Numbers are ordered by their value, other literals and IRIs by how they are written
+fn compare_values(left: Option<&database::Object>, right: Option<&database::Object>) -> Ordering {
+    match (left, right) {
+        (Some(database::Object::I(l)), Some(database::Object::I(r))) => {
+            l.normalized().cmp(&r.normalized())
+        }
+        (Some(database::Object::L(l)), Some(database::Object::L(r))) => {
+            match (l.parsed, r.parsed) {
+                (Some(l), Some(r)) => l.total_cmp(&r),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => l.lexical().cmp(r.lexical()),
+            }
+        }
+        _ => rank(left).cmp(&rank(right)),
+    }
+}
+
+fn compare(order: &[query::OrderCondition], left: &Mapping, right: &Mapping) -> Ordering {
+    order
+        .iter()
+        .map(|c| {
+            let ordering = compare_values(left.get(&c.variable), right.get(&c.variable));
+
+            if c.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+impl<O: Iterator<Item = Mapping>> Sort<O> {
+    /// Sort `results` and write them to disk as a run
+    fn spill(&self, mut results: MappingSet) -> io::Result<SpillReader> {
+        results.sort_by(|l, r| compare(&self.order, l, r));
+
+        let mut writer = SpillWriter::new()?;
+
+        for m in results.iter() {
+            writer.write(m)?;
+        }
+
+        Ok(writer.finish()?.reader())
+    }
+
+    /// Read all results of the input, spilling them in sorted runs once they exceed the budget
+    fn sort(&mut self) -> io::Result<Sorted> {
+        let mut results = MappingSet::new();
+        let mut runs = Vec::new();
+        let mut used = 0;
+
+        while let Some(m) = self.operation.next() {
+            if self.ctx.memory_budget.is_some() {
+                used += m.estimated_size();
+            }
+
+            results.push(m);
+
+            if self.ctx.exceeds_budget(used) {
+                log::info!("Sort exceeds memory budget, spilling a sorted run to disk");
+
+                runs.push(self.spill(std::mem::take(&mut results))?);
+                used = 0;
+            }
+        }
+
+        if runs.is_empty() {
+            // A stable sort keeps the order of the input for results with the same values
+            results.sort_by(|l, r| compare(&self.order, l, r));
+
+            return Ok(Sorted::Memory(results.into_iter()));
+        }
+
+        if !results.is_empty() {
+            runs.push(self.spill(results)?);
+        }
+
+        log::debug!("Merging {} sorted runs", runs.len());
+
+        runs.into_iter()
+            .map(|mut run| {
+                let next = run.next().transpose()?;
+                Ok((run, next))
+            })
+            .collect::<io::Result<_>>()
+            .map(Sorted::Runs)
+    }
+
+    /// The smallest next result of all runs, the one of the earliest run if several are equal
+    fn merge(&mut self) -> io::Result<Option<Mapping>> {
+        let Some(Sorted::Runs(runs)) = self.sorted.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut smallest: Option<usize> = None;
+
+        for (i, (_, next)) in runs.iter().enumerate() {
+            let Some(next) = next else {
+                continue;
+            };
+
+            let smaller = match smallest.and_then(|s| runs[s].1.as_ref()) {
+                Some(current) => compare(&self.order, next, current).is_lt(),
+                None => true,
+            };
+
+            if smaller {
+                smallest = Some(i);
+            }
+        }
+
+        let Some(i) = smallest else {
+            return Ok(None);
+        };
+
+        let (run, next) = &mut runs[i];
+        let following = run.next().transpose()?;
+
+        Ok(std::mem::replace(next, following))
+    }
+}
+
+impl<O: Iterator<Item = Mapping>> Iterator for Sort<O> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        if self.sorted.is_none() {
+            match self.sort() {
+                Ok(sorted) => self.sorted = Some(sorted),
+                Err(e) => {
+                    self.ctx.fail(e);
+                    return None;
+                }
+            }
+        }
+
+        if let Some(Sorted::Memory(results)) = self.sorted.as_mut() {
+            return results.next();
+        }
+
+        self.merge().unwrap_or_else(|e| {
+            self.ctx.fail(e);
+            None
+        })
+    }
+}
+
+impl<O> Selectivity for Sort<O> {}
@@ -0,0 +1,65 @@
+use std::{
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[derive(Debug, Default)]
+struct Counters {
+    rows: AtomicUsize,
+    nanos: AtomicU64,
+}
+
+/// Number of mappings an operation produced and the time spent producing them.
+///
+/// Statistics are only recorded once enabled, clones of an operation share the same counters so
+/// that they can be read from a copy of a plan after evaluating the plan itself. They never take
+/// part in comparing or hashing operations.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stats(Option<Arc<Counters>>);
+
+impl Stats {
+    pub(crate) fn enabled() -> Self {
+        Self(Some(Arc::new(Counters::default())))
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn record(&self, rows: usize, elapsed: Duration) {
+        if let Some(counters) = &self.0 {
+            counters.rows.fetch_add(rows, Ordering::Relaxed);
+            counters
+                .nanos
+                .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of mappings produced so far, `None` if statistics are not enabled
+    pub(crate) fn rows(&self) -> Option<usize> {
+        self.0.as_ref().map(|c| c.rows.load(Ordering::Relaxed))
+    }
+
+    /// Time spent producing mappings, including the time spent in the operation's inputs
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        self.0
+            .as_ref()
+            .map(|c| Duration::from_nanos(c.nanos.load(Ordering::Relaxed)))
+    }
+}
+
+impl PartialEq for Stats {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Stats {}
+
+impl Hash for Stats {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
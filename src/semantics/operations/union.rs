@@ -1,13 +1,26 @@
-use std::fmt;
+use std::hash::Hash;
+use std::{fmt, io};
 
-use crate::semantics::{mapping::Mapping, selectivity::Selectivity};
+use crate::{
+    semantics::{
+        batch::{Batch, BatchIterator},
+        mapping::{Mapping, MappingSet},
+        selectivity::Selectivity,
+    },
+    syntax::dictionary::Dictionary,
+};
 
-use super::{visitors::printer::Printer, Operation, OperationVisitor};
+use super::{
+    context::Context, stats::Stats, visitors::printer::Printer, Operation, OperationVisitor,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone)]
 pub(crate) struct Union<O> {
     pub(crate) left: Box<O>,
     pub(crate) right: Box<O>,
+    ctx: Context,
+    branches: Option<std::vec::IntoIter<Mapping>>,
+    pub(super) stats: Stats,
 }
 
 impl<'a> Union<Operation<'a>> {
@@ -15,8 +28,15 @@ impl<'a> Union<Operation<'a>> {
         Self {
             left: Box::new(left),
             right: Box::new(right),
+            ctx: Context::new(),
+            branches: None,
+            stats: Stats::default(),
         }
     }
+
+    pub(crate) fn with_context(self, ctx: Context) -> Self {
+        Self { ctx, ..self }
+    }
 }
 
 impl<'a> fmt::Display for Union<Operation<'a>> {
@@ -25,12 +45,63 @@ impl<'a> fmt::Display for Union<Operation<'a>> {
     }
 }
 
-impl<O: Iterator<Item = Mapping>> Iterator for Union<O> {
+impl<O: Hash> Hash for Union<O> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.left.hash(state);
+        self.right.hash(state);
+    }
+}
+
+impl<O: PartialEq> Eq for Union<O> {}
+impl<O: PartialEq> PartialEq for Union<O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.left.eq(&other.left) && self.right.eq(&other.right)
+    }
+}
+
+impl<O: Iterator<Item = Mapping> + Send> Union<O> {
+    /**
+     * Evaluate both branches in parallel.
+     *
+     * Mappings of the left branch are returned before those of the right branch, just like
+     * in the sequential union.
+     */
+    fn union_parallel(&mut self) -> io::Result<MappingSet> {
+        log::debug!("Evaluating union branches in parallel");
+
+        let branches = self
+            .ctx
+            .map_parallel(vec![self.left.as_mut(), self.right.as_mut()], |branch| {
+                branch.collect::<MappingSet>()
+            })?;
+
+        Ok(branches.into_iter().flatten().collect())
+    }
+}
+
+impl<O: Iterator<Item = Mapping> + Send> Iterator for Union<O> {
     type Item = Mapping;
 
     fn next(&mut self) -> Option<Self::Item> {
         log::trace!("Union next()");
 
+        if self.ctx.is_expired() {
+            return None;
+        }
+
+        if self.ctx.is_parallel() {
+            if self.branches.is_none() {
+                let branches = self.union_parallel().unwrap_or_else(|e| {
+                    self.ctx.fail(e);
+                    MappingSet::new()
+                });
+
+                self.branches = Some(branches.into_iter());
+            }
+
+            return self.branches.as_mut().and_then(|b| b.next());
+        }
+
         if let Some(next) = self.left.next() {
             log::trace!("Union next() returns left {next}");
             Some(next)
@@ -44,4 +115,12 @@ impl<O: Iterator<Item = Mapping>> Iterator for Union<O> {
     }
 }
 
+impl<O: BatchIterator> BatchIterator for Union<O> {
+    fn next_batch(&mut self, dictionary: &Dictionary) -> Option<Batch> {
+        self.left
+            .next_batch(dictionary)
+            .or_else(|| self.right.next_batch(dictionary))
+    }
+}
+
 impl<O> Selectivity for Union<O> {}
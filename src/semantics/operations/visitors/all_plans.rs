@@ -5,8 +5,9 @@ use std::{cell::RefCell, rc::Rc};
 use std::{error::Error, fmt::Display};
 
 use crate::semantics::operations::{
-    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus, offset::Offset,
-    projection::Projection, scan::Scan, union::Union, Operation, OperationVisitor,
+    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+    minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+    Operation, OperationVisitor,
 };
 
 use super::flatten::Flatten;
@@ -37,7 +38,7 @@ impl Display for AllPlansError {
 impl Error for AllPlansError {}
 
 impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
-    fn visit(&mut self, o: &'a Operation<'a>) -> AllPlansResult<'a> {
+    fn visit(&mut self, o: &Operation<'a>) -> AllPlansResult<'a> {
         if let Ok(ops) = Flatten::new().visit(o) {
             let scans: Vec<Scan<'a>> = ops
                 .iter()
@@ -106,20 +107,27 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             Operation::Filter(f) => self.visit_filter(f),
             Operation::LeftJoin(l) => self.visit_leftjoin(l),
             Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
             Operation::Offset(o) => self.visit_offset(o),
             Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
         }
     }
 
-    fn visit_scan(&mut self, _: &'a Scan<'a>) -> AllPlansResult<'a> {
+    fn visit_scan(&mut self, _: &Scan<'a>) -> AllPlansResult<'a> {
         panic!("Should have optimized before now")
     }
 
-    fn visit_join(&mut self, _: &'a Join<Operation<'a>>) -> AllPlansResult<'a> {
-        panic!("Should have optimized before now")
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> AllPlansResult<'a> {
+        Ok(self
+            .visit(&o.left)?
+            .into_iter()
+            .cartesian_product(self.visit(&o.right)?.into_iter())
+            .map(|(l, r)| Operation::Join(Join::new(l, r)))
+            .collect())
     }
 
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.operation)?
             .into_iter()
@@ -127,7 +135,7 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .collect())
     }
 
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.left)?
             .into_iter()
@@ -136,7 +144,7 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .collect())
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.operation)?
             .into_iter()
@@ -144,7 +152,7 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .collect())
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.left)?
             .into_iter()
@@ -153,7 +161,7 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .collect())
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.left)?
             .into_iter()
@@ -162,7 +170,15 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .collect())
     }
 
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> AllPlansResult<'a> {
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> AllPlansResult<'a> {
+        Ok(self
+            .visit(&o.operation)?
+            .into_iter()
+            .map(|op| Operation::Sort(Sort::new(op, o.order.clone())))
+            .collect())
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.operation)?
             .into_iter()
@@ -172,7 +188,7 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
 
     fn visit_limit(
         &mut self,
-        o: &'a crate::semantics::operations::limit::Limit<Operation<'a>>,
+        o: &crate::semantics::operations::limit::Limit<Operation<'a>>,
     ) -> AllPlansResult<'a> {
         Ok(self
             .visit(&o.operation)?
@@ -180,6 +196,10 @@ impl<'a> OperationVisitor<'a, AllPlansResult<'a>> for AllPlans {
             .map(|op| Operation::Limit(Limit::new(op, o.limit)))
             .collect())
     }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> AllPlansResult<'a> {
+        Ok(vec![Operation::Materialized(o.clone())])
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
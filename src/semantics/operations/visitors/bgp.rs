@@ -0,0 +1,102 @@
+use crate::semantics::operations::{
+    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+    minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+    Operation, OperationVisitor,
+};
+
+use super::flatten::Flatten;
+
+/// Collects the scans of each basic graph pattern in a plan
+pub(crate) struct Bgps;
+
+impl Bgps {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+type BgpsResult<'a> = Vec<Vec<Scan<'a>>>;
+
+impl<'a> OperationVisitor<'a, BgpsResult<'a>> for Bgps {
+    fn visit(&mut self, o: &Operation<'a>) -> BgpsResult<'a> {
+        if let Ok(ops) = Flatten::new().visit(o) {
+            return vec![ops
+                .into_iter()
+                .filter_map(|o| match o {
+                    Operation::Scan(s) => Some(s),
+                    _ => None,
+                })
+                .collect()];
+        }
+
+        match o {
+            Operation::Scan(s) => self.visit_scan(s),
+            Operation::Join(j) => self.visit_join(j),
+            Operation::Projection(p) => self.visit_projection(p),
+            Operation::Union(u) => self.visit_union(u),
+            Operation::Filter(f) => self.visit_filter(f),
+            Operation::LeftJoin(l) => self.visit_leftjoin(l),
+            Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
+            Operation::Offset(o) => self.visit_offset(o),
+            Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
+        }
+    }
+
+    fn visit_scan(&mut self, o: &Scan<'a>) -> BgpsResult<'a> {
+        vec![vec![o.clone()]]
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> BgpsResult<'a> {
+        let mut left = self.visit(&o.left);
+        left.append(&mut self.visit(&o.right));
+
+        left
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> BgpsResult<'a> {
+        self.visit(&o.operation)
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> BgpsResult<'a> {
+        let mut left = self.visit(&o.left);
+        left.append(&mut self.visit(&o.right));
+
+        left
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> BgpsResult<'a> {
+        self.visit(&o.operation)
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> BgpsResult<'a> {
+        let mut left = self.visit(&o.left);
+        left.append(&mut self.visit(&o.right));
+
+        left
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> BgpsResult<'a> {
+        let mut left = self.visit(&o.left);
+        left.append(&mut self.visit(&o.right));
+
+        left
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> BgpsResult<'a> {
+        self.visit(&o.operation)
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> BgpsResult<'a> {
+        self.visit(&o.operation)
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> BgpsResult<'a> {
+        self.visit(&o.operation)
+    }
+
+    fn visit_materialized(&mut self, _o: &Materialized<Operation<'a>>) -> BgpsResult<'a> {
+        vec![]
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::HashSet;
 
 use crate::{
     semantics::operations::{
-        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus, offset::Offset,
-        projection::Projection, scan::Scan, union::Union, Operation, OperationVisitor,
+        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+        minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+        Operation, OperationVisitor,
     },
     syntax::query,
 };
@@ -16,7 +17,7 @@ impl BoundVars {
 }
 
 impl<'a> OperationVisitor<'a, HashSet<query::Variable>> for BoundVars {
-    fn visit_scan(&mut self, o: &'a Scan) -> HashSet<query::Variable> {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> HashSet<query::Variable> {
         let mut result = HashSet::new();
 
         if let query::Subject::V(v) = &o.subject {
@@ -31,6 +32,8 @@ impl<'a> OperationVisitor<'a, HashSet<query::Variable>> for BoundVars {
             result.insert(v.to_owned());
         }
 
+        result.extend(o.constants.iter().map(|(v, _)| v.to_owned()));
+
         result
     }
 
@@ -55,18 +58,22 @@ impl<'a> OperationVisitor<'a, HashSet<query::Variable>> for BoundVars {
         left.intersection(&right).cloned().collect()
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> HashSet<query::Variable> {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> HashSet<query::Variable> {
         self.visit(&o.operation)
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> HashSet<query::Variable> {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> HashSet<query::Variable> {
         self.visit(&o.operation)
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> HashSet<query::Variable> {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> HashSet<query::Variable> {
         self.visit(&o.left)
     }
 
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> HashSet<query::Variable> {
+        self.visit(&o.operation)
+    }
+
     fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> HashSet<query::Variable> {
         self.visit(&o.operation)
     }
@@ -74,4 +81,39 @@ impl<'a> OperationVisitor<'a, HashSet<query::Variable>> for BoundVars {
     fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> HashSet<query::Variable> {
         self.visit(&o.operation)
     }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> HashSet<query::Variable> {
+        self.visit(&o.operation)
+    }
+}
+
+/// Variables that are bound in some result of an operation, unlike `BoundVars` which are bound in all
+pub(crate) fn possible_vars(o: &Operation) -> HashSet<query::Variable> {
+    match o {
+        Operation::Scan(_) => BoundVars::new().visit(o),
+        Operation::Join(j) => possible_vars(&j.left)
+            .union(&possible_vars(&j.right))
+            .cloned()
+            .collect(),
+        // Projections at the root bind unbound variables to blank nodes
+        Operation::Projection(p) if !p.pushed => p.vars.iter().cloned().collect(),
+        Operation::Projection(p) => possible_vars(&p.operation)
+            .into_iter()
+            .filter(|v| p.vars.iter().any(|x| x == v))
+            .collect(),
+        Operation::Union(u) => possible_vars(&u.left)
+            .union(&possible_vars(&u.right))
+            .cloned()
+            .collect(),
+        Operation::Filter(f) => possible_vars(&f.operation),
+        Operation::LeftJoin(l) => possible_vars(&l.left)
+            .union(&possible_vars(&l.right))
+            .cloned()
+            .collect(),
+        Operation::Minus(m) => possible_vars(&m.left),
+        Operation::Sort(s) => possible_vars(&s.operation),
+        Operation::Offset(f) => possible_vars(&f.operation),
+        Operation::Limit(l) => possible_vars(&l.operation),
+        Operation::Materialized(m) => possible_vars(&m.operation),
+    }
 }
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::{
+    semantics::{
+        cache::Row,
+        mapping::Mapping,
+        operations::{
+            filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit,
+            materialized::Materialized, minus::Minus, offset::Offset, projection::Projection,
+            scan::Scan, sort::Sort, union::Union, Operation, OperationVisitor,
+        },
+    },
+    syntax::{
+        database,
+        query::{self, Condition, ConditionVisitor},
+    },
+};
+
+/**
+ * Canonical form of an operation, which is the same for operations that only differ in the names
+ * of their variables.
+ *
+ * Variables are numbered by name in the order they occur, constants are written with all their
+ * parts, so that literals with the same value but a different datatype or language stay apart.
+ * IRIs are written in full, so that prefixed names and the IRIs they expand to are alike.
+ */
+pub(crate) struct Canonical {
+    /// Variables by their number, as they are bound in the results of the operation
+    variables: Vec<query::Variable>,
+    numbers: HashMap<String, usize>,
+}
+
+impl Canonical {
+    pub(crate) fn new() -> Self {
+        Self {
+            variables: Vec::new(),
+            numbers: HashMap::new(),
+        }
+    }
+
+    fn number(&mut self, v: &query::Variable) -> usize {
+        let next = self.variables.len();
+        let number = *self.numbers.entry(v.name.to_owned()).or_insert(next);
+
+        if number == next {
+            self.variables.push(v.to_owned());
+        }
+
+        number
+    }
+
+    fn variable(&mut self, v: &query::Variable) -> String {
+        format!("?{}", self.number(v))
+    }
+
+    fn subject(&mut self, s: &query::Subject) -> String {
+        match s {
+            query::Subject::I(i) => i.normalized(),
+            query::Subject::V(v) => self.variable(v),
+        }
+    }
+
+    fn predicate(&mut self, p: &query::Predicate) -> String {
+        match p {
+            query::Predicate::I(i) => i.normalized(),
+            query::Predicate::V(v) => self.variable(v),
+        }
+    }
+
+    fn object(&mut self, o: &query::Object) -> String {
+        match o {
+            query::Object::I(i) => i.normalized(),
+            query::Object::L(l) => format!("{l:?}"),
+            query::Object::V(v) => self.variable(v),
+        }
+    }
+
+    /// Canonical form of `o`, numbering the variables it shares with earlier operations alike
+    pub(crate) fn operation(&mut self, o: &Operation) -> String {
+        OperationVisitor::visit(self, o)
+    }
+
+    fn condition(&mut self, c: &Condition) -> String {
+        ConditionVisitor::visit(self, c)
+    }
+
+    /// A result of the operation with its variables replaced by their numbers
+    pub(crate) fn row(&self, mapping: &Mapping) -> Row {
+        mapping
+            .items
+            .iter()
+            .filter_map(|(v, value)| Some((*self.numbers.get(&v.name)?, value.to_owned())))
+            .collect()
+    }
+
+    /// A result of the operation with the numbers of its variables replaced by the variables
+    pub(crate) fn mapping(&self, row: &Row) -> Mapping {
+        let mut mapping = Mapping::new();
+
+        for (number, value) in row {
+            mapping.insert(self.variables[*number].to_owned(), value.to_owned());
+        }
+
+        mapping
+    }
+}
+
+fn constant(term: &database::Object) -> String {
+    match term {
+        database::Object::I(i) => i.normalized(),
+        term => format!("{term:?}"),
+    }
+}
+
+impl<'a> OperationVisitor<'a, String> for Canonical {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> String {
+        let pattern = format!(
+            "{} {} {}",
+            self.subject(&o.subject),
+            self.predicate(&o.predicate),
+            self.object(&o.object)
+        );
+
+        let constants: Vec<String> = o
+            .constants
+            .iter()
+            .map(|(v, term)| format!("{} = {}", self.variable(v), constant(term)))
+            .collect();
+
+        format!("SCAN({pattern} [{}] {:?})", constants.join(", "), o.range)
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> String {
+        format!(
+            "JOIN({}, {})",
+            self.operation(&o.left),
+            self.operation(&o.right)
+        )
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> String {
+        let operation = self.operation(&o.operation);
+
+        if o.pushed {
+            let vars: Vec<String> = o.vars.iter().map(|v| self.variable(v)).collect();
+
+            return format!("PUSHED PROJECTION[{}]({operation})", vars.join(" "));
+        }
+
+        let vars: Vec<String> = o
+            .vars
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let number = self.number(v);
+                // The results bind the projected variables with their position
+                self.variables[number] = v.to_owned().set_pos(i);
+
+                format!("?{number}")
+            })
+            .collect();
+
+        format!("PROJECTION[{}]({operation})", vars.join(" "))
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> String {
+        format!(
+            "UNION({}, {})",
+            self.operation(&o.left),
+            self.operation(&o.right)
+        )
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> String {
+        let operation = self.operation(&o.operation);
+        let condition = self.condition(&o.condition);
+
+        format!("FILTER[{condition}]({operation})")
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> String {
+        format!(
+            "LEFTJOIN({}, {})",
+            self.operation(&o.left),
+            self.operation(&o.right)
+        )
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> String {
+        format!(
+            "MINUS({}, {})",
+            self.operation(&o.left),
+            self.operation(&o.right)
+        )
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> String {
+        let operation = self.operation(&o.operation);
+        let order: Vec<String> = o
+            .order
+            .iter()
+            .map(|c| match c.descending {
+                true => format!("DESC({})", self.variable(&c.variable)),
+                false => self.variable(&c.variable),
+            })
+            .collect();
+
+        format!("SORT[{}]({operation})", order.join(" "))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> String {
+        format!("OFFSET {}({})", o.offset, self.operation(&o.operation))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> String {
+        format!("LIMIT {}({})", o.limit, self.operation(&o.operation))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> String {
+        // The results are the ones of the evaluated operation
+        self.operation(&o.operation)
+    }
+}
+
+impl ConditionVisitor<String> for Canonical {
+    fn visit_equals(&mut self, o1: &query::Object, o2: &query::Object) -> String {
+        format!("{} = {}", self.object(o1), self.object(o2))
+    }
+
+    fn visit_gt(&mut self, o1: &query::Object, o2: &query::Object) -> String {
+        format!("{} > {}", self.object(o1), self.object(o2))
+    }
+
+    fn visit_lt(&mut self, o1: &query::Object, o2: &query::Object) -> String {
+        format!("{} < {}", self.object(o1), self.object(o2))
+    }
+
+    fn visit_bound(&mut self, v: &query::Variable) -> String {
+        format!("BOUND({})", self.variable(v))
+    }
+
+    fn visit_not(&mut self, c: &Condition) -> String {
+        format!("NOT({})", self.condition(c))
+    }
+
+    fn visit_and(&mut self, c1: &Condition, c2: &Condition) -> String {
+        format!("AND({}, {})", self.condition(c1), self.condition(c2))
+    }
+
+    fn visit_or(&mut self, c1: &Condition, c2: &Condition) -> String {
+        format!("OR({}, {})", self.condition(c1), self.condition(c2))
+    }
+}
@@ -292,3 +292,23 @@ impl ConditionVisitor<Condition> for Normalize {
         Condition::Or(Box::new(self.visit(c1)), Box::new(self.visit(c2)))
     }
 }
+
+/// Variables that occur in a condition
+pub(crate) fn condition_vars(c: &Condition) -> HashSet<Variable> {
+    let object = |o: &Object| match o {
+        Object::V(v) => Some(v.to_owned()),
+        _ => None,
+    };
+
+    match c {
+        Condition::Equals(o1, o2) | Condition::LT(o1, o2) | Condition::GT(o1, o2) => {
+            object(o1).into_iter().chain(object(o2)).collect()
+        }
+        Condition::Bound(v) => HashSet::from([v.to_owned()]),
+        Condition::Not(c) => condition_vars(c),
+        Condition::And(c1, c2) | Condition::Or(c1, c2) => condition_vars(c1)
+            .union(&condition_vars(c2))
+            .cloned()
+            .collect(),
+    }
+}
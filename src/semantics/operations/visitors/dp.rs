@@ -0,0 +1,741 @@
+//! Join ordering by dynamic programming over the join graph of a basic graph pattern.
+//!
+//! The scans of a basic graph pattern are the nodes of the join graph, two scans are connected
+//! if they share a variable. Plans are enumerated with the DPccp algorithm by Moerkotte and
+//! Neumann, which only considers pairs of connected subgraphs that are connected to each other.
+//! This avoids cartesian products and yields bushy plans. The cost of a plan is the sum of the
+//! estimated cardinalities of its operations, weighted by a per-operator cost.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    semantics::{
+        operations::{
+            context::Context,
+            filter::Filter,
+            join::Join,
+            leftjoin::LeftJoin,
+            limit::Limit,
+            materialized::{collect_within, Materialized},
+            minus::Minus,
+            offset::Offset,
+            projection::Projection,
+            scan::Scan,
+            sort::Sort,
+            union::Union,
+            Operation, OperationVisitor,
+        },
+        selectivity::{q_error, SelectivityEstimator},
+    },
+    syntax::query::ConditionVisitor,
+};
+
+use super::{
+    condition::{ConditionAnalyzer, ConditionInfo},
+    flatten::Flatten,
+    optimize::{insert_filter_operation, Optimize, OptimizerError},
+    prepare::Prepare,
+};
+
+/// Largest number of scans in a basic graph pattern that is ordered by dynamic programming
+///
+/// A star of n scans is a clique in the join graph, for which DPccp visits about 3^n pairs of
+/// connected subgraphs.
+const MAX_SCANS: usize = 14;
+
+/// Cost of reading one triple in a scan
+const SCAN_COST: f64 = 1.0;
+
+/// Cost of inserting one mapping into the hash table of a join
+const BUILD_COST: f64 = 2.0;
+
+/// Cost of looking up one mapping in the hash table of a join
+const PROBE_COST: f64 = 1.0;
+
+/// Cost of producing one result of a join
+const OUTPUT_COST: f64 = 1.0;
+
+type Set = u64;
+
+fn single(i: usize) -> Set {
+    1 << i
+}
+
+/// All nodes with an index lower than or equal to `i`
+fn up_to(i: usize) -> Set {
+    (single(i) << 1).wrapping_sub(1)
+}
+
+/// All non-empty subsets of `set`
+fn subsets(set: Set) -> impl Iterator<Item = Set> {
+    let mut next = set;
+
+    std::iter::from_fn(move || {
+        if next == 0 {
+            return None;
+        }
+
+        let current = next;
+        next = (next - 1) & set;
+
+        Some(current)
+    })
+}
+
+/// All nodes of `set` in descending order
+fn nodes(set: Set) -> impl Iterator<Item = usize> {
+    let mut rest = set;
+
+    std::iter::from_fn(move || {
+        if rest == 0 {
+            return None;
+        }
+
+        let i = (Set::BITS - 1 - rest.leading_zeros()) as usize;
+        rest &= !single(i);
+
+        Some(i)
+    })
+}
+
+/// Best plan found for a set of scans
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    cardinality: f64,
+    cost: f64,
+    /// Sets of the left and right operation, `None` for a single scan
+    split: Option<(Set, Set)>,
+}
+
+/// The join graph of a basic graph pattern with estimated cardinalities and join factors
+struct JoinGraph<'a> {
+    /// Scans, or materialized results of joins of scans
+    leaves: Vec<Operation<'a>>,
+    triples: f64,
+    cardinalities: Vec<f64>,
+    neighbours: Vec<Set>,
+    /// Factor by which the product of the cardinalities of two scans is reduced, 1 for scans
+    /// that are not connected
+    factors: Vec<Vec<f64>>,
+}
+
+impl<'a> JoinGraph<'a> {
+    fn new(
+        scans: Vec<Scan<'a>>,
+        estimator: &SelectivityEstimator,
+        triples: f64,
+    ) -> Result<Self, OptimizerError> {
+        let cardinalities = scans
+            .iter()
+            .map(|s| Ok(estimator.cardinality(&Operation::Scan(s.clone()))?.max(1.0)))
+            .collect::<Result<Vec<f64>, OptimizerError>>()?;
+
+        let mut neighbours = vec![0; scans.len()];
+        let mut factors = vec![vec![1.0; scans.len()]; scans.len()];
+
+        for (i, left) in scans.iter().enumerate() {
+            for (j, right) in scans.iter().enumerate().skip(i + 1) {
+                let join = Join::new(
+                    Operation::Scan(left.clone()),
+                    Operation::Scan(right.clone()),
+                );
+
+                if join.join_vars().is_empty() {
+                    continue;
+                }
+
+                let larger = cardinalities[i].max(cardinalities[j]);
+
+                // The join cardinality is its selectivity times the t² combinations of triples
+                // of both scans, so the factor relates it to the product of the scan
+                // cardinalities. Without an estimate for the join, assume each mapping of the
+                // smaller input finds one partner in the larger input
+                let factor = match estimator.cardinality(&Operation::Join(join)) {
+                    Ok(c) if c.is_finite() => c.max(1.0) / (cardinalities[i] * cardinalities[j]),
+                    _ => 1.0 / larger,
+                };
+
+                neighbours[i] |= single(j);
+                neighbours[j] |= single(i);
+                factors[i][j] = factor;
+                factors[j][i] = factor;
+            }
+        }
+
+        Ok(Self {
+            leaves: scans.into_iter().map(Operation::Scan).collect(),
+            triples,
+            cardinalities,
+            neighbours,
+            factors,
+        })
+    }
+
+    /**
+     * The join graph in which each of the given sets of nodes is replaced by a single node with the
+     * given results and their actual number.
+     *
+     * The factors of the edges between the replaced nodes and another node are combined into the
+     * factor of the edge between the new node and that node.
+     */
+    fn materialize(&self, groups: Vec<(Set, Operation<'a>, f64)>) -> Self {
+        let kept: Vec<usize> = (0..self.leaves.len())
+            .filter(|i| groups.iter().all(|(set, _, _)| set & single(*i) == 0))
+            .collect();
+
+        let index = |i: usize| match kept.iter().position(|k| *k == i) {
+            Some(position) => position,
+            None => {
+                kept.len()
+                    + groups
+                        .iter()
+                        .position(|(set, _, _)| set & single(i) != 0)
+                        .unwrap()
+            }
+        };
+
+        let mut leaves: Vec<Operation<'a>> = kept.iter().map(|i| self.leaves[*i].clone()).collect();
+        let mut cardinalities: Vec<f64> = kept.iter().map(|i| self.cardinalities[*i]).collect();
+        for (_, leaf, cardinality) in groups.iter() {
+            leaves.push(leaf.clone());
+            cardinalities.push(cardinality.max(1.0));
+        }
+
+        let mut neighbours = vec![0; leaves.len()];
+        let mut factors = vec![vec![1.0; leaves.len()]; leaves.len()];
+        for (i, others) in self.neighbours.iter().enumerate() {
+            for j in nodes(*others & !up_to(i)) {
+                let (k, l) = (index(i), index(j));
+                if k == l {
+                    continue;
+                }
+
+                neighbours[k] |= single(l);
+                neighbours[l] |= single(k);
+                factors[k][l] *= self.factors[i][j];
+                factors[l][k] *= self.factors[i][j];
+            }
+        }
+
+        Self {
+            leaves,
+            triples: self.triples,
+            cardinalities,
+            neighbours,
+            factors,
+        }
+    }
+
+    fn neighbours(&self, set: Set) -> Set {
+        nodes(set).fold(0, |n, i| n | self.neighbours[i]) & !set
+    }
+
+    /// Product of the factors of all edges between two disjoint sets
+    fn factor(&self, left: Set, right: Set) -> f64 {
+        nodes(left)
+            .flat_map(|i| nodes(self.neighbours[i] & right).map(move |j| self.factors[i][j]))
+            .product()
+    }
+
+    /// Estimated number of results of joining all scans of a set
+    fn cardinality(&self, set: Set) -> f64 {
+        let scans: f64 = nodes(set).map(|i| self.cardinalities[i]).product();
+        let factors: f64 = nodes(set)
+            .map(|i| self.factor(single(i), set & !up_to(i)))
+            .product();
+
+        (scans * factors).max(1.0)
+    }
+}
+
+/// Estimated number of results of joining all given scans, combining the estimates for single
+/// scans and pairs of scans the same way the join enumeration does
+pub(crate) fn estimate<'a>(
+    scans: Vec<Scan<'a>>,
+    estimator: &SelectivityEstimator,
+    triples: usize,
+) -> Result<f64, OptimizerError> {
+    let len = scans.len();
+    let graph = JoinGraph::new(scans, estimator, triples.max(1) as f64)?;
+
+    Ok(graph.cardinality(up_to(len - 1)))
+}
+
+/// The DPccp enumeration of plans for one join graph
+struct Enumeration<'g, 'a> {
+    graph: &'g JoinGraph<'a>,
+    best: HashMap<Set, Entry>,
+}
+
+impl<'g, 'a> Enumeration<'g, 'a> {
+    fn new(graph: &'g JoinGraph<'a>) -> Self {
+        let best = graph
+            .cardinalities
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                (
+                    single(i),
+                    Entry {
+                        cardinality: *c,
+                        // Every scan reads all triples of the database
+                        cost: SCAN_COST * graph.triples,
+                        split: None,
+                    },
+                )
+            })
+            .collect();
+
+        Self { graph, best }
+    }
+
+    fn run(mut self) -> HashMap<Set, Entry> {
+        for i in (0..self.graph.leaves.len()).rev() {
+            self.emit_csg(single(i));
+            self.enumerate_csg_rec(single(i), up_to(i));
+        }
+
+        self.best
+    }
+
+    fn enumerate_csg_rec(&mut self, s: Set, x: Set) {
+        let n = self.graph.neighbours(s) & !x;
+
+        for sub in subsets(n) {
+            self.emit_csg(s | sub);
+        }
+
+        for sub in subsets(n) {
+            self.enumerate_csg_rec(s | sub, x | n);
+        }
+    }
+
+    fn emit_csg(&mut self, s1: Set) {
+        let min = s1.trailing_zeros() as usize;
+        let x = s1 | up_to(min);
+        let n = self.graph.neighbours(s1) & !x;
+
+        for i in nodes(n) {
+            let s2 = single(i);
+
+            self.emit_csg_cmp(s1, s2);
+            self.enumerate_cmp_rec(s1, s2, x | (up_to(i) & n));
+        }
+    }
+
+    fn enumerate_cmp_rec(&mut self, s1: Set, s2: Set, x: Set) {
+        let n = self.graph.neighbours(s2) & !x;
+
+        for sub in subsets(n) {
+            self.emit_csg_cmp(s1, s2 | sub);
+        }
+
+        for sub in subsets(n) {
+            self.enumerate_cmp_rec(s1, s2 | sub, x | n);
+        }
+    }
+
+    fn emit_csg_cmp(&mut self, s1: Set, s2: Set) {
+        let (Some(p1), Some(p2)) = (self.best.get(&s1).copied(), self.best.get(&s2).copied())
+        else {
+            log::debug!("No plans for {s1:b} and {s2:b} yet");
+            return;
+        };
+
+        let cardinality = (p1.cardinality * p2.cardinality * self.graph.factor(s1, s2)).max(1.0);
+
+        // The hash table is built from the left operation, so try both sides
+        for (left, l, right, r) in [(s1, p1, s2, p2), (s2, p2, s1, p1)] {
+            let entry = Entry {
+                cardinality,
+                cost: join_cost(&l, &r, cardinality),
+                split: Some((left, right)),
+            };
+
+            match self.best.get(&(left | right)) {
+                Some(best) if best.cost <= entry.cost => {}
+                _ => {
+                    self.best.insert(left | right, entry);
+                }
+            }
+        }
+    }
+}
+
+fn join_cost(left: &Entry, right: &Entry, cardinality: f64) -> f64 {
+    left.cost
+        + right.cost
+        + BUILD_COST * left.cardinality
+        + PROBE_COST * right.cardinality
+        + OUTPUT_COST * cardinality
+}
+
+/// What adaptive optimization did while ordering the joins of a plan
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Adaptation {
+    /// Number of times the remaining joins of a basic graph pattern were planned again
+    pub(crate) reoptimizations: usize,
+    /// Time spent evaluating the materialized parts of the plan
+    pub(crate) materializing: Duration,
+}
+
+/**
+ * Optimizer that orders the joins of each basic graph pattern by dynamic programming.
+ *
+ * Basic graph patterns with more than `MAX_SCANS` scans are ordered greedily by `Optimize`
+ * instead.
+ */
+pub(crate) struct DpOptimize<'e> {
+    estimator: SelectivityEstimator<'e>,
+    condition: bool,
+    triples: f64,
+    /// The q-error above which joins are planned again, and the context to evaluate joins in
+    adaptive: Option<(f64, Context)>,
+
+    condition_info: ConditionInfo,
+    adaptation: Adaptation,
+}
+
+impl<'e> DpOptimize<'e> {
+    pub(crate) fn new(estimator: SelectivityEstimator<'e>, triples: usize) -> Self {
+        Self {
+            estimator,
+            condition: false,
+            triples: triples.max(1) as f64,
+            adaptive: None,
+
+            condition_info: ConditionInfo::new(),
+            adaptation: Adaptation::default(),
+        }
+    }
+
+    pub(crate) fn with_condition(self, condition: bool) -> Self {
+        Self { condition, ..self }
+    }
+
+    /**
+     * Evaluate the joins of each basic graph pattern one at a time while optimizing, in the order
+     * of the plan, and materialize their results.
+     *
+     * Once the number of results of a join is off from its estimate by more than `threshold`, the
+     * remaining joins are planned again with the actual numbers of results. Otherwise the rest of
+     * the plan is kept and evaluated as usual. The last join of a plan is never materialized, and
+     * neither are joins once the materialized results exceed the memory budget of `ctx`.
+     */
+    pub(crate) fn with_adaptive(self, threshold: Option<f64>, ctx: Context) -> Self {
+        Self {
+            adaptive: threshold.map(|t| (t, ctx)),
+            ..self
+        }
+    }
+
+    pub(crate) fn adaptation(&self) -> Adaptation {
+        self.adaptation
+    }
+
+    fn leaf<'a>(&self, leaf: &Operation<'a>) -> Operation<'a> {
+        match leaf {
+            Operation::Scan(_) if self.condition => {
+                insert_filter_operation(leaf.clone(), &self.condition_info)
+            }
+            _ => leaf.clone(),
+        }
+    }
+
+    /// The plan for a set of nodes, with the results of the sets in `done` instead of their plans
+    fn build<'a>(
+        &self,
+        graph: &JoinGraph<'a>,
+        best: &HashMap<Set, Entry>,
+        done: &HashMap<Set, Operation<'a>>,
+        set: Set,
+    ) -> Operation<'a> {
+        if let Some(materialized) = done.get(&set) {
+            return materialized.clone();
+        }
+
+        match best[&set].split {
+            Some((left, right)) => Operation::Join(Join::new(
+                self.build(graph, best, done, left),
+                self.build(graph, best, done, right),
+            )),
+            None => self.leaf(&graph.leaves[set.trailing_zeros() as usize]),
+        }
+    }
+
+    fn plan<'a>(
+        &self,
+        graph: &JoinGraph<'a>,
+        best: &HashMap<Set, Entry>,
+        done: &HashMap<Set, Operation<'a>>,
+        components: &[Set],
+    ) -> Operation<'a> {
+        components
+            .iter()
+            .map(|c| self.build(graph, best, done, *c))
+            .reduce(|left, right| Operation::Join(Join::new(left, right)))
+            .expect("Basic graph pattern without scans")
+    }
+
+    fn order<'a>(&self, scans: Vec<Scan<'a>>) -> Result<Operation<'a>, OptimizerError> {
+        let graph = JoinGraph::new(scans, &self.estimator, self.triples)?;
+        let best = Enumeration::new(&graph).run();
+
+        Ok(self.plan(&graph, &best, &HashMap::new(), &components(&graph, &best)))
+    }
+
+    fn adapt<'a>(
+        &mut self,
+        scans: Vec<Scan<'a>>,
+        threshold: f64,
+        ctx: &Context,
+    ) -> Result<Operation<'a>, OptimizerError> {
+        let mut graph = JoinGraph::new(scans, &self.estimator, self.triples)?;
+        // Bytes of all materialized results, which are kept until the plan is evaluated
+        let mut used = 0;
+
+        loop {
+            let best = Enumeration::new(&graph).run();
+            let components = components(&graph, &best);
+            let mut done: HashMap<Set, Operation<'a>> = HashMap::new();
+
+            loop {
+                // The results of the last join of a component are not materialized
+                let next = components
+                    .iter()
+                    .find_map(|c| first(&best, &done, *c).filter(|s| s != c));
+
+                let Some(set) = next else {
+                    return Ok(self.plan(&graph, &best, &done, &components));
+                };
+
+                let estimated = best[&set].cardinality;
+
+                let now = Instant::now();
+                let mut operation =
+                    Prepare::new(ctx.clone()).visit(&self.build(&graph, &best, &done, set));
+                let rows = collect_within(operation.by_ref(), ctx.memory_budget, &mut used);
+                self.adaptation.materializing += now.elapsed();
+
+                // The rest of the plan is evaluated as planned, streaming the results of the join
+                let Some(rows) = rows else {
+                    log::info!("Materialized joins exceed the memory budget, keeping the plan");
+
+                    return Ok(self.plan(&graph, &best, &done, &components));
+                };
+
+                let actual = rows.len() as f64;
+                done.insert(
+                    set,
+                    Operation::Materialized(Materialized::new(operation, rows, Some(estimated))),
+                );
+
+                let error = q_error(estimated, actual);
+                if ctx.timed_out() {
+                    return Ok(self.plan(&graph, &best, &done, &components));
+                }
+
+                if error <= threshold {
+                    continue;
+                }
+
+                log::info!(
+                    "Planning joins again, estimated {estimated:.0} results but got {actual} (q-error {error:.2})"
+                );
+                self.adaptation.reoptimizations += 1;
+
+                // Only the largest materialized sets are needed, the smaller ones are part of them
+                let groups = done
+                    .iter()
+                    .filter(|(s, _)| done.keys().all(|o| o == *s || *o & **s != **s))
+                    .map(|(s, o)| {
+                        let rows = match o {
+                            Operation::Materialized(m) => m.len(),
+                            _ => unreachable!("Only materialized results are done"),
+                        };
+
+                        (*s, o.clone(), rows as f64)
+                    })
+                    .collect();
+
+                graph = graph.materialize(groups);
+                break;
+            }
+        }
+    }
+}
+
+/// Connected components of the join graph, which are joined by cartesian products, ordered by
+/// their estimated number of results
+fn components(graph: &JoinGraph, best: &HashMap<Set, Entry>) -> Vec<Set> {
+    let mut remaining: Set = up_to(graph.leaves.len() - 1);
+    let mut components = Vec::new();
+    while remaining != 0 {
+        let mut component = single(remaining.trailing_zeros() as usize);
+        loop {
+            let next = component | graph.neighbours(component);
+            if next == component {
+                break;
+            }
+            component = next;
+        }
+
+        remaining &= !component;
+        components.push(component);
+    }
+
+    components.sort_by(|a, b| best[a].cardinality.total_cmp(&best[b].cardinality));
+
+    log::debug!(
+        "Ordered {} scans in {} components with estimated cost {:.2e}",
+        graph.leaves.len(),
+        components.len(),
+        components.iter().map(|c| best[c].cost).sum::<f64>()
+    );
+
+    components
+}
+
+/// The set of the first join of a plan that is evaluated, not counting the sets that are done
+fn first(best: &HashMap<Set, Entry>, done: &HashMap<Set, Operation>, set: Set) -> Option<Set> {
+    if done.contains_key(&set) {
+        return None;
+    }
+
+    let (left, right) = best[&set].split?;
+
+    first(best, done, left)
+        .or_else(|| first(best, done, right))
+        .or(Some(set))
+}
+
+type DpResult<'a> = Result<Operation<'a>, OptimizerError>;
+
+impl<'a, 'e> OperationVisitor<'a, DpResult<'a>> for DpOptimize<'e> {
+    fn visit(&mut self, o: &Operation<'a>) -> DpResult<'a> {
+        if self.estimator.is_off() {
+            return Ok(o.to_owned());
+        }
+
+        if let Ok(ops) = Flatten::new().visit(o) {
+            let scans = ops
+                .into_iter()
+                .map(|o| match o {
+                    Operation::Scan(s) => Ok(s),
+                    _ => Err(OptimizerError::UnexpectedOperation),
+                })
+                .collect::<Result<Vec<Scan<'a>>, OptimizerError>>()?;
+
+            if scans.len() > MAX_SCANS {
+                log::warn!(
+                    "Ordering {} scans greedily, dynamic programming supports up to {MAX_SCANS}",
+                    scans.len()
+                );
+
+                return Optimize::new(self.estimator.clone())
+                    .with_condition(self.condition)
+                    .visit(o);
+            }
+
+            return match self.adaptive.clone() {
+                Some((threshold, ctx)) => self.adapt(scans, threshold, &ctx),
+                None => self.order(scans),
+            };
+        }
+
+        match o {
+            Operation::Scan(s) => self.visit_scan(s),
+            Operation::Join(j) => self.visit_join(j),
+            Operation::Projection(p) => self.visit_projection(p),
+            Operation::Union(u) => self.visit_union(u),
+            Operation::Filter(f) => self.visit_filter(f),
+            Operation::LeftJoin(l) => self.visit_leftjoin(l),
+            Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
+            Operation::Offset(o) => self.visit_offset(o),
+            Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
+        }
+    }
+
+    fn visit_scan(&mut self, _: &Scan<'a>) -> DpResult<'a> {
+        panic!("Should have optimized before now")
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> DpResult<'a> {
+        // Joins with operations other than scans keep their order, only their inputs are optimized
+        Ok(Operation::Join(Join::new(
+            self.visit(&o.left)?,
+            self.visit(&o.right)?,
+        )))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Projection(Projection::new(
+            self.visit(&o.operation)?,
+            o.vars.to_owned(),
+        )))
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Union(Union::new(
+            self.visit(&o.left)?,
+            self.visit(&o.right)?,
+        )))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> DpResult<'a> {
+        if self.condition {
+            self.condition_info = self
+                .condition_info
+                .union(ConditionAnalyzer::new().visit(&o.condition));
+        }
+
+        Ok(Operation::Filter(Filter::new(
+            self.visit(&o.operation)?,
+            *o.condition.to_owned(),
+        )))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::LeftJoin(LeftJoin::new(
+            self.visit(&o.left)?,
+            self.visit(&o.right)?,
+        )))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Minus(Minus::new(
+            self.visit(&o.left)?,
+            self.visit(&o.right)?,
+        )))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Sort(Sort::new(
+            self.visit(&o.operation)?,
+            o.order.clone(),
+        )))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Offset(Offset::new(
+            self.visit(&o.operation)?,
+            o.offset,
+        )))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Limit(Limit::new(
+            self.visit(&o.operation)?,
+            o.limit,
+        )))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> DpResult<'a> {
+        Ok(Operation::Materialized(o.clone()))
+    }
+}
@@ -0,0 +1,124 @@
+use crate::semantics::{
+    operations::{
+        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+        minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+        Operation, OperationVisitor,
+    },
+    selectivity::{q_error, SelectivityEstimator},
+};
+
+/**
+ * Prints an evaluated plan with the estimated and the actual number of results of each operation.
+ *
+ * The plan has to be prepared with statistics enabled and share them with the evaluated plan,
+ * operations without statistics are printed without actual results.
+ */
+pub(crate) struct Explain<'e> {
+    estimator: SelectivityEstimator<'e>,
+}
+
+impl<'e> Explain<'e> {
+    pub(crate) fn new(estimator: SelectivityEstimator<'e>) -> Self {
+        Self { estimator }
+    }
+
+    fn line(&self, name: String, o: &Operation) -> String {
+        let (selectivity, estimate) = self.estimator.plan_estimates(o);
+
+        let actual = o.stats().rows();
+
+        vec![
+            Some(name),
+            selectivity.map(|s| format!("est. selectivity {s:1.2e},")),
+            estimate.map(|e| format!("est. rows {e:.0},")),
+            actual.map(|a| format!("actual rows {a},")),
+            estimate
+                .zip(actual)
+                .map(|(e, a)| format!("q-error {:.2},", q_error(e, a as f64))),
+            o.stats().elapsed().map(|d| format!("time {d:.2?}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>()
+        .join(" ")
+        .trim_end_matches(',')
+        .to_owned()
+    }
+}
+
+impl<'a, 'e> OperationVisitor<'a, String> for Explain<'e> {
+    fn visit(&mut self, o: &Operation<'a>) -> String {
+        let (name, children): (String, Vec<&Operation<'a>>) = match o {
+            Operation::Scan(s) => (
+                match s.restrictions() {
+                    Some(r) => format!(
+                        "SCAN {{ {} {} {} }} [{r}]",
+                        s.subject, s.predicate, s.object
+                    ),
+                    None => format!("SCAN {{ {} {} {} }}", s.subject, s.predicate, s.object),
+                },
+                vec![],
+            ),
+            Operation::Join(j) => (format!("JOIN {}", j.join_vars), vec![&j.left, &j.right]),
+            Operation::Projection(p) => (format!("PROJECTION {}", p.vars), vec![&p.operation]),
+            Operation::Union(u) => ("UNION".to_owned(), vec![&u.left, &u.right]),
+            Operation::Filter(f) => (format!("FILTER {}", f.condition), vec![&f.operation]),
+            Operation::LeftJoin(l) => ("LEFTJOIN".to_owned(), vec![&l.operation]),
+            Operation::Minus(m) => ("MINUS".to_owned(), vec![&m.left, &m.right]),
+            Operation::Sort(s) => (format!("SORT {}", s.order_by()), vec![&s.operation]),
+            Operation::Offset(f) => (format!("OFFSET {}", f.offset), vec![&f.operation]),
+            Operation::Limit(l) => (format!("LIMIT {}", l.limit), vec![&l.operation]),
+            Operation::Materialized(m) => ("MATERIALIZED".to_owned(), vec![&m.operation]),
+        };
+
+        std::iter::once(self.line(name, o))
+            .chain(children.into_iter().map(|c| self.visit(c)))
+            .collect::<Vec<String>>()
+            .join("\n")
+            .replace('\n', "\n  ")
+    }
+
+    fn visit_scan(&mut self, o: &Scan<'a>) -> String {
+        self.visit(&Operation::Scan(o.clone()))
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> String {
+        self.visit(&Operation::Join(o.clone()))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> String {
+        self.visit(&Operation::Projection(o.clone()))
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> String {
+        self.visit(&Operation::Union(o.clone()))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> String {
+        self.visit(&Operation::Filter(o.clone()))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> String {
+        self.visit(&Operation::LeftJoin(o.clone()))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> String {
+        self.visit(&Operation::Minus(o.clone()))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> String {
+        self.visit(&Operation::Sort(o.clone()))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> String {
+        self.visit(&Operation::Offset(o.clone()))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> String {
+        self.visit(&Operation::Limit(o.clone()))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> String {
+        self.visit(&Operation::Materialized(o.clone()))
+    }
+}
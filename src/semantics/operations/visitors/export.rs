@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+use crate::semantics::{
+    operations::Operation, options::PlanFormat, selectivity::SelectivityEstimator,
+};
+
+/// One operation of an exported plan with its estimates and, if recorded, its statistics
+#[derive(Debug, Serialize)]
+pub(crate) struct PlanNode {
+    pub(crate) operator: String,
+    /// Triple pattern of a scan, condition of a filter or parameters of other operations
+    pub(crate) detail: Option<String>,
+    pub(crate) bound: Vec<String>,
+    pub(crate) join_vars: Vec<String>,
+    pub(crate) selectivity: Option<f64>,
+    pub(crate) estimated_rows: Option<f64>,
+    pub(crate) actual_rows: Option<usize>,
+    pub(crate) elapsed_ms: Option<f64>,
+    pub(crate) children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    pub(crate) fn render(&self, format: PlanFormat) -> String {
+        match format {
+            PlanFormat::Dot => self.to_dot(),
+            PlanFormat::Json => self.to_json(),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Plan cannot be serialized")
+    }
+
+    /// Graphviz graph with one box per operation, pointing from each operation to its inputs
+    pub(crate) fn to_dot(&self) -> String {
+        let mut lines = vec![
+            "digraph plan {".to_owned(),
+            "  node [shape=box];".to_owned(),
+        ];
+
+        self.dot_nodes(&mut 0, &mut lines);
+        lines.push("}".to_owned());
+
+        lines.join("\n")
+    }
+
+    fn dot_nodes(&self, next: &mut usize, lines: &mut Vec<String>) -> usize {
+        let id = *next;
+        *next += 1;
+
+        let label = vec![
+            Some(self.operator.clone()),
+            self.detail.clone(),
+            (!self.join_vars.is_empty()).then(|| format!("join: {}", self.join_vars.join(", "))),
+            self.selectivity
+                .map(|s| format!("est. selectivity {s:1.2e}")),
+            self.estimated_rows.map(|e| format!("est. rows {e:.0}")),
+            self.actual_rows.map(|a| format!("actual rows {a}")),
+            self.elapsed_ms.map(|t| format!("time {t:.3}ms")),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|l| l.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<String>>()
+        .join("\\n");
+
+        lines.push(format!("  n{id} [label=\"{label}\"];"));
+
+        for child in self.children.iter() {
+            let child_id = child.dot_nodes(next, lines);
+            lines.push(format!("  n{id} -> n{child_id};"));
+        }
+
+        id
+    }
+}
+
+/**
+ * Converts a plan into a tree of plain nodes that can be exported as Graphviz DOT or JSON.
+ *
+ * Operations without statistics are exported without actual results, like in explain.
+ */
+pub(crate) struct Export<'e> {
+    estimator: SelectivityEstimator<'e>,
+}
+
+impl<'e> Export<'e> {
+    pub(crate) fn new(estimator: SelectivityEstimator<'e>) -> Self {
+        Self { estimator }
+    }
+
+    /// The node of `o` with the nodes of its inputs as children
+    pub(crate) fn node(&self, o: &Operation) -> PlanNode {
+        let (operator, detail, join_vars, children): (&str, _, _, Vec<&Operation>) = match o {
+            Operation::Scan(s) => (
+                "SCAN",
+                Some(match s.restrictions() {
+                    Some(r) => format!("{} {} {} [{r}]", s.subject, s.predicate, s.object),
+                    None => format!("{} {} {}", s.subject, s.predicate, s.object),
+                }),
+                vec![],
+                vec![],
+            ),
+            Operation::Join(j) => (
+                "JOIN",
+                None,
+                j.join_vars.iter().map(|v| v.name.clone()).collect(),
+                vec![&j.left, &j.right],
+            ),
+            Operation::Projection(p) => (
+                "PROJECTION",
+                Some(p.vars.to_string()),
+                vec![],
+                vec![&p.operation],
+            ),
+            Operation::Union(u) => ("UNION", None, vec![], vec![&u.left, &u.right]),
+            Operation::Filter(f) => (
+                "FILTER",
+                Some(f.condition.to_string()),
+                vec![],
+                vec![&f.operation],
+            ),
+            Operation::LeftJoin(l) => ("LEFTJOIN", None, vec![], vec![&l.operation]),
+            Operation::Minus(m) => ("MINUS", None, vec![], vec![&m.left, &m.right]),
+            Operation::Sort(s) => ("SORT", Some(s.order_by()), vec![], vec![&s.operation]),
+            Operation::Offset(f) => (
+                "OFFSET",
+                Some(f.offset.to_string()),
+                vec![],
+                vec![&f.operation],
+            ),
+            Operation::Limit(l) => (
+                "LIMIT",
+                Some(l.limit.to_string()),
+                vec![],
+                vec![&l.operation],
+            ),
+            Operation::Materialized(m) => ("MATERIALIZED", None, vec![], vec![&m.operation]),
+        };
+
+        let (selectivity, estimated_rows) = self.estimator.plan_estimates(o);
+
+        let mut bound: Vec<String> = o.bound_vars().into_iter().map(|v| v.name).collect();
+        bound.sort();
+
+        PlanNode {
+            operator: operator.to_owned(),
+            detail,
+            bound,
+            join_vars,
+            selectivity,
+            estimated_rows,
+            actual_rows: o.stats().rows(),
+            elapsed_ms: o.stats().elapsed().map(|d| d.as_secs_f64() * 1000.0),
+            children: children.into_iter().map(|c| self.node(c)).collect(),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::semantics::operations::{
-    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus, offset::Offset,
-    projection::Projection, scan::Scan, union::Union, Operation, OperationVisitor,
+    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+    minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+    Operation, OperationVisitor,
 };
 
 pub(crate) enum FlattenError {
@@ -17,11 +18,11 @@ impl Flatten {
 type FlattenResult<'a> = Result<Vec<Operation<'a>>, FlattenError>;
 
 impl<'a> OperationVisitor<'a, FlattenResult<'a>> for Flatten {
-    fn visit_scan(&mut self, o: &'a Scan<'a>) -> FlattenResult<'a> {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> FlattenResult<'a> {
         Ok(vec![Operation::Scan(o.to_owned())])
     }
 
-    fn visit_join(&mut self, o: &'a Join<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> FlattenResult<'a> {
         let mut left = self.visit(&o.left)?;
         let mut right = self.visit(&o.right)?;
 
@@ -30,31 +31,39 @@ impl<'a> OperationVisitor<'a, FlattenResult<'a>> for Flatten {
         Ok(left)
     }
 
-    fn visit_projection(&mut self, _o: &'a Projection<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_projection(&mut self, _o: &Projection<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_union(&mut self, _o: &'a Union<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_union(&mut self, _o: &Union<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_filter(&mut self, _o: &'a Filter<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_filter(&mut self, _o: &Filter<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_leftjoin(&mut self, _o: &'a LeftJoin<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_leftjoin(&mut self, _o: &LeftJoin<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_minus(&mut self, _o: &'a Minus<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_minus(&mut self, _o: &Minus<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_offset(&mut self, _o: &'a Offset<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_sort(&mut self, _o: &Sort<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 
-    fn visit_limit(&mut self, _o: &'a Limit<Operation<'a>>) -> FlattenResult<'a> {
+    fn visit_offset(&mut self, _o: &Offset<Operation<'a>>) -> FlattenResult<'a> {
+        Err(FlattenError::NonConjunctiveStructure)
+    }
+
+    fn visit_limit(&mut self, _o: &Limit<Operation<'a>>) -> FlattenResult<'a> {
+        Err(FlattenError::NonConjunctiveStructure)
+    }
+
+    fn visit_materialized(&mut self, _o: &Materialized<Operation<'a>>) -> FlattenResult<'a> {
         Err(FlattenError::NonConjunctiveStructure)
     }
 }
@@ -4,8 +4,9 @@ use super::{
 };
 use crate::{
     semantics::operations::{
-        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus, offset::Offset,
-        projection::Projection, scan::Scan, union::Union, Operation, OperationVisitor,
+        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+        minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+        Operation, OperationVisitor,
     },
     syntax::{query::Condition, query::Object},
 };
@@ -21,7 +22,7 @@ impl FilterInserter {
 }
 
 impl<'a> OperationVisitor<'a, Operation<'a>> for FilterInserter {
-    fn visit_scan(&mut self, o: &'a Scan) -> Operation<'a> {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
         let mut condition = None;
 
         for v in BoundVars::new().visit_scan(o) {
@@ -75,35 +76,43 @@ impl<'a> OperationVisitor<'a, Operation<'a>> for FilterInserter {
         }
     }
 
-    fn visit_join(&mut self, o: &'a Join<Operation<'a>>) -> Operation<'a> {
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
         Operation::Join(Join::new(self.visit(&o.left), self.visit(&o.right)))
     }
 
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> Operation<'a> {
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
         Operation::Projection(Projection::new(self.visit(&o.operation), o.vars.clone()))
     }
 
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> Operation<'a> {
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
         Operation::Union(Union::new(self.visit(&o.left), self.visit(&o.right)))
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> Operation<'a> {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
         Operation::Filter(Filter::new(self.visit(&o.operation), *o.condition.clone()))
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> Operation<'a> {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
         Operation::LeftJoin(LeftJoin::new(self.visit(&o.left), self.visit(&o.right)))
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> Operation<'a> {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
         Operation::Minus(Minus::new(self.visit(&o.left), self.visit(&o.right)))
     }
 
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> Operation<'a> {
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        Operation::Sort(Sort::new(self.visit(&o.operation), o.order.clone()))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
         Operation::Offset(Offset::new(self.visit(&o.operation), o.offset))
     }
 
-    fn visit_limit(&mut self, o: &'a Limit<Operation<'a>>) -> Operation<'a> {
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
         Operation::Limit(Limit::new(self.visit(&o.operation), o.limit))
     }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        Operation::Materialized(o.clone())
+    }
 }
@@ -2,8 +2,9 @@ use std::ops::Add;
 
 use crate::semantics::{
     operations::{
-        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus, offset::Offset,
-        projection::Projection, scan::Scan, union::Union, Operation, OperationVisitor,
+        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+        minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+        Operation, OperationVisitor,
     },
     results::OperationMeta,
 };
@@ -30,14 +31,14 @@ impl Add<Self> for OperationMeta {
 }
 
 impl<'a> OperationVisitor<'a, OperationMeta> for Meta {
-    fn visit_scan(&mut self, _o: &'a Scan) -> OperationMeta {
+    fn visit_scan(&mut self, _o: &Scan<'a>) -> OperationMeta {
         OperationMeta {
             scans: 1,
             ..Default::default()
         }
     }
 
-    fn visit_join(&mut self, o: &'a Join<Operation<'a>>) -> OperationMeta {
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> OperationMeta {
         let meta = OperationMeta {
             joins: 1,
             disjunct_joins: usize::from(o.join_vars().is_empty()),
@@ -47,15 +48,15 @@ impl<'a> OperationVisitor<'a, OperationMeta> for Meta {
         meta + self.visit(&o.left) + self.visit(&o.right)
     }
 
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> OperationMeta {
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> OperationMeta {
         self.visit(&o.operation)
     }
 
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> OperationMeta {
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> OperationMeta {
         self.visit(&o.left) + self.visit(&o.right)
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> OperationMeta {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> OperationMeta {
         let meta = OperationMeta {
             filters: 1,
             ..Default::default()
@@ -64,19 +65,27 @@ impl<'a> OperationVisitor<'a, OperationMeta> for Meta {
         meta + self.visit(&o.operation)
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> OperationMeta {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> OperationMeta {
         self.visit(&o.operation)
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> OperationMeta {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> OperationMeta {
         self.visit(&o.left) + self.visit(&o.right)
     }
 
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> OperationMeta {
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> OperationMeta {
         self.visit(&o.operation)
     }
 
-    fn visit_limit(&mut self, o: &'a Limit<Operation<'a>>) -> OperationMeta {
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> OperationMeta {
+        self.visit(&o.operation)
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> OperationMeta {
+        self.visit(&o.operation)
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> OperationMeta {
         self.visit(&o.operation)
     }
 }
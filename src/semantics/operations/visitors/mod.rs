@@ -1,9 +1,19 @@
 pub(crate) mod all_plans;
+pub(crate) mod bgp;
 pub(super) mod bound;
+pub(crate) mod canonical;
 pub(crate) mod condition;
+pub(crate) mod dp;
+pub(crate) mod explain;
+pub(crate) mod export;
 mod flatten;
 pub(crate) mod insert_filter;
 pub(super) mod meta;
 pub(crate) mod optimize;
 pub(crate) mod planner;
+pub(crate) mod prepare;
 pub(super) mod printer;
+pub(crate) mod pushdown;
+pub(crate) mod reuse;
+pub(crate) mod rewrite;
+pub(crate) mod unnest;
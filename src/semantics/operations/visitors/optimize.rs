@@ -8,10 +8,12 @@ use crate::{
             join::Join,
             leftjoin::LeftJoin,
             limit::Limit,
+            materialized::Materialized,
             minus::Minus,
             offset::Offset,
             projection::Projection,
             scan::Scan,
+            sort::Sort,
             union::Union,
             visitors::{bound::BoundVars, condition::VariableInfo},
             Operation, OperationVisitor,
@@ -87,9 +89,9 @@ impl From<SelectivityError> for OptimizerError {
     }
 }
 
-impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
-    fn visit(&mut self, o: &'a Operation<'a>) -> OptimizeResult<'a> {
-        if let SelectivityEstimator::Off = self.estimator {
+impl<'a, 'e> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'e> {
+    fn visit(&mut self, o: &Operation<'a>) -> OptimizeResult<'a> {
+        if self.estimator.is_off() {
             return Ok(o.to_owned());
         }
 
@@ -97,7 +99,7 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
             let mut scans: Vec<(Scan<'a>, f64)> = ops
                 .iter()
                 .map(|o| match o {
-                    Operation::Scan(s) => Ok((s.clone(), self.estimator.selectivity(s)?)),
+                    Operation::Scan(s) => Ok((s.clone(), self.estimator.selectivity(o)?)),
                     _ => Err(OptimizerError::UnexpectedOperation),
                 })
                 .collect::<Result<Vec<(Scan<'a>, f64)>, OptimizerError>>()?;
@@ -126,7 +128,11 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
                 .filter(|(a, b)| a != b)
                 .map(|(a, b)| Join::new(a.to_owned(), b.to_owned()))
                 // .filter(|j| !j.join_vars.is_empty())
-                .map(|j| Ok((j.clone(), self.estimator.selectivity(&j)?)))
+                .map(|j| {
+                    let selectivity = self.estimator.selectivity(&Operation::Join(j.clone()))?;
+
+                    Ok((j, selectivity))
+                })
                 .collect::<Result<Vec<(Join<Operation<'a>>, f64)>, OptimizerError>>()?
                 .into_iter()
                 .map(|(j, s)| {
@@ -164,8 +170,8 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
                 .find(|j| !j.join_vars.is_empty())
                 .unwrap_or(joins.first().unwrap().0.clone());
 
-            if self.estimator.selectivity(&*first.left).unwrap()
-                > self.estimator.selectivity(&*first.right).unwrap()
+            if self.estimator.selectivity(&first.left).unwrap()
+                > self.estimator.selectivity(&first.right).unwrap()
             {
                 first = Join::new(*first.right, *first.left)
             }
@@ -173,7 +179,9 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
             log::debug!(
                 "Picked first join with selectivity ({}): {:?}\n{}\n",
                 self.estimator,
-                self.estimator.selectivity(&first).unwrap_or_default(),
+                self.estimator
+                    .selectivity(&Operation::Join(first.clone()))
+                    .unwrap_or_default(),
                 first
             );
 
@@ -251,34 +259,40 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
             Operation::Filter(f) => self.visit_filter(f),
             Operation::LeftJoin(l) => self.visit_leftjoin(l),
             Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
             Operation::Offset(o) => self.visit_offset(o),
             Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
         }
     }
 
-    fn visit_scan(&mut self, _: &'a Scan<'a>) -> OptimizeResult<'a> {
+    fn visit_scan(&mut self, _: &Scan<'a>) -> OptimizeResult<'a> {
         panic!("Should have optimized before now")
     }
 
-    fn visit_join(&mut self, _: &'a Join<Operation<'a>>) -> OptimizeResult<'a> {
-        panic!("Should have optimized before now")
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> OptimizeResult<'a> {
+        // Joins with operations other than scans keep their order, only their inputs are optimized
+        Ok(Operation::Join(Join::new(
+            self.visit(&o.left)?,
+            self.visit(&o.right)?,
+        )))
     }
 
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::Projection(Projection::new(
             self.visit(&o.operation)?,
             o.vars.to_owned(),
         )))
     }
 
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::Union(Union::new(
             self.visit(&o.left)?,
             self.visit(&o.right)?,
         )))
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> OptimizeResult<'a> {
         if self.condition {
             self.condition_info = self
                 .condition_info
@@ -291,36 +305,50 @@ impl<'a> OperationVisitor<'a, OptimizeResult<'a>> for Optimize<'a> {
         )))
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::LeftJoin(LeftJoin::new(
             self.visit(&o.left)?,
             self.visit(&o.right)?,
         )))
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::Minus(Minus::new(
             self.visit(&o.left)?,
             self.visit(&o.right)?,
         )))
     }
 
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> OptimizeResult<'a> {
+        Ok(Operation::Sort(Sort::new(
+            self.visit(&o.operation)?,
+            o.order.clone(),
+        )))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::Offset(Offset::new(
             self.visit(&o.operation)?,
             o.offset,
         )))
     }
 
-    fn visit_limit(&mut self, o: &'a Limit<Operation<'a>>) -> OptimizeResult<'a> {
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> OptimizeResult<'a> {
         Ok(Operation::Limit(Limit::new(
             self.visit(&o.operation)?,
             o.limit,
         )))
     }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> OptimizeResult<'a> {
+        Ok(Operation::Materialized(o.clone()))
+    }
 }
 
-fn insert_filter_operation<'a>(op: Operation<'a>, info: &ConditionInfo) -> Operation<'a> {
+pub(super) fn insert_filter_operation<'a>(
+    op: Operation<'a>,
+    info: &ConditionInfo,
+) -> Operation<'a> {
     let operation = op.clone();
     let mut condition = None;
 
@@ -1,7 +1,7 @@
 use crate::{
     semantics::operations::{
         filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, offset::Offset,
-        projection::Projection, scan::Scan, union::Union, Operation,
+        projection::Projection, scan::Scan, sort::Sort, union::Union, Operation,
     },
     syntax::{
         database,
@@ -21,12 +21,12 @@ impl<'a> Planner<'a> {
     }
 }
 
-impl<'a> query::QueryVisitor<'a, Operation<'a>> for Planner<'a> {
+impl<'a, 'q> query::QueryVisitor<'q, Operation<'a>> for Planner<'a> {
     fn visit_select(
         &mut self,
-        vars: &'a query::Variables,
-        expr: &'a query::Expression,
-        modifier: &'a query::SolutionModifier,
+        vars: &'q query::Variables,
+        expr: &'q query::Expression,
+        modifier: &'q query::SolutionModifier,
     ) -> Operation<'a> {
         Operation::Projection(Projection::new(
             self.visit_modifier(expr, modifier),
@@ -36,19 +36,23 @@ impl<'a> query::QueryVisitor<'a, Operation<'a>> for Planner<'a> {
 
     fn visit_ask(
         &mut self,
-        expr: &'a query::Expression,
-        modifier: &'a query::SolutionModifier,
+        expr: &'q query::Expression,
+        modifier: &'q query::SolutionModifier,
     ) -> Operation<'a> {
         self.visit_modifier(expr, modifier)
     }
 
     fn visit_modifier(
         &mut self,
-        expr: &'a query::Expression,
-        modifier: &'a query::SolutionModifier,
+        expr: &'q query::Expression,
+        modifier: &'q query::SolutionModifier,
     ) -> Operation<'a> {
         let mut result = ExpressionVisitor::visit(self, expr);
 
+        if !modifier.order.is_empty() {
+            result = Operation::Sort(Sort::new(result, modifier.order.clone()));
+        }
+
         if let Some(offset) = modifier.offset {
             result = Operation::Offset(Offset::new(result, offset));
         }
@@ -61,12 +65,12 @@ impl<'a> query::QueryVisitor<'a, Operation<'a>> for Planner<'a> {
     }
 }
 
-impl<'a> ExpressionVisitor<'a, Operation<'a>> for Planner<'a> {
+impl<'a, 'q> ExpressionVisitor<'q, Operation<'a>> for Planner<'a> {
     fn visit_spo(
         &mut self,
-        subject: &'a query::Subject,
-        predicate: &'a query::Predicate,
-        object: &'a query::Object,
+        subject: &'q query::Subject,
+        predicate: &'q query::Predicate,
+        object: &'q query::Object,
     ) -> Operation<'a> {
         Operation::Scan(Scan::new(
             self.db,
@@ -78,32 +82,32 @@ impl<'a> ExpressionVisitor<'a, Operation<'a>> for Planner<'a> {
 
     fn visit_and(
         &mut self,
-        left: &'a query::Expression,
-        right: &'a query::Expression,
+        left: &'q query::Expression,
+        right: &'q query::Expression,
     ) -> Operation<'a> {
         Operation::Join(Join::new(self.visit(left), self.visit(right)))
     }
 
     fn visit_union(
         &mut self,
-        left: &'a query::Expression,
-        right: &'a query::Expression,
+        left: &'q query::Expression,
+        right: &'q query::Expression,
     ) -> Operation<'a> {
         Operation::Union(Union::new(self.visit(left), self.visit(right)))
     }
 
     fn visit_optional(
         &mut self,
-        left: &'a query::Expression,
-        right: &'a query::Expression,
+        left: &'q query::Expression,
+        right: &'q query::Expression,
     ) -> Operation<'a> {
         Operation::LeftJoin(LeftJoin::new(self.visit(left), self.visit(right)))
     }
 
     fn visit_filter(
         &mut self,
-        expr: &'a query::Expression,
-        cond: &'a query::Condition,
+        expr: &'q query::Expression,
+        cond: &'q query::Condition,
     ) -> Operation<'a> {
         Operation::Filter(Filter::new(self.visit(expr), Normalize::new().visit(cond)))
     }
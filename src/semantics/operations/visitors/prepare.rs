@@ -0,0 +1,102 @@
+use crate::semantics::operations::{
+    context::Context, filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit,
+    materialized::Materialized, minus::Minus, offset::Offset, projection::Projection, scan::Scan,
+    sort::Sort, stats::Stats, union::Union, Operation, OperationVisitor,
+};
+
+/// Prepares a plan for evaluation by handing the evaluation context to each operation, and
+/// enables the statistics of each operation if the context asks for them
+pub(crate) struct Prepare {
+    ctx: Context,
+}
+
+impl Prepare {
+    pub(crate) fn new(ctx: Context) -> Self {
+        Self { ctx }
+    }
+}
+
+impl<'a> OperationVisitor<'a, Operation<'a>> for Prepare {
+    fn visit(&mut self, o: &Operation<'a>) -> Operation<'a> {
+        let prepared = match o {
+            Operation::Scan(s) => self.visit_scan(s),
+            Operation::Join(j) => self.visit_join(j),
+            Operation::Projection(p) => self.visit_projection(p),
+            Operation::Union(u) => self.visit_union(u),
+            Operation::Filter(f) => self.visit_filter(f),
+            Operation::LeftJoin(l) => self.visit_leftjoin(l),
+            Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
+            Operation::Offset(o) => self.visit_offset(o),
+            Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
+        };
+
+        if self.ctx.analyze {
+            prepared.with_stats(Stats::enabled())
+        } else {
+            prepared
+        }
+    }
+
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
+        Operation::Scan(o.clone().with_context(self.ctx.clone()))
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
+        Operation::Join(
+            Join::new(self.visit(&o.left), self.visit(&o.right)).with_context(self.ctx.clone()),
+        )
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
+        Operation::Projection(
+            Projection::new(self.visit(&o.operation), o.vars.clone()).with_pushed(o.pushed),
+        )
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
+        Operation::Union(
+            Union::new(self.visit(&o.left), self.visit(&o.right)).with_context(self.ctx.clone()),
+        )
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
+        Operation::Filter(Filter::new(self.visit(&o.operation), *o.condition.clone()))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
+        // The evaluated operation is built in `LeftJoin::new`, so it has to be prepared as well
+        Operation::LeftJoin(LeftJoin {
+            operation: Box::new(self.visit(&o.operation)),
+            left: Box::new(self.visit(&o.left)),
+            right: Box::new(self.visit(&o.right)),
+            stats: o.stats.clone(),
+        })
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
+        Operation::Minus(
+            Minus::new(self.visit(&o.left), self.visit(&o.right)).with_context(self.ctx.clone()),
+        )
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        Operation::Sort(
+            Sort::new(self.visit(&o.operation), o.order.clone()).with_context(self.ctx.clone()),
+        )
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
+        Operation::Offset(Offset::new(self.visit(&o.operation), o.offset))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
+        Operation::Limit(Limit::new(self.visit(&o.operation), o.limit))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        // The operation was already evaluated, only its results are read again
+        Operation::Materialized(o.clone())
+    }
+}
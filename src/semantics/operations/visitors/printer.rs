@@ -1,9 +1,9 @@
 use crate::{
     semantics::{
         operations::{
-            filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, minus::Minus,
-            offset::Offset, projection::Projection, scan::Scan, union::Union, Operation,
-            OperationVisitor,
+            filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit,
+            materialized::Materialized, minus::Minus, offset::Offset, projection::Projection,
+            scan::Scan, sort::Sort, union::Union, Operation, OperationVisitor,
         },
         selectivity::SelectivityEstimator,
     },
@@ -53,7 +53,7 @@ impl<'a> Printer<'a> {
 }
 
 impl<'a, 'b> OperationVisitor<'a, String> for Printer<'b> {
-    fn visit_scan(&mut self, o: &'a Scan) -> String {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> String {
         let bound: Option<String> = if self.bound {
             let bound = BoundVars::new()
                 .visit_scan(o)
@@ -79,18 +79,22 @@ impl<'a, 'b> OperationVisitor<'a, String> for Printer<'b> {
             format!(
                 "{}: {:1.2e}",
                 estimator,
-                estimator.selectivity(o).unwrap_or(f64::NAN)
+                estimator
+                    .selectivity(&Operation::Scan(o.clone()))
+                    .unwrap_or(f64::NAN)
             )
         });
 
-        vec![Some("SCAN".to_owned()), selectivity, bound, bgp]
+        let pushed = o.restrictions().map(|r| format!("Pushed: {r}"));
+
+        vec![Some("SCAN".to_owned()), selectivity, bound, bgp, pushed]
             .into_iter()
             .flatten()
             .collect::<Vec<String>>()
             .join(" ")
     }
 
-    fn visit_join(&mut self, o: &'a Join<Operation<'a>>) -> String {
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> String {
         let bound: Option<String> = if self.bound {
             let bound = BoundVars::new()
                 .visit_join(o)
@@ -113,7 +117,9 @@ impl<'a, 'b> OperationVisitor<'a, String> for Printer<'b> {
             format!(
                 "{}: {:1.2e}",
                 estimator,
-                estimator.selectivity(o).unwrap_or(f64::NAN)
+                estimator
+                    .selectivity(&Operation::Join(o.clone()))
+                    .unwrap_or(f64::NAN)
             )
         });
 
@@ -128,45 +134,60 @@ impl<'a, 'b> OperationVisitor<'a, String> for Printer<'b> {
             .replace('\n', "\n  ")
     }
 
-    fn visit_projection(&mut self, o: &'a Projection<Operation<'a>>) -> String {
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> String {
         vec![format!("PROJECTION {}", o.vars), self.visit(&o.operation)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_union(&mut self, o: &'a Union<Operation<'a>>) -> String {
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> String {
         vec![format!("UNION"), self.visit(&o.left), self.visit(&o.right)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_filter(&mut self, o: &'a Filter<Operation<'a>>) -> String {
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> String {
         vec![format!("FILTER {}", o.condition), self.visit(&o.operation)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_leftjoin(&mut self, o: &'a LeftJoin<Operation<'a>>) -> String {
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> String {
         vec![format!("LEFTJOIN"), self.visit(&o.operation)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_minus(&mut self, o: &'a Minus<Operation<'a>>) -> String {
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> String {
         vec![format!("MINUS"), self.visit(&o.left), self.visit(&o.right)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_offset(&mut self, o: &'a Offset<Operation<'a>>) -> String {
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> String {
+        vec![format!("SORT {}", o.order_by()), self.visit(&o.operation)]
+            .join("\n")
+            .replace('\n', "\n  ")
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> String {
         vec![format!("OFFSET {}", o.offset), self.visit(&o.operation)]
             .join("\n")
             .replace('\n', "\n  ")
     }
 
-    fn visit_limit(&mut self, o: &'a Limit<Operation<'a>>) -> String {
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> String {
         vec![format!("LIMIT {}", o.limit), self.visit(&o.operation)]
             .join("\n")
             .replace('\n', "\n  ")
     }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> String {
+        vec![
+            format!("MATERIALIZED {} rows", o.len()),
+            self.visit(&o.operation),
+        ]
+        .join("\n")
+        .replace('\n', "\n  ")
+    }
 }
@@ -0,0 +1,128 @@
+use std::ops::Bound;
+
+use crate::{
+    semantics::operations::{
+        filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+        minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+        Operation, OperationVisitor,
+    },
+    syntax::{
+        query::{Condition, Object},
+        values::ValueRange,
+    },
+};
+
+/**
+ * Pushes the conditions of filters directly above scans into the scans.
+ *
+ * Equality with a constant replaces the variable in the triple pattern, strict comparisons with a
+ * number restrict the object to a range that is looked up in the value index of the database.
+ * All other conditions stay in the filter, including `<=` and `>=`, which are negated comparisons
+ * and so also hold for objects that are not numbers.
+ */
+pub(crate) struct Pushdown;
+
+impl Pushdown {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+/// The conditions of a conjunction
+fn conjuncts(c: &Condition) -> Vec<&Condition> {
+    match c {
+        Condition::And(c1, c2) => {
+            let mut result = conjuncts(c1);
+            result.append(&mut conjuncts(c2));
+
+            result
+        }
+        c => vec![c],
+    }
+}
+
+fn push<'a>(scan: &Scan<'a>, c: &Condition) -> Option<Scan<'a>> {
+    let scan = scan.clone();
+
+    match c {
+        Condition::Equals(Object::V(v), o) | Condition::Equals(o, Object::V(v)) => {
+            scan.with_constant(v, o)
+        }
+        Condition::LT(Object::V(v), Object::L(l)) | Condition::GT(Object::L(l), Object::V(v)) => {
+            scan.with_range(v, ValueRange::new().below(Bound::Excluded(l.parsed?)))
+        }
+        Condition::GT(Object::V(v), Object::L(l)) | Condition::LT(Object::L(l), Object::V(v)) => {
+            scan.with_range(v, ValueRange::new().above(Bound::Excluded(l.parsed?)))
+        }
+        _ => None,
+    }
+}
+
+impl<'a> OperationVisitor<'a, Operation<'a>> for Pushdown {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
+        Operation::Scan(o.clone())
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
+        Operation::Join(Join::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
+        Operation::Projection(
+            Projection::new(self.visit(&o.operation), o.vars.clone()).with_pushed(o.pushed),
+        )
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
+        Operation::Union(Union::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
+        let operation = self.visit(&o.operation);
+
+        let Operation::Scan(mut scan) = operation else {
+            return Operation::Filter(Filter::new(operation, *o.condition.clone()));
+        };
+
+        let mut remaining = Vec::new();
+
+        for c in conjuncts(&o.condition) {
+            match push(&scan, c) {
+                Some(pushed) => scan = pushed,
+                None => remaining.push(c.to_owned()),
+            }
+        }
+
+        match remaining
+            .into_iter()
+            .reduce(|c1, c2| Condition::And(Box::new(c1), Box::new(c2)))
+        {
+            Some(c) => Operation::Filter(Filter::new(Operation::Scan(scan), c)),
+            None => Operation::Scan(scan),
+        }
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
+        Operation::LeftJoin(LeftJoin::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
+        Operation::Minus(Minus::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        Operation::Sort(Sort::new(self.visit(&o.operation), o.order.clone()))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
+        Operation::Offset(Offset::new(self.visit(&o.operation), o.offset))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
+        Operation::Limit(Limit::new(self.visit(&o.operation), o.limit))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        Operation::Materialized(o.clone())
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+use crate::semantics::{
+    cache::ResultCache,
+    mapping::Mapping,
+    operations::{
+        context::Context,
+        filter::Filter,
+        join::Join,
+        leftjoin::LeftJoin,
+        limit::Limit,
+        materialized::{collect_within, Materialized},
+        minus::Minus,
+        offset::Offset,
+        projection::Projection,
+        scan::Scan,
+        sort::Sort,
+        union::Union,
+        Operation, OperationVisitor,
+    },
+};
+
+use super::{canonical::Canonical, prepare::Prepare};
+
+/**
+ * Replaces the subtrees of a plan whose results are cached by their results, and evaluates the
+ * others bottom up to cache their results for later plans.
+ *
+ * Scans are never cached, as they read the indexes of the database directly, and neither are
+ * limits and offsets or the subtrees above them, whose results depend on the order of the input
+ * of the limits. Subtrees below a limit only use cached results and are streamed otherwise, as
+ * only some of their results are needed. A subtree whose results do not fit in the cache budget,
+ * or in the memory budget together with the results materialized before, is streamed as well.
+ */
+pub(crate) struct Reuse {
+    cache: ResultCache,
+    ctx: Context,
+    /// Whether only some results of the current subtree are needed
+    limited: bool,
+    /// Number of limits and offsets in the plan so far
+    limits: usize,
+    /// Size of the results materialized in the plan so far
+    used: usize,
+    /// Time spent evaluating the subtrees that were not cached
+    materializing: Duration,
+}
+
+impl Reuse {
+    pub(crate) fn new(cache: ResultCache, ctx: Context) -> Self {
+        Self {
+            cache,
+            ctx,
+            limited: false,
+            limits: 0,
+            used: 0,
+            materializing: Duration::default(),
+        }
+    }
+
+    /// Whether only some results of the plan are needed, as for ASK queries
+    pub(crate) fn with_limited(self, limited: bool) -> Self {
+        Self { limited, ..self }
+    }
+
+    pub(crate) fn materializing(&self) -> Duration {
+        self.materializing
+    }
+
+    /// The operation with each of its inputs replaced by cached results
+    fn rebuild<'a>(&mut self, o: &Operation<'a>) -> Operation<'a> {
+        match o {
+            Operation::Scan(s) => self.visit_scan(s),
+            Operation::Join(j) => self.visit_join(j),
+            Operation::Projection(p) => self.visit_projection(p),
+            Operation::Union(u) => self.visit_union(u),
+            Operation::Filter(f) => self.visit_filter(f),
+            Operation::LeftJoin(l) => self.visit_leftjoin(l),
+            Operation::Minus(m) => self.visit_minus(m),
+            Operation::Sort(s) => self.visit_sort(s),
+            Operation::Offset(o) => self.visit_offset(o),
+            Operation::Limit(l) => self.visit_limit(l),
+            Operation::Materialized(m) => self.visit_materialized(m),
+        }
+    }
+}
+
+impl<'a> OperationVisitor<'a, Operation<'a>> for Reuse {
+    fn visit(&mut self, o: &Operation<'a>) -> Operation<'a> {
+        match o {
+            Operation::Scan(_) | Operation::Materialized(_) => return o.clone(),
+            Operation::Offset(_) | Operation::Limit(_) => {
+                self.limits += 1;
+
+                let limited = std::mem::replace(&mut self.limited, true);
+                let operation = self.rebuild(o);
+                self.limited = limited;
+
+                return operation;
+            }
+            _ => {}
+        }
+
+        let mut canonical = Canonical::new();
+        let key = canonical.operation(o);
+
+        if let Some(rows) = self.cache.get(&key) {
+            let rows: Vec<Mapping> = rows.iter().map(|row| canonical.mapping(row)).collect();
+            self.used += rows.iter().map(Mapping::estimated_size).sum::<usize>();
+
+            // Cached results were not estimated by the optimizer
+            return Operation::Materialized(Materialized::new(o.clone(), rows, None));
+        }
+
+        let (used, limits) = (self.used, self.limits);
+        let inputs = self.rebuild(o);
+
+        // Only some results are needed, or they depend on the order of a limit's input
+        if self.limited || self.limits > limits {
+            return inputs;
+        }
+
+        let budget = match self.ctx.memory_budget {
+            Some(memory) => self.cache.budget().min(memory.saturating_sub(self.used)),
+            None => self.cache.budget(),
+        };
+
+        let now = Instant::now();
+        let mut size = 0;
+        let rows = collect_within(
+            Prepare::new(self.ctx.clone()).visit(&inputs),
+            Some(budget),
+            &mut size,
+        );
+        self.materializing += now.elapsed();
+
+        let Some(rows) = rows else {
+            log::info!("Results exceed the cache budget, streaming them");
+
+            return inputs;
+        };
+
+        // The results of a subtree that failed are incomplete, the failure is reported when the
+        // plan is evaluated
+        if self.ctx.failed() {
+            return inputs;
+        }
+
+        // The materialized results of the inputs are dropped with them
+        self.used = used + size;
+
+        // The results of a subtree that was stopped early are incomplete
+        if !self.ctx.timed_out() {
+            self.cache
+                .insert(key, rows.iter().map(|m| canonical.row(m)).collect());
+        }
+
+        // The subtree is kept without its evaluation, which would hold the results of its inputs
+        Operation::Materialized(Materialized::new(o.clone(), rows, None))
+    }
+
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
+        Operation::Scan(o.clone())
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
+        Operation::Join(Join::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
+        Operation::Projection(
+            Projection::new(self.visit(&o.operation), o.vars.clone()).with_pushed(o.pushed),
+        )
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
+        Operation::Union(Union::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
+        Operation::Filter(Filter::new(self.visit(&o.operation), *o.condition.clone()))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
+        Operation::LeftJoin(LeftJoin::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
+        Operation::Minus(Minus::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        Operation::Sort(Sort::new(self.visit(&o.operation), o.order.clone()))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
+        Operation::Offset(Offset::new(self.visit(&o.operation), o.offset))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
+        Operation::Limit(Limit::new(self.visit(&o.operation), o.limit))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        Operation::Materialized(o.clone())
+    }
+}
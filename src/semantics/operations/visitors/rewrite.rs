@@ -0,0 +1,446 @@
+use std::collections::HashSet;
+
+use crate::{
+    semantics::{
+        mapping::satisfies,
+        operations::{
+            filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit,
+            materialized::Materialized, minus::Minus, offset::Offset, projection::Projection,
+            scan::Scan, sort::Sort, union::Union, Operation, OperationVisitor,
+        },
+        options::Rewrite,
+    },
+    syntax::query::{Condition, Object, Variable, Variables},
+};
+
+use super::{
+    bound::{possible_vars, BoundVars},
+    condition::condition_vars,
+};
+
+/// Passes after which rewriting stops, even if the rules still change the plan
+const MAX_PASSES: usize = 100;
+
+/**
+ * Rewrites a plan with a set of rules until none of them changes it anymore.
+ *
+ * Each pass rewrites the plan bottom up, applying the rules to every operation after its inputs.
+ */
+pub(crate) struct Rewriter {
+    rules: Vec<Rewrite>,
+}
+
+impl Rewriter {
+    pub(crate) fn new(rules: Vec<Rewrite>) -> Self {
+        Self { rules }
+    }
+
+    pub(crate) fn rewrite<'a>(&self, o: &Operation<'a>) -> Operation<'a> {
+        let mut current = o.to_owned();
+
+        if self.rules.is_empty() {
+            return current;
+        }
+
+        for pass in 1..=MAX_PASSES {
+            let mut visitor = Pass {
+                rules: &self.rules,
+                changed: false,
+            };
+
+            current = visitor.visit(&current);
+
+            if !visitor.changed {
+                log::info!("Rewriting reached a fixpoint after {pass} passes");
+
+                return current;
+            }
+        }
+
+        log::warn!("Rewriting stopped after {MAX_PASSES} passes without a fixpoint");
+
+        current
+    }
+}
+
+struct Pass<'r> {
+    rules: &'r [Rewrite],
+    changed: bool,
+}
+
+impl<'r> Pass<'r> {
+    fn apply<'a>(&mut self, o: Operation<'a>) -> Operation<'a> {
+        let mut current = o;
+
+        // Every rule is applied at most once per operation and pass, the next pass continues
+        for rule in self.rules {
+            if let Some(next) = apply(*rule, &current) {
+                log::debug!("{rule} rewrites\n{current}\ninto\n{next}");
+
+                self.changed = true;
+                current = next;
+            }
+        }
+
+        current
+    }
+}
+
+impl<'a, 'r> OperationVisitor<'a, Operation<'a>> for Pass<'r> {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
+        self.apply(Operation::Scan(o.clone()))
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
+        let join = Join::new(self.visit(&o.left), self.visit(&o.right));
+
+        self.apply(Operation::Join(join))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
+        let projection =
+            Projection::new(self.visit(&o.operation), o.vars.clone()).with_pushed(o.pushed);
+
+        self.apply(Operation::Projection(projection))
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
+        let union = Union::new(self.visit(&o.left), self.visit(&o.right));
+
+        self.apply(Operation::Union(union))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
+        let filter = Filter::new(self.visit(&o.operation), *o.condition.clone());
+
+        self.apply(Operation::Filter(filter))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
+        let leftjoin = LeftJoin::new(self.visit(&o.left), self.visit(&o.right));
+
+        self.apply(Operation::LeftJoin(leftjoin))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
+        let minus = Minus::new(self.visit(&o.left), self.visit(&o.right));
+
+        self.apply(Operation::Minus(minus))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        let sort = Sort::new(self.visit(&o.operation), o.order.clone());
+
+        self.apply(Operation::Sort(sort))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
+        let offset = Offset::new(self.visit(&o.operation), o.offset);
+
+        self.apply(Operation::Offset(offset))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
+        let limit = Limit::new(self.visit(&o.operation), o.limit);
+
+        self.apply(Operation::Limit(limit))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        Operation::Materialized(o.clone())
+    }
+}
+
+/// Rewrite an operation with a rule, `None` if the rule does not apply to it
+fn apply<'a>(rule: Rewrite, o: &Operation<'a>) -> Option<Operation<'a>> {
+    match rule {
+        Rewrite::SplitFilter => split_filter(o),
+        Rewrite::PushFilter => push_filter(o),
+        Rewrite::FlattenUnion => flatten_union(o),
+        Rewrite::PushProjection => push_projection(o),
+        Rewrite::OptionalToJoin => optional_to_join(o),
+        Rewrite::FoldConstants => fold_constants(o),
+    }
+}
+
+fn split_filter<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    match o {
+        Operation::Filter(f) => match f.condition.as_ref() {
+            Condition::And(c1, c2) => Some(Operation::Filter(Filter::new(
+                Operation::Filter(Filter::new(*f.operation.clone(), *c1.clone())),
+                *c2.clone(),
+            ))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/**
+ * Move the conditions of a stack of filters into the inputs of the operation below them.
+ *
+ * A condition only moves into an input that binds all of its variables in every result, so that
+ * the condition sees the same values before and after the operation.
+ */
+fn push_filter<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    let mut conditions = Vec::new();
+    let mut base = o;
+
+    while let Operation::Filter(f) = base {
+        conditions.push(f.condition.as_ref().to_owned());
+        base = &f.operation;
+    }
+
+    if conditions.is_empty() {
+        return None;
+    }
+
+    let covers =
+        |o: &Operation<'a>, c: &Condition| condition_vars(c).is_subset(&BoundVars::new().visit(o));
+
+    let (pushed, remaining): (Operation<'a>, Vec<Condition>) = match base {
+        Operation::Join(j) => {
+            let (left, rest): (Vec<_>, Vec<_>) =
+                conditions.into_iter().partition(|c| covers(&j.left, c));
+            let (right, remaining): (Vec<_>, Vec<_>) =
+                rest.into_iter().partition(|c| covers(&j.right, c));
+
+            if left.is_empty() && right.is_empty() {
+                return None;
+            }
+
+            (
+                Operation::Join(Join::new(
+                    filtered(*j.left.clone(), left),
+                    filtered(*j.right.clone(), right),
+                )),
+                remaining,
+            )
+        }
+        Operation::Union(u) => (
+            Operation::Union(Union::new(
+                filtered(*u.left.clone(), conditions.clone()),
+                filtered(*u.right.clone(), conditions),
+            )),
+            vec![],
+        ),
+        Operation::LeftJoin(l) => {
+            let (left, remaining): (Vec<_>, Vec<_>) =
+                conditions.into_iter().partition(|c| covers(&l.left, c));
+
+            if left.is_empty() {
+                return None;
+            }
+
+            (
+                Operation::LeftJoin(LeftJoin::new(
+                    filtered(*l.left.clone(), left),
+                    *l.right.clone(),
+                )),
+                remaining,
+            )
+        }
+        // The results of minus are results of its left side, unchanged
+        Operation::Minus(m) => (
+            Operation::Minus(Minus::new(
+                filtered(*m.left.clone(), conditions),
+                *m.right.clone(),
+            )),
+            vec![],
+        ),
+        _ => return None,
+    };
+
+    Some(filtered(pushed, remaining))
+}
+
+fn flatten_union<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    match o {
+        Operation::Union(outer) => match outer.left.as_ref() {
+            Operation::Union(inner) => Some(Operation::Union(Union::new(
+                *inner.left.clone(),
+                Operation::Union(Union::new(*inner.right.clone(), *outer.right.clone())),
+            ))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/**
+ * Project the inputs of the operation below a projection to the variables that are projected or
+ * needed by the operation itself.
+ */
+fn push_projection<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    let Operation::Projection(p) = o else {
+        return None;
+    };
+
+    let vars: HashSet<Variable> = p.vars.iter().cloned().collect();
+
+    let operation = match p.operation.as_ref() {
+        Operation::Join(j) => {
+            let shared: HashSet<Variable> = possible_vars(&j.left)
+                .intersection(&possible_vars(&j.right))
+                .cloned()
+                .collect();
+            let keep: HashSet<Variable> = vars.union(&shared).cloned().collect();
+
+            let (left, right) = (project(&j.left, &keep), project(&j.right, &keep));
+
+            if left.is_none() && right.is_none() {
+                return None;
+            }
+
+            Operation::Join(Join::new(
+                left.unwrap_or(*j.left.clone()),
+                right.unwrap_or(*j.right.clone()),
+            ))
+        }
+        Operation::Union(u) => {
+            let (left, right) = (project(&u.left, &vars), project(&u.right, &vars));
+
+            if left.is_none() && right.is_none() {
+                return None;
+            }
+
+            Operation::Union(Union::new(
+                left.unwrap_or(*u.left.clone()),
+                right.unwrap_or(*u.right.clone()),
+            ))
+        }
+        Operation::Filter(f) => {
+            let keep: HashSet<Variable> =
+                vars.union(&condition_vars(&f.condition)).cloned().collect();
+
+            Operation::Filter(Filter::new(
+                project(&f.operation, &keep)?,
+                *f.condition.clone(),
+            ))
+        }
+        _ => return None,
+    };
+
+    Some(Operation::Projection(
+        Projection::new(operation, p.vars.clone()).with_pushed(p.pushed),
+    ))
+}
+
+/// Pushed down projection of an operation to `keep`, `None` if it binds no other variables
+fn project<'a>(o: &Operation<'a>, keep: &HashSet<Variable>) -> Option<Operation<'a>> {
+    let bound = possible_vars(o);
+
+    if bound.is_subset(keep) {
+        return None;
+    }
+
+    let mut vars: Vec<Variable> = bound.intersection(keep).cloned().collect();
+    vars.sort();
+
+    Some(Operation::Projection(
+        Projection::new(o.to_owned(), Variables::new(vars)).with_pushed(true),
+    ))
+}
+
+/**
+ * Replace an optional below a filter by a join, if the filter rejects every result in which a
+ * variable that only the optional side binds is unbound.
+ *
+ * Those are exactly the results of the left side that have no compatible result on the right.
+ */
+fn optional_to_join<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    let Operation::Filter(f) = o else {
+        return None;
+    };
+
+    let Operation::LeftJoin(l) = f.operation.as_ref() else {
+        return None;
+    };
+
+    let left = possible_vars(&l.left);
+    let right = BoundVars::new().visit(&l.right);
+
+    let rejected = right
+        .difference(&left)
+        .any(|v| rejects_unbound(&f.condition, v));
+
+    rejected.then(|| {
+        Operation::Filter(Filter::new(
+            Operation::Join(Join::new(*l.left.clone(), *l.right.clone())),
+            *f.condition.clone(),
+        ))
+    })
+}
+
+/// Whether a condition is false for every mapping in which `v` is unbound
+fn rejects_unbound(c: &Condition, v: &Variable) -> bool {
+    let is = |o: &Object| matches!(o, Object::V(x) if x == v);
+
+    match c {
+        Condition::Equals(o1, o2) => {
+            (is(o1) && !matches!(o2, Object::V(_))) || (is(o2) && !matches!(o1, Object::V(_)))
+        }
+        Condition::LT(o1, o2) | Condition::GT(o1, o2) => is(o1) || is(o2),
+        Condition::Bound(x) => x == v,
+        Condition::Not(_) => false,
+        Condition::And(c1, c2) => rejects_unbound(c1, v) || rejects_unbound(c2, v),
+        Condition::Or(c1, c2) => rejects_unbound(c1, v) && rejects_unbound(c2, v),
+    }
+}
+
+/// Result of folding a condition, which is either known or still depends on variables
+enum Folded {
+    True,
+    False,
+    Condition(Condition),
+}
+
+fn fold(c: &Condition) -> Folded {
+    let folded = |b: bool| if b { Folded::True } else { Folded::False };
+
+    match c {
+        Condition::Not(inner) => match fold(inner) {
+            Folded::True => Folded::False,
+            Folded::False => Folded::True,
+            Folded::Condition(c) => Folded::Condition(Condition::Not(Box::new(c))),
+        },
+        Condition::And(c1, c2) => match (fold(c1), fold(c2)) {
+            (Folded::False, _) | (_, Folded::False) => Folded::False,
+            (Folded::True, other) | (other, Folded::True) => other,
+            (Folded::Condition(c1), Folded::Condition(c2)) => {
+                Folded::Condition(Condition::And(Box::new(c1), Box::new(c2)))
+            }
+        },
+        Condition::Or(c1, c2) => match (fold(c1), fold(c2)) {
+            (Folded::True, _) | (_, Folded::True) => Folded::True,
+            (Folded::False, other) | (other, Folded::False) => other,
+            (Folded::Condition(c1), Folded::Condition(c2)) => {
+                Folded::Condition(Condition::Or(Box::new(c1), Box::new(c2)))
+            }
+        },
+        c if condition_vars(c).is_empty() => folded(satisfies(c, &|_| None)),
+        c => Folded::Condition(c.to_owned()),
+    }
+}
+
+/// Drop filters that always hold and replace filters that never hold by an empty limit
+fn fold_constants<'a>(o: &Operation<'a>) -> Option<Operation<'a>> {
+    let Operation::Filter(f) = o else {
+        return None;
+    };
+
+    match fold(&f.condition) {
+        Folded::True => Some(*f.operation.clone()),
+        Folded::False => Some(Operation::Limit(Limit::new(*f.operation.clone(), 0))),
+        Folded::Condition(c) if c != *f.condition => {
+            Some(Operation::Filter(Filter::new(*f.operation.clone(), c)))
+        }
+        Folded::Condition(_) => None,
+    }
+}
+
+fn filtered<'a>(o: Operation<'a>, conditions: Vec<Condition>) -> Operation<'a> {
+    conditions
+        .into_iter()
+        .fold(o, |o, c| Operation::Filter(Filter::new(o, c)))
+}
@@ -0,0 +1,129 @@
+use crate::semantics::operations::{
+    filter::Filter, join::Join, leftjoin::LeftJoin, limit::Limit, materialized::Materialized,
+    minus::Minus, offset::Offset, projection::Projection, scan::Scan, sort::Sort, union::Union,
+    Operation, OperationVisitor,
+};
+
+use super::{
+    bound::{possible_vars, BoundVars},
+    condition::condition_vars,
+    flatten::Flatten,
+};
+
+/**
+ * Moves joins below left joins, unions and filters, so that the optimizer sees larger conjunctive
+ * blocks of scans to reorder.
+ *
+ * A join with a left join becomes a left join of the join with its mandatory part, which is
+ * equivalent for well-designed patterns: every variable the join shares with the optional part
+ * must be bound by the mandatory part. A join with a union is distributed over the branches when
+ * the other input is conjunctive and joins with each branch on some variable. A join with a filter
+ * becomes a filter of the join, if the condition only uses variables bound by the filtered input.
+ */
+pub(crate) struct Unnest;
+
+impl Unnest {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Whether `o` can be joined with the mandatory part of `l` without changing the results
+fn well_designed(o: &Operation, l: &LeftJoin<Operation>) -> bool {
+    let mandatory = BoundVars::new().visit(&l.left);
+
+    possible_vars(o)
+        .intersection(&possible_vars(&l.right))
+        .all(|v| mandatory.contains(v))
+}
+
+/// Whether distributing a join of `o` over the branches of `u` exposes more joins of scans
+fn distributes(o: &Operation, u: &Union<Operation>) -> bool {
+    let bound = BoundVars::new().visit(o);
+
+    Flatten::new().visit(o).is_ok()
+        && [&u.left, &u.right]
+            .iter()
+            .all(|b| !bound.is_disjoint(&BoundVars::new().visit(b)))
+}
+
+/// Whether the condition of `f` can be evaluated after joining its input with another operation
+fn lifts(f: &Filter<Operation>) -> bool {
+    condition_vars(&f.condition).is_subset(&BoundVars::new().visit(&f.operation))
+}
+
+fn lift<'a>(left: Operation<'a>, right: Operation<'a>) -> Operation<'a> {
+    match (&left, &right) {
+        (_, Operation::Filter(f)) if lifts(f) => Operation::Filter(Filter::new(
+            lift(left.clone(), *f.operation.clone()),
+            *f.condition.clone(),
+        )),
+        (Operation::Filter(f), _) if lifts(f) => Operation::Filter(Filter::new(
+            lift(*f.operation.clone(), right.clone()),
+            *f.condition.clone(),
+        )),
+        (_, Operation::LeftJoin(l)) if well_designed(&left, l) => Operation::LeftJoin(
+            LeftJoin::new(lift(left.clone(), *l.left.clone()), *l.right.clone()),
+        ),
+        (Operation::LeftJoin(l), _) if well_designed(&right, l) => Operation::LeftJoin(
+            LeftJoin::new(lift(*l.left.clone(), right.clone()), *l.right.clone()),
+        ),
+        (_, Operation::Union(u)) if distributes(&left, u) => Operation::Union(Union::new(
+            lift(left.clone(), *u.left.clone()),
+            lift(left.clone(), *u.right.clone()),
+        )),
+        (Operation::Union(u), _) if distributes(&right, u) => Operation::Union(Union::new(
+            lift(*u.left.clone(), right.clone()),
+            lift(*u.right.clone(), right.clone()),
+        )),
+        _ => Operation::Join(Join::new(left, right)),
+    }
+}
+
+impl<'a> OperationVisitor<'a, Operation<'a>> for Unnest {
+    fn visit_scan(&mut self, o: &Scan<'a>) -> Operation<'a> {
+        Operation::Scan(o.clone())
+    }
+
+    fn visit_join(&mut self, o: &Join<Operation<'a>>) -> Operation<'a> {
+        lift(self.visit(&o.left), self.visit(&o.right))
+    }
+
+    fn visit_projection(&mut self, o: &Projection<Operation<'a>>) -> Operation<'a> {
+        Operation::Projection(
+            Projection::new(self.visit(&o.operation), o.vars.clone()).with_pushed(o.pushed),
+        )
+    }
+
+    fn visit_union(&mut self, o: &Union<Operation<'a>>) -> Operation<'a> {
+        Operation::Union(Union::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_filter(&mut self, o: &Filter<Operation<'a>>) -> Operation<'a> {
+        Operation::Filter(Filter::new(self.visit(&o.operation), *o.condition.clone()))
+    }
+
+    fn visit_leftjoin(&mut self, o: &LeftJoin<Operation<'a>>) -> Operation<'a> {
+        Operation::LeftJoin(LeftJoin::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_minus(&mut self, o: &Minus<Operation<'a>>) -> Operation<'a> {
+        Operation::Minus(Minus::new(self.visit(&o.left), self.visit(&o.right)))
+    }
+
+    fn visit_sort(&mut self, o: &Sort<Operation<'a>>) -> Operation<'a> {
+        Operation::Sort(Sort::new(self.visit(&o.operation), o.order.clone()))
+    }
+
+    fn visit_offset(&mut self, o: &Offset<Operation<'a>>) -> Operation<'a> {
+        Operation::Offset(Offset::new(self.visit(&o.operation), o.offset))
+    }
+
+    fn visit_limit(&mut self, o: &Limit<Operation<'a>>) -> Operation<'a> {
+        Operation::Limit(Limit::new(self.visit(&o.operation), o.limit))
+    }
+
+    fn visit_materialized(&mut self, o: &Materialized<Operation<'a>>) -> Operation<'a> {
+        Operation::Materialized(o.clone())
+    }
+}
@@ -1,13 +1,31 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use clap::ValueEnum;
+use itertools::Itertools;
+
+use super::cache::ResultCache;
 
 #[derive(Clone)]
 pub struct EvalOptions {
     pub optimizer: Optimizer,
+    pub estimator: Option<String>,
+    pub join_order: JoinOrder,
+    pub rewrites: Vec<Rewrite>,
     pub condition: bool,
     pub dryrun: bool,
     pub log: bool,
+    pub threads: usize,
+    pub batch: bool,
+    pub pipelined: bool,
+    pub memory_budget: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub explain: bool,
+    pub export: Option<PlanFormat>,
+    pub adaptive: Option<f64>,
+    pub samples: usize,
+    pub sample_budget: Option<Duration>,
+    pub seed: u64,
+    pub cache: Option<ResultCache>,
 }
 
 impl EvalOptions {
@@ -19,6 +37,20 @@ impl EvalOptions {
         Self { optimizer, ..self }
     }
 
+    /// Estimate with the estimator registered under the given name instead of the optimizer's
+    pub fn with_estimator(self, estimator: Option<String>) -> Self {
+        Self { estimator, ..self }
+    }
+
+    pub fn with_join_order(self, join_order: JoinOrder) -> Self {
+        Self { join_order, ..self }
+    }
+
+    /// Apply the given rewrite rules to the optimized plan until none of them changes it anymore
+    pub fn with_rewrites(self, rewrites: Vec<Rewrite>) -> Self {
+        Self { rewrites, ..self }
+    }
+
     pub fn with_log(self, log: bool) -> Self {
         Self { log, ..self }
     }
@@ -30,15 +62,117 @@ impl EvalOptions {
     pub fn with_condition(self, condition: bool) -> Self {
         Self { condition, ..self }
     }
+
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_batch(self, batch: bool) -> Self {
+        Self { batch, ..self }
+    }
+
+    pub fn with_pipelined(self, pipelined: bool) -> Self {
+        Self { pipelined, ..self }
+    }
+
+    /// Stop evaluating a query once it has run for longer than `timeout`
+    pub fn with_timeout(self, timeout: Option<Duration>) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Record the results of each operation and report them next to the estimates of the optimizer
+    pub fn with_explain(self, explain: bool) -> Self {
+        Self { explain, ..self }
+    }
+
+    /// Export the evaluated plan with its estimates and the results of each operation
+    pub fn with_export(self, export: Option<PlanFormat>) -> Self {
+        Self { export, ..self }
+    }
+
+    /**
+     * Evaluate the joins of each basic graph pattern step by step while optimizing, and plan the
+     * remaining joins again with the actual numbers of results whenever an estimate is off by a
+     * q-error of more than `threshold`
+     *
+     * Joins are ordered by dynamic programming in this case, regardless of the join order. Once
+     * the materialized results exceed the memory budget, the remaining joins are evaluated as
+     * planned without materializing them.
+     */
+    pub fn with_adaptive(self, threshold: Option<f64>) -> Self {
+        Self {
+            adaptive: threshold,
+            ..self
+        }
+    }
+
+    /**
+     * Sample `samples` triples per scan and make as many random walks per join when estimating
+     * with the sampling optimizer, stopping each estimate after `budget`
+     */
+    pub fn with_sampling(self, samples: usize, budget: Option<Duration>) -> Self {
+        Self {
+            samples,
+            sample_budget: budget,
+            ..self
+        }
+    }
+
+    /// Make every random decision of the optimizer depend on `seed` only, for reproducible plans
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /**
+     * Read the results of subtrees of the plan from `cache` if an earlier evaluation cached them,
+     * and cache the results of the others
+     *
+     * The cache is shared by all clones of it, so it can be handed to the evaluation of each query
+     * on the same database.
+     */
+    pub fn with_cache(self, cache: Option<ResultCache>) -> Self {
+        Self { cache, ..self }
+    }
+
+    /**
+     * Limit the bytes each join, minus and sort operation keeps in memory, larger state is spilled
+     * to disk
+     *
+     * Joins are then evaluated on a single thread and batch evaluation is rejected.
+     */
+    pub fn with_memory_budget(self, memory_budget: Option<usize>) -> Self {
+        Self {
+            memory_budget,
+            ..self
+        }
+    }
 }
 
 impl Default for EvalOptions {
     fn default() -> Self {
         Self {
             optimizer: Optimizer::default(),
+            estimator: None,
+            join_order: JoinOrder::default(),
+            rewrites: Vec::new(),
             condition: false,
             dryrun: false,
             log: true,
+            threads: 1,
+            batch: false,
+            pipelined: false,
+            memory_budget: None,
+            timeout: None,
+            explain: false,
+            export: None,
+            adaptive: None,
+            samples: 100,
+            sample_budget: None,
+            seed: 0,
+            cache: None,
         }
     }
 }
@@ -47,8 +181,29 @@ impl Display for EvalOptions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("Evaluation Options:\n")?;
         f.write_str(&format!("Optimizer: {}\n", self.optimizer))?;
+        f.write_str(&format!("Estimator: {:?}\n", self.estimator))?;
+        f.write_str(&format!("Join order: {}\n", self.join_order))?;
+        f.write_str(&format!(
+            "Rewrites: [{}]\n",
+            self.rewrites.iter().map(|r| r.to_string()).join(", ")
+        ))?;
         f.write_str(&format!("Filter condition analysis: {}\n", self.condition))?;
         f.write_str(&format!("Dry-Run: {}\n", self.dryrun))?;
+        f.write_str(&format!("Threads: {}\n", self.threads))?;
+        f.write_str(&format!("Batch evaluation: {}\n", self.batch))?;
+        f.write_str(&format!("Pipelined joins: {}\n", self.pipelined))?;
+        f.write_str(&format!("Memory budget: {:?}\n", self.memory_budget))?;
+        f.write_str(&format!("Timeout: {:?}\n", self.timeout))?;
+        f.write_str(&format!("Explain: {}\n", self.explain))?;
+        f.write_str(&format!("Plan export: {:?}\n", self.export))?;
+        f.write_str(&format!("Adaptive threshold: {:?}\n", self.adaptive))?;
+        f.write_str(&format!("Samples: {}\n", self.samples))?;
+        f.write_str(&format!("Sample budget: {:?}\n", self.sample_budget))?;
+        f.write_str(&format!("Seed: {}\n", self.seed))?;
+        f.write_str(&format!(
+            "Result cache budget: {:?}\n",
+            self.cache.as_ref().map(|c| c.budget())
+        ))?;
         f.write_str(&format!("Logging: {}\n", self.log))
     }
 }
@@ -82,6 +237,22 @@ pub enum Optimizer {
 
     // Use Variable Counting to optimize queries
     Arqvcp,
+
+    // Use characteristic sets to estimate stars of triple patterns on the same subject
+    Arqcs,
+
+    // Sample the database while optimizing, estimating joins by random walks through the samples
+    Sampling,
+}
+
+impl Optimizer {
+    /// Name of the optimizer on the command line, which its estimator is registered under
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .expect("Optimizers are not skipped")
+            .get_name()
+            .to_owned()
+    }
 }
 
 impl Display for Optimizer {
@@ -96,6 +267,69 @@ impl Display for Optimizer {
             Optimizer::Arqpfjc => f.write_str("ARQ/PFJC"),
             Optimizer::Arqvc => f.write_str("ARQ/VC"),
             Optimizer::Arqvcp => f.write_str("ARQ/VCP"),
+            Optimizer::Arqcs => f.write_str("ARQ/CS"),
+            Optimizer::Sampling => f.write_str("Sampling"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum, Hash, PartialEq, Eq, Debug, Default)]
+pub enum JoinOrder {
+    // Repeatedly join the most selective pair of operations
+    #[default]
+    Greedy,
+
+    // Enumerate bushy join trees without cartesian products by dynamic programming
+    Dp,
+}
+
+impl Display for JoinOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinOrder::Greedy => f.write_str("Greedy"),
+            JoinOrder::Dp => f.write_str("DP"),
         }
     }
 }
+
+#[derive(Clone, Copy, ValueEnum, Hash, PartialEq, Eq, Debug)]
+pub enum Rewrite {
+    // Split filters on a conjunction into one filter per condition
+    SplitFilter,
+
+    // Move filters below joins, unions, optional and minus towards the operations binding their variables
+    PushFilter,
+
+    // Nest unions to the right, so that every branch is the left side of a union
+    FlattenUnion,
+
+    // Drop variables below joins, unions and filters as soon as no operation above needs them
+    PushProjection,
+
+    // Replace optional by a join if a filter above rejects the results without the optional part
+    OptionalToJoin,
+
+    // Evaluate conditions without variables
+    FoldConstants,
+}
+
+impl Display for Rewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rewrite::SplitFilter => f.write_str("Split filter"),
+            Rewrite::PushFilter => f.write_str("Push filter"),
+            Rewrite::FlattenUnion => f.write_str("Flatten union"),
+            Rewrite::PushProjection => f.write_str("Push projection"),
+            Rewrite::OptionalToJoin => f.write_str("Optional to join"),
+            Rewrite::FoldConstants => f.write_str("Fold constants"),
+        }
+    }
+}
+
+/// Formats a plan can be exported in
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum PlanFormat {
+    /// Graphviz graph
+    Dot,
+    Json,
+}
@@ -0,0 +1,180 @@
+//! The qerror module measures how accurately the selectivity estimators predict the number of
+//! results of a query, independently of how long the chosen plans take to run.
+//!
+//! Every connected part of each basic graph pattern of a query, up to a given number of triple
+//! patterns, is evaluated on the database and estimated by every estimator. The accuracy of an
+//! estimate is its q-error, the factor by which it differs from the actual number of results.
+//!
+//! Filters of the query are neither applied to the parts nor considered by their estimates, so
+//! that both count the results of the triple patterns alone.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use crate::syntax::{
+    database::Database,
+    query::{Query, QueryVisitor},
+};
+
+use super::{
+    estimator,
+    operations::{
+        context::Context,
+        join::Join,
+        scan::Scan,
+        visitors::{bgp::Bgps, condition::ConditionInfo, dp, planner::Planner, prepare::Prepare},
+        Operation, OperationVisitor,
+    },
+    options::EvalOptions,
+    selectivity::q_error,
+};
+
+/// Estimated and actual number of results of a part of a basic graph pattern
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    /// Index of the basic graph pattern in the query
+    pub bgp: usize,
+    /// Indices of the triple patterns in the basic graph pattern
+    pub patterns: Vec<usize>,
+    /// Name of the estimator, as it is printed in plans
+    pub estimator: String,
+    /// Number of results, `None` if evaluating the patterns timed out
+    pub actual: Option<usize>,
+    /// Estimated number of results, `None` if the estimator has no estimate for the patterns
+    pub estimate: Option<f64>,
+}
+
+impl Estimate {
+    pub fn q_error(&self) -> Option<f64> {
+        self.estimate
+            .zip(self.actual)
+            .map(|(estimate, actual)| q_error(estimate, actual as f64))
+    }
+}
+
+/**
+ * Estimate and evaluate all connected parts of the basic graph patterns of a query with at most
+ * `max_size` triple patterns, stopping the evaluation of each part after `timeout`. Random
+ * estimates depend on `seed` only.
+ */
+pub fn qerror(
+    query: Query,
+    database: &Database,
+    max_size: usize,
+    timeout: Option<Duration>,
+    seed: u64,
+) -> Result<Vec<Estimate>, Box<dyn Error>> {
+    let expanded = &query.expand()?;
+
+    let initial = Planner::new(database).visit(expanded);
+    // The parts are evaluated without the filters of the query, so they are estimated without
+    // them too
+    let info = ConditionInfo::new();
+    let triples = database.triples().len();
+
+    let mut estimates = Vec::new();
+
+    for (bgp, scans) in Bgps::new().visit(&initial).into_iter().enumerate() {
+        let parts = connected_parts(&scans, max_size);
+
+        log::info!(
+            "Estimating {} parts of basic graph pattern {bgp} with {} scans",
+            parts.len(),
+            scans.len()
+        );
+
+        for patterns in parts {
+            let part: Vec<Scan> = patterns.iter().map(|i| scans[*i].clone()).collect();
+
+            let plan = part
+                .iter()
+                .cloned()
+                .map(Operation::Scan)
+                .reduce(|left, right| Operation::Join(Join::new(left, right)))
+                .expect("Part without scans");
+
+            let now = Instant::now();
+            let ctx = Context::new().with_timeout(timeout);
+            let results = Prepare::new(ctx.clone()).visit(&plan).count();
+
+            let actual = if ctx.timed_out() {
+                log::warn!(
+                    "Patterns {patterns:?} timed out after {:.2?}",
+                    now.elapsed()
+                );
+
+                None
+            } else {
+                Some(results)
+            };
+
+            for name in estimator::names() {
+                let opts = EvalOptions::default()
+                    .with_estimator(Some(name))
+                    .with_seed(seed);
+                let estimator = estimator(database, &opts, &info)?;
+
+                // Estimators only cover some joins directly, the others are estimated from their
+                // scans and pairs of scans like the join enumeration does
+                let estimate = match estimator.cardinality(&plan) {
+                    Ok(c) if c.is_finite() => Some(c),
+                    _ => dp::estimate(part.clone(), &estimator, triples)
+                        .ok()
+                        .filter(|c| c.is_finite()),
+                };
+
+                estimates.push(Estimate {
+                    bgp,
+                    patterns: patterns.clone(),
+                    estimator: estimator.to_string(),
+                    actual,
+                    estimate,
+                });
+            }
+        }
+    }
+
+    Ok(estimates)
+}
+
+/// Indices of all sets of at most `max_size` scans that are connected by shared variables
+fn connected_parts(scans: &[Scan], max_size: usize) -> Vec<Vec<usize>> {
+    let vars: Vec<_> = scans
+        .iter()
+        .map(|s| Operation::Scan(s.clone()).bound_vars())
+        .collect();
+
+    let connected = |part: &Vec<usize>, i: usize| {
+        !part.contains(&i) && part.iter().any(|j| !vars[*j].is_disjoint(&vars[i]))
+    };
+
+    let mut parts: Vec<Vec<usize>> = (0..scans.len()).map(|i| vec![i]).collect();
+    let mut current = parts.clone();
+
+    for _ in 1..max_size {
+        let mut seen = HashSet::new();
+
+        current = current
+            .iter()
+            .flat_map(|part| {
+                (0..scans.len())
+                    .filter(|i| connected(part, *i))
+                    .map(|i| {
+                        let mut next = part.clone();
+                        next.push(i);
+                        next.sort();
+                        next
+                    })
+                    .collect::<Vec<Vec<usize>>>()
+            })
+            .filter(|part| seen.insert(part.clone()))
+            .collect();
+
+        parts.extend(current.iter().cloned());
+    }
+
+    parts
+}
@@ -1,6 +1,69 @@
-use std::{fmt::Display, time::Duration};
+use std::{error::Error, fmt::Display, io, time::Duration};
 
-use super::{mapping::MappingSet, options::Optimizer};
+use crate::syntax::database::Database;
+
+use super::{
+    batch::Rows,
+    mapping::{Mapping, MappingSet},
+    operations::{context::Context, Operation},
+    options::Optimizer,
+};
+
+/// Solutions of a query, evaluated lazily while they are consumed
+pub struct Solutions<'a> {
+    rows: Box<dyn Iterator<Item = Mapping> + 'a>,
+    ctx: Context,
+}
+
+impl<'a> Solutions<'a> {
+    pub(crate) fn new(db: &'a Database, plan: Operation<'a>, ctx: Context, batch: bool) -> Self {
+        Self {
+            rows: if batch {
+                Box::new(Rows::new(plan, &db.encoded().dictionary))
+            } else {
+                Box::new(plan)
+            },
+            ctx,
+        }
+    }
+
+    /// Fails with the error that stopped the evaluation, such as a join that could not spill
+    pub fn check_failure(&self) -> io::Result<()> {
+        match self.ctx.take_failure() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if the solutions produced so far are incomplete because the query timed out
+    pub fn check_timeout(&self) -> Result<(), TimeoutError> {
+        if self.ctx.timed_out() {
+            Err(TimeoutError)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'a> Iterator for Solutions<'a> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+/// The evaluation of a query was stopped because it exceeded its timeout
+#[derive(Debug)]
+pub struct TimeoutError;
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Query evaluation timed out")
+    }
+}
+
+impl Error for TimeoutError {}
 
 #[derive(Debug, Default)]
 pub struct QueryResult {
@@ -9,6 +72,9 @@ pub struct QueryResult {
     opt_duration: Option<Duration>,
     optimizers: Vec<Optimizer>,
     operations: Option<OperationMeta>,
+    explain: Option<String>,
+    plan: Option<String>,
+    reoptimizations: usize,
 }
 
 #[derive(Debug, Default)]
@@ -25,6 +91,7 @@ impl QueryResult {
             ResultType::SelectResult(_, s) => s.to_owned(),
             ResultType::AskResult(_) => 1,
             ResultType::DryRun => 0,
+            ResultType::TimedOut => 0,
         }
     }
 
@@ -37,6 +104,9 @@ impl QueryResult {
             opt_duration: None,
             optimizers: vec![],
             operations: None,
+            explain: None,
+            plan: None,
+            reoptimizations: 0,
         }
     }
 
@@ -54,6 +124,13 @@ impl QueryResult {
         }
     }
 
+    pub(crate) fn timed_out() -> Self {
+        Self {
+            kind: ResultType::TimedOut,
+            ..Default::default()
+        }
+    }
+
     pub(crate) fn with_run_duration(self, elapsed: Duration) -> QueryResult {
         Self {
             run_duration: Some(elapsed),
@@ -79,12 +156,28 @@ impl QueryResult {
         }
     }
 
+    pub(crate) fn with_explain(self, explain: Option<String>) -> QueryResult {
+        Self { explain, ..self }
+    }
+
+    pub(crate) fn with_plan(self, plan: Option<String>) -> QueryResult {
+        Self { plan, ..self }
+    }
+
+    pub(crate) fn with_reoptimizations(self, reoptimizations: usize) -> QueryResult {
+        Self {
+            reoptimizations,
+            ..self
+        }
+    }
+
     pub(crate) fn discard_mappings(self) -> Self {
         Self {
             kind: match self.kind {
                 ResultType::SelectResult(_, s) => ResultType::SelectResult(Vec::new(), s),
                 ResultType::AskResult(r) => ResultType::AskResult(r),
                 ResultType::DryRun => ResultType::DryRun,
+                ResultType::TimedOut => ResultType::TimedOut,
             },
             ..self
         }
@@ -106,13 +199,33 @@ impl QueryResult {
         &self.operations
     }
 
+    /// The evaluated plan with estimated and actual results per operation, if explain was enabled
+    pub fn explain(&self) -> Option<&str> {
+        self.explain.as_deref()
+    }
+
+    /// The evaluated plan as Graphviz DOT or JSON, if an export format was given
+    pub fn plan(&self) -> Option<&str> {
+        self.plan.as_deref()
+    }
+
+    /// Number of times adaptive optimization planned the remaining joins again during evaluation
+    pub fn reoptimizations(&self) -> usize {
+        self.reoptimizations
+    }
+
     pub fn is_dryrun(&self) -> bool {
         match self.kind {
             ResultType::SelectResult(_, _) => false,
             ResultType::AskResult(_) => false,
             ResultType::DryRun => true,
+            ResultType::TimedOut => false,
         }
     }
+
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self.kind, ResultType::TimedOut)
+    }
 }
 
 impl PartialEq for QueryResult {
@@ -127,6 +240,7 @@ enum ResultType {
     AskResult(bool),
     #[default]
     DryRun,
+    TimedOut,
 }
 
 impl Display for QueryResult {
@@ -143,6 +257,7 @@ impl Display for QueryResult {
             }
             ResultType::AskResult(b) => f.write_str(&format!("{b}"))?,
             ResultType::DryRun => f.write_str("No results (Dry-Run)")?,
+            ResultType::TimedOut => f.write_str("No results (timed out)")?,
         };
 
         Ok(())
@@ -0,0 +1,206 @@
+use std::{
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use fnv::FnvHasher;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::syntax::database::Database;
+
+use super::{
+    mapping::Mapping,
+    operations::{scan::Scan, Operation},
+    selectivity::{SelectivityError, SelectivityResult},
+};
+
+/// Seed of the random decisions about `value`, which depends on `seed` and `value` only and not
+/// on the order in which the decisions are made
+pub(crate) fn seed_for<T: Hash>(seed: u64, value: &T) -> u64 {
+    let mut hasher = FnvHasher::with_key(seed);
+    value.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/**
+ * Estimates numbers of results by sampling the database while optimizing.
+ *
+ * A scan is estimated from a sample of the triples with its most selective term. A join of scans
+ * is estimated by random walks: each walk picks a random candidate triple of each scan in turn,
+ * looking the candidates up in the term index with the variables bound by the walk so far, and
+ * ends without results once a triple does not match. The product of the numbers of candidates
+ * along a walk that matches every scan, and zero for the others, is an unbiased estimate of the
+ * number of results, and the estimate is the average over all walks.
+ */
+#[derive(Clone)]
+pub(crate) struct Sampler<'a> {
+    db: &'a Database,
+    /// Number of sampled triples per scan and of random walks per join
+    size: usize,
+    /// Time each estimate may take, at least one walk is made regardless
+    budget: Option<Duration>,
+    seed: u64,
+}
+
+impl<'a> Sampler<'a> {
+    pub(crate) fn new(db: &'a Database, size: usize, budget: Option<Duration>, seed: u64) -> Self {
+        Self {
+            db,
+            size: size.max(1),
+            budget,
+            seed,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.budget.map(|budget| Instant::now() + budget)
+    }
+
+    /**
+     * A uniformly chosen candidate triple of `scan` once the variables of `bound` are replaced by
+     * their values, as the extension of `bound` if it matches, with the number of candidates.
+     */
+    fn step(&self, scan: &Scan, bound: &Mapping, rng: &mut StdRng) -> (usize, Option<Mapping>) {
+        let triples = self.db.triples();
+        let positions = scan.lookup(self.db.terms(), bound);
+        let candidates = positions.map_or(triples.len(), |p| p.len());
+
+        if candidates == 0 {
+            return (0, None);
+        }
+
+        let i = rng.gen_range(0..candidates);
+
+        (
+            candidates,
+            scan.extend(&triples[positions.map_or(i, |p| p[i])], bound),
+        )
+    }
+
+    /// Number of triples in the database, as the base of the selectivities
+    fn triples(&self) -> f64 {
+        self.db.triples().len().max(1) as f64
+    }
+
+    /**
+     * Selectivity of a scan.
+     *
+     * Scans with at most as many candidate triples as the sample size are counted exactly, larger
+     * ones are extrapolated from the matches among uniformly sampled candidates.
+     */
+    pub(crate) fn scan(&self, scan: &Scan) -> SelectivityResult {
+        let triples = self.db.triples();
+        let bound = Mapping::new();
+        let positions = scan.lookup(self.db.terms(), &bound);
+        let candidates = positions.map_or(triples.len(), |p| p.len());
+
+        if candidates <= self.size {
+            let matches = (0..candidates)
+                .filter(|i| {
+                    let triple = &triples[positions.map_or(*i, |p| p[*i])];
+                    scan.extend(triple, &bound).is_some()
+                })
+                .count();
+
+            return Ok(matches as f64 / self.triples());
+        }
+
+        let deadline = self.deadline();
+        let mut rng = StdRng::seed_from_u64(seed_for(self.seed, scan));
+        let mut sampled = 0;
+        let mut hits = 0;
+
+        while sampled < self.size && (sampled == 0 || deadline.is_none_or(|d| Instant::now() < d)) {
+            let i = rng.gen_range(0..candidates);
+            let triple = &triples[positions.map_or(i, |p| p[i])];
+
+            if scan.extend(triple, &bound).is_some() {
+                hits += 1;
+            }
+
+            sampled += 1;
+        }
+
+        let cardinality = candidates as f64 * hits as f64 / sampled as f64;
+
+        Ok(cardinality / self.triples())
+    }
+
+    /// Scans in the order of the walks: the one with the fewest candidates first, then each next
+    /// one shares a variable with those before it whenever possible
+    fn order<'s, 'b>(&self, scans: &[&'s Scan<'b>]) -> Vec<&'s Scan<'b>> {
+        let bound = Mapping::new();
+        let candidates = |s: &Scan| {
+            s.lookup(self.db.terms(), &bound)
+                .map_or(self.db.triples().len(), |p| p.len())
+        };
+
+        let mut remaining = scans.to_vec();
+        let first = (0..remaining.len())
+            .min_by_key(|i| candidates(remaining[*i]))
+            .expect("Joins have at least one scan");
+
+        let mut ordered = vec![remaining.remove(first)];
+        let mut vars = Operation::Scan(ordered[0].clone()).bound_vars();
+
+        while !remaining.is_empty() {
+            let next = (0..remaining.len())
+                .find(|i| !vars.is_disjoint(&Operation::Scan(remaining[*i].clone()).bound_vars()))
+                .unwrap_or(0);
+            let scan = remaining.remove(next);
+
+            vars.extend(Operation::Scan(scan.clone()).bound_vars());
+            ordered.push(scan);
+        }
+
+        ordered
+    }
+
+    /// Selectivity of a join of scans, estimated by random walks through the scans
+    pub(crate) fn join(&self, scans: &[&Scan]) -> SelectivityResult {
+        if scans.is_empty() {
+            return Err(SelectivityError::NonConjunctiveStructure);
+        }
+
+        let ordered = self.order(scans);
+        let base = self.triples().powi(scans.len() as i32);
+
+        let deadline = self.deadline();
+        let passed = || deadline.is_some_and(|d| Instant::now() >= d);
+        let mut rng = StdRng::seed_from_u64(seed_for(self.seed, &ordered));
+        let mut walks = 0;
+        let mut total = 0.0;
+
+        'walks: while walks < self.size && (walks == 0 || !passed()) {
+            let mut weight = 1.0;
+            let mut current = Some(Mapping::new());
+
+            for scan in ordered.iter() {
+                let Some(bound) = current else {
+                    break;
+                };
+
+                // A walk that runs out of time is dropped, unless it is the only one
+                if walks > 0 && passed() {
+                    break 'walks;
+                }
+
+                let (candidates, next) = self.step(scan, &bound, &mut rng);
+
+                weight *= candidates as f64;
+                current = next;
+            }
+
+            if current.is_some() {
+                total += weight;
+            }
+
+            walks += 1;
+        }
+
+        log::debug!("Estimated {} scans by {walks} random walks", scans.len());
+
+        Ok(total / walks as f64 / base)
+    }
+}
@@ -1,75 +1,106 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use rand::distributions::Uniform;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::syntax::{
     database::{self, Summary},
     query::{Object, Predicate, Subject},
 };
 
-use super::operations::visitors::condition::ConditionInfo;
+use super::{
+    estimator::{EstimationContext, Estimator, SubPlan},
+    operations::{visitors::condition::ConditionInfo, Operation},
+    options::Optimizer,
+    sampling::Sampler,
+};
 
+/**
+ * The estimator the optimizer orders operations by, `None` if it keeps the order of the query.
+ */
 #[derive(Clone)]
-pub enum SelectivityEstimator<'a> {
-    // Do not change the given query
-    Off,
+pub(crate) struct SelectivityEstimator<'a> {
+    name: String,
+    estimator: Option<Arc<dyn Estimator>>,
+    ctx: EstimationContext<'a>,
+}
 
-    // Triples are evaluated in random order
-    Random,
+impl<'a> Display for SelectivityEstimator<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
 
-    // Triples are evaluated in random order
-    Fixed,
+impl<'a> SelectivityEstimator<'a> {
+    pub(crate) fn new(
+        name: String,
+        estimator: Arc<dyn Estimator>,
+        ctx: EstimationContext<'a>,
+    ) -> Self {
+        Self {
+            name,
+            estimator: Some(estimator),
+            ctx,
+        }
+    }
 
-    // Use the probabilistic framework selectivity estimation to order triples
-    Arqpf(&'a Summary),
+    pub(crate) fn off(ctx: EstimationContext<'a>) -> Self {
+        Self {
+            name: Optimizer::Off.to_string(),
+            estimator: None,
+            ctx,
+        }
+    }
 
-    // Similar to ARQ/PF, but also use variable distribution statistics
-    Arqpfc(&'a Summary, &'a ConditionInfo),
+    pub(crate) fn is_off(&self) -> bool {
+        self.estimator.is_none()
+    }
 
-    // Use the probabilistic framework selectivity estimation to order triples
-    Arqpfj(&'a Summary),
+    /// Estimated number of results of an operation.
+    pub(crate) fn cardinality(&self, o: &Operation) -> SelectivityResult {
+        match &self.estimator {
+            Some(estimator) => estimator.cardinality(&SubPlan::new(o), &self.ctx),
+            None => panic!("No selectivity for OFF Optimizer"),
+        }
+    }
 
-    // Similar to ARQ/PFJ, but also use variable distribution statistics
-    Arqpfjc(&'a Summary, &'a ConditionInfo),
+    /// Estimated fraction of all combinations of triples the scans of an operation could match
+    /// that are results of the operation.
+    pub(crate) fn selectivity(&self, o: &Operation) -> SelectivityResult {
+        let scans = o.meta().scans as i32;
+        let triples = self.ctx.triples().max(1) as f64;
 
-    // Use Variable Counting to optimize queries
-    Arqvc,
+        Ok(self.cardinality(o)? / triples.powi(scans))
+    }
 
-    // Use Variable Counting to optimize queries
-    Arqvcp,
-}
+    /// Estimated selectivity and number of results of an operation in a printed plan, if there
+    /// are estimates for it
+    pub(crate) fn plan_estimates(&self, o: &Operation) -> (Option<f64>, Option<f64>) {
+        // Estimators only cover basic graph patterns
+        let (selectivity, rows) = match o {
+            _ if self.is_off() => (None, None),
+            Operation::Scan(_) | Operation::Join(_) | Operation::Filter(_) => {
+                (self.selectivity(o).ok(), self.cardinality(o).ok())
+            }
+            // The estimate the optimizer compared with the materialized results, cached results
+            // have none
+            Operation::Materialized(m) => (None, m.estimated),
+            _ => (None, None),
+        };
 
-impl<'a> Display for SelectivityEstimator<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SelectivityEstimator::Off => f.write_str("Off"),
-            SelectivityEstimator::Random => f.write_str("Random"),
-            SelectivityEstimator::Fixed => f.write_str("Fixed"),
-            SelectivityEstimator::Arqpf(_) => f.write_str("ARQ/PF"),
-            SelectivityEstimator::Arqpfc(_, _) => f.write_str("ARQ/PFC"),
-            SelectivityEstimator::Arqpfj(_) => f.write_str("ARQ/PFJ"),
-            SelectivityEstimator::Arqpfjc(_, _) => f.write_str("ARQ/PFJC"),
-            SelectivityEstimator::Arqvc => f.write_str("ARQ/VC"),
-            SelectivityEstimator::Arqvcp => f.write_str("ARQ/VCP"),
-        }
+        (
+            selectivity.filter(|s| s.is_finite()),
+            rows.filter(|r| r.is_finite()),
+        )
     }
 }
 
-impl<'a> SelectivityEstimator<'a> {
-    pub(crate) fn selectivity(&self, item: &(dyn Selectivity + 'a)) -> SelectivityResult {
-        match self {
-            SelectivityEstimator::Off => panic!("No selectivity for OFF Optimizer"),
-            SelectivityEstimator::Random => item.sel_random(),
-            SelectivityEstimator::Fixed => item.sel_fixed(),
-            SelectivityEstimator::Arqpf(summary) => item.sel_pf(summary),
-            SelectivityEstimator::Arqpfc(summary, infos) => item.sel_pfc(summary, infos),
-            SelectivityEstimator::Arqpfj(summary) => item.sel_pfj(summary),
-            SelectivityEstimator::Arqpfjc(summary, infos) => item.sel_pfjc(summary, infos),
-            SelectivityEstimator::Arqvc => item.sel_vc(),
-            SelectivityEstimator::Arqvcp => item.sel_vcp(),
-        }
-    }
+/// Factor by which an estimate differs from the actual value, in either direction
+pub(crate) fn q_error(estimate: f64, actual: f64) -> f64 {
+    let estimate = estimate.max(1.0);
+    let actual = actual.max(1.0);
+
+    (estimate / actual).max(actual / estimate)
 }
 
 #[derive(Debug)]
@@ -82,8 +113,8 @@ pub enum SelectivityError {
 pub type SelectivityResult = Result<f64, SelectivityError>;
 
 pub(crate) trait Selectivity {
-    fn sel_random(&self) -> SelectivityResult {
-        Ok(thread_rng().sample(Uniform::new(0.0, 1.0)))
+    fn sel_random(&self, seed: u64) -> SelectivityResult {
+        Ok(StdRng::seed_from_u64(seed).sample(Uniform::new(0.0, 1.0)))
     }
 
     fn sel_fixed(&self) -> SelectivityResult {
@@ -119,6 +150,16 @@ pub(crate) trait Selectivity {
         log::warn!("Hit default implementation for sel_pfjc");
         Err(SelectivityError::NonConjunctiveStructure)
     }
+
+    fn sel_cs(&self, _s: &database::Summary) -> SelectivityResult {
+        log::warn!("Hit default implementation for sel_cs");
+        Err(SelectivityError::NonConjunctiveStructure)
+    }
+
+    fn sel_sampling(&self, _s: &Sampler) -> SelectivityResult {
+        log::warn!("Hit default implementation for sel_sampling");
+        Err(SelectivityError::NonConjunctiveStructure)
+    }
 }
 
 impl Selectivity for Subject {
@@ -0,0 +1,150 @@
+//! Temporary files for operations whose state exceeds the memory budget.
+//!
+//! Mappings are written to a spill file one per line and read back lazily. A spill file is
+//! deleted from disk as soon as the last handle to it is dropped.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use super::mapping::Mapping;
+
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            log::warn!("Could not remove spill file {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// A finished spill file, can be read any number of times
+#[derive(Debug, Clone)]
+pub(crate) struct SpillFile {
+    path: Arc<TempPath>,
+    len: usize,
+}
+
+impl SpillFile {
+    /// Number of mappings in this file
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Reader over the mappings of this file, which only opens the file once it is read
+    pub(crate) fn reader(&self) -> SpillReader {
+        SpillReader {
+            file: self.clone(),
+            reader: None,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SpillWriter {
+    path: TempPath,
+    writer: BufWriter<File>,
+    len: usize,
+}
+
+impl SpillWriter {
+    pub(crate) fn new() -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "thesis-spill-{}-{}.ron",
+            std::process::id(),
+            SPILL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        log::debug!("Spilling to {}", path.display());
+
+        Ok(Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path: TempPath(path),
+            len: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, mapping: &Mapping) -> io::Result<()> {
+        let line = ron::to_string(mapping).map_err(io::Error::other)?;
+
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<SpillFile> {
+        self.writer.flush()?;
+
+        Ok(SpillFile {
+            path: Arc::new(self.path),
+            len: self.len,
+        })
+    }
+}
+
+/// Iterator over the mappings of a spill file
+#[derive(Debug)]
+pub(crate) struct SpillReader {
+    file: SpillFile,
+    reader: Option<BufReader<File>>,
+    offset: u64,
+}
+
+impl SpillReader {
+    fn read(&mut self) -> io::Result<Option<Mapping>> {
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => {
+                let mut file = File::open(&self.file.path.0)?;
+                file.seek(SeekFrom::Start(self.offset))?;
+
+                self.reader.insert(BufReader::new(file))
+            }
+        };
+
+        let mut line = String::new();
+        let read = reader.read_line(&mut line)?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        self.offset += read as u64;
+
+        ron::from_str(&line)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Clone for SpillReader {
+    /// The clone reopens the file at the same position once it is read
+    fn clone(&self) -> Self {
+        Self {
+            file: self.file.clone(),
+            reader: None,
+            offset: self.offset,
+        }
+    }
+}
+
+impl Iterator for SpillReader {
+    type Item = io::Result<Mapping>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
@@ -1,11 +1,16 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 
-use crate::syntax::{database::Database, query::Query};
+use crate::syntax::{
+    database::{Database, Object, Predicate, Subject, Triple},
+    query::{self, Expression, Query, SolutionModifier, Type, Variables},
+};
 
 use super::options::{EvalOptions, Optimizer};
 
-use super::evaluate;
+use super::mapping::MappingSet;
 use super::results::QueryResult;
+use super::{evaluate, stream};
+use crate::examples::databases::example1;
 
 fn iter_evaluate(
     db: &Database,
@@ -27,6 +32,80 @@ fn coll_evaluate(
     evaluate(db, query, Some(options))
 }
 
+/// Assert that both sets contain the same solutions, ignoring their order
+fn assert_same_solutions(expected: MappingSet, mut actual: MappingSet) {
+    for mapping in expected {
+        let position = actual.iter().position(|m| *m == mapping);
+        assert!(position.is_some(), "Missing solution {mapping}");
+        actual.remove(position.unwrap());
+    }
+
+    assert!(actual.is_empty(), "Unexpected solutions {actual:?}");
+}
+
+/// Pattern relating the variables `s` and `o` by the predicate `p`
+fn triple(s: &str, p: &str, o: &str) -> Box<Expression> {
+    Box::new(Expression::Triple(
+        Box::new(query::Subject::V(s.into())),
+        Box::new(query::Predicate::I(p.into())),
+        Box::new(query::Object::V(o.into())),
+    ))
+}
+
+/// SELECT query for `vars` matching `expression`
+fn select(vars: &[&str], expression: Expression) -> Query {
+    Query {
+        prologue: HashMap::new(),
+        kind: Type::SelectQuery(
+            Variables::new(vars.iter().map(|v| (*v).into()).collect()),
+            expression,
+            SolutionModifier::default(),
+        ),
+    }
+}
+
+/// Solutions of `query` on the first example database
+fn solutions(query: &Query, options: EvalOptions) -> MappingSet {
+    stream(&example1(), query, Some(options)).unwrap().collect()
+}
+
+/// One test per example query, each checking the query with `$check`
+macro_rules! example_queries {
+    ($check:ident) => {
+        example_queries!($check, query1 example1, query2 example2, query3 example3,
+            query4 example4, query5 example5, query6 example6, query7 example7,
+            query8 example8);
+    };
+    ($check:ident, $($test:ident $query:ident),*) => {
+        $(
+            #[test]
+            fn $test() {
+                $check(crate::examples::queries::$query());
+            }
+        )*
+    };
+}
+
+/// Forty people of the same type, so that all mappings of a join share one join key
+fn skewed() -> (Database, Query) {
+    let db: Database = (0..40)
+        .map(|i| {
+            Triple::new(
+                Subject::I(format!("<P{i}>").into()),
+                Predicate::I("<type>".into()),
+                Object::I("<Person>".into()),
+            )
+        })
+        .collect();
+
+    let query = select(
+        &["?p", "?q"],
+        Expression::And(triple("?p", "<type>", "?t"), triple("?q", "<type>", "?t")),
+    );
+
+    (db, query)
+}
+
 mod iterator {
     use super::iter_evaluate as evaluate;
     use crate::examples::databases::example1 as db;
@@ -456,3 +535,2337 @@ mod collection {
         );
     }
 }
+
+mod parallel {
+    use super::iter_evaluate as evaluate;
+    use crate::examples::databases::example1 as db;
+    use crate::semantics::options::EvalOptions;
+    use crate::syntax::query::Query;
+
+    fn assert_same_results(query: Query) {
+        let sequential = evaluate(&db(), query.clone(), None).unwrap();
+        let parallel =
+            evaluate(&db(), query, Some(EvalOptions::default().with_threads(4))).unwrap();
+
+        assert_eq!(parallel, sequential);
+    }
+
+    example_queries!(assert_same_results);
+}
+
+mod batch {
+    use super::iter_evaluate as evaluate;
+    use crate::examples::databases::example1 as db;
+    use crate::semantics::options::EvalOptions;
+    use crate::syntax::query::Query;
+
+    fn assert_same_results(query: Query) {
+        let rows = evaluate(&db(), query.clone(), None).unwrap();
+        let batches =
+            evaluate(&db(), query, Some(EvalOptions::default().with_batch(true))).unwrap();
+
+        assert_eq!(batches, rows);
+    }
+
+    example_queries!(assert_same_results);
+}
+
+mod pipelined {
+    use crate::examples::databases::example1 as db;
+    use crate::semantics::{
+        self,
+        mapping::MappingSet,
+        operations::Operation,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::query::Query;
+
+    use super::{assert_same_solutions as assert_same, skewed};
+
+    fn options() -> EvalOptions {
+        EvalOptions::default().with_optimizer(Optimizer::Fixed)
+    }
+
+    fn assert_same_solutions(query: Query) {
+        let db = db();
+        let hashed: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let pipelined: MappingSet = stream(&db, &query, Some(options().with_pipelined(true)))
+            .unwrap()
+            .collect();
+
+        // The pipelined join may return the same solutions in a different order
+        assert_same(hashed, pipelined);
+    }
+
+    example_queries!(assert_same_solutions);
+
+    /// Number of mappings each scan of a plan produced
+    fn scanned(o: &Operation) -> Vec<usize> {
+        match o {
+            Operation::Scan(_) => vec![o.stats().rows().unwrap()],
+            _ => o.inputs().into_iter().flat_map(scanned).collect(),
+        }
+    }
+
+    #[test]
+    fn stream_stops_early() {
+        let (db, query) = skewed();
+
+        for (pipelined, early) in [(false, false), (true, true)] {
+            let opts = options().with_pipelined(pipelined).with_explain(true);
+            let (mut plan, _, _, _) = semantics::plan(&db, &query, &opts).unwrap();
+
+            assert!(plan.next().is_some());
+
+            // Without pipelining, the hash table holds all forty people before the first solution
+            let scanned = scanned(&plan);
+            assert_eq!(scanned.len(), 2);
+            assert_eq!(scanned.iter().all(|rows| *rows < 20), early, "{scanned:?}");
+        }
+    }
+}
+
+mod spill {
+    use std::collections::HashMap;
+
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        mapping::MappingSet,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::{
+        database::{Database, Object, Predicate, Subject, Triple},
+        query::{self, Expression, OrderCondition, Query, SolutionModifier, Type, Variables},
+        Literal,
+    };
+
+    use super::{assert_same_solutions as assert_same, skewed};
+
+    fn options() -> EvalOptions {
+        EvalOptions::default().with_optimizer(Optimizer::Fixed)
+    }
+
+    fn assert_same_solutions(query: Query) {
+        let db = db();
+        let in_memory: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let spilled: MappingSet = stream(&db, &query, Some(options().with_memory_budget(Some(1))))
+            .unwrap()
+            .collect();
+
+        // Joins spilled to disk return the same solutions in a different order
+        assert_same(in_memory, spilled);
+    }
+
+    example_queries!(assert_same_solutions);
+
+    #[test]
+    fn skewed_partition() {
+        let (db, query) = skewed();
+
+        let in_memory: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let spilled: MappingSet =
+            stream(&db, &query, Some(options().with_memory_budget(Some(1024))))
+                .unwrap()
+                .collect();
+
+        assert_eq!(in_memory.len(), 1600);
+        assert_same(in_memory, spilled);
+    }
+
+    #[test]
+    fn parallel_and_pipelined() {
+        let (db, query) = skewed();
+
+        let in_memory: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let spilled: MappingSet = stream(
+            &db,
+            &query,
+            Some(
+                options()
+                    .with_threads(4)
+                    .with_pipelined(true)
+                    .with_memory_budget(Some(1024)),
+            ),
+        )
+        .unwrap()
+        .collect();
+
+        assert_same(in_memory, spilled);
+    }
+
+    #[test]
+    fn pipelined_spilled() {
+        let (db, query) = skewed();
+
+        let in_memory: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+
+        // The budget is exceeded after some of the results were produced from the hash tables
+        let spilled: MappingSet = stream(
+            &db,
+            &query,
+            Some(
+                options()
+                    .with_pipelined(true)
+                    .with_memory_budget(Some(2048)),
+            ),
+        )
+        .unwrap()
+        .collect();
+
+        assert_same(in_memory, spilled);
+    }
+
+    #[test]
+    fn sorted_runs() {
+        let db: Database = (0..40)
+            .map(|i| {
+                Triple::new(
+                    Subject::I(format!("<P{i}>").into()),
+                    Predicate::I("<age>".into()),
+                    Object::L(Literal::new(format!("\"{}\"", (i * 7) % 40))),
+                )
+            })
+            .collect();
+
+        let mut modifier = SolutionModifier::default();
+        modifier.with_order(OrderCondition::new("?a".into(), true));
+
+        let query = Query {
+            prologue: HashMap::new(),
+            kind: Type::SelectQuery(
+                Variables::new(vec!["?p".into(), "?a".into()]),
+                Expression::Triple(
+                    Box::new(query::Subject::V("?p".into())),
+                    Box::new(query::Predicate::I("<age>".into())),
+                    Box::new(query::Object::V("?a".into())),
+                ),
+                modifier,
+            ),
+        };
+
+        let in_memory: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let spilled: MappingSet =
+            stream(&db, &query, Some(options().with_memory_budget(Some(1024))))
+                .unwrap()
+                .collect();
+
+        let ages: Vec<f64> = in_memory
+            .iter()
+            .map(|m| match m.items.iter().find(|(v, _)| v.name == "?a") {
+                Some((_, Object::L(age))) => age.parsed.unwrap(),
+                other => panic!("Unexpected age {other:?}"),
+            })
+            .collect();
+
+        // Runs spilled to disk are merged into the same order
+        assert_eq!(ages, (0..40).rev().map(f64::from).collect::<Vec<_>>());
+        assert_eq!(in_memory, spilled);
+    }
+
+    #[test]
+    fn batch_rejected() {
+        let options = options().with_batch(true).with_memory_budget(Some(1024));
+
+        assert!(stream(&db(), &queries::example1(), Some(options)).is_err());
+    }
+}
+
+mod timeout {
+    use std::{thread, time::Duration};
+
+    use super::iter_evaluate as evaluate;
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        operations::context::Context,
+        options::{EvalOptions, Optimizer},
+        stream, QueryResult,
+    };
+
+    #[test]
+    fn expired() {
+        let options = EvalOptions::default().with_timeout(Some(Duration::ZERO));
+
+        assert_eq!(
+            evaluate(&db(), queries::example2(), Some(options)).unwrap(),
+            QueryResult::timed_out()
+        );
+    }
+
+    #[test]
+    fn not_expired() {
+        let options = EvalOptions::default().with_timeout(Some(Duration::from_secs(3600)));
+
+        assert_eq!(
+            evaluate(&db(), queries::example2(), Some(options)).unwrap(),
+            evaluate(&db(), queries::example2(), None).unwrap()
+        );
+    }
+
+    #[test]
+    fn stream_reports_timeout() {
+        let db = db();
+        let options = EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_timeout(Some(Duration::ZERO));
+        let mut solutions = stream(&db, &queries::example2(), Some(options)).unwrap();
+
+        assert!(solutions.next().is_none());
+        assert!(solutions.check_timeout().is_err());
+    }
+
+    #[test]
+    fn clock_starts_with_evaluation() {
+        let ctx = Context::new().with_timeout(Some(Duration::from_millis(50)));
+
+        // Planning the query before the first operation checks the deadline takes no time off
+        thread::sleep(Duration::from_millis(100));
+        assert!(!ctx.is_expired());
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(ctx.is_expired());
+    }
+}
+
+mod dp {
+    use crate::examples::databases::example1 as db;
+    use crate::semantics::{
+        self,
+        estimator::{self, EstimationContext, EstimationResult, Estimator, SubPlan},
+        mapping::MappingSet,
+        operations::{
+            join::Join,
+            visitors::{bgp::Bgps, condition::ConditionAnalyzer, dp::DpOptimize, planner::Planner},
+            Operation, OperationVisitor,
+        },
+        options::{EvalOptions, JoinOrder, Optimizer},
+        stream,
+    };
+    use crate::syntax::query::{Expression, Query, QueryVisitor};
+
+    use super::{assert_same_solutions as assert_same, select, triple};
+
+    /// Estimates scans and joins of scans of the chain query by the predicates of their patterns
+    struct Chain;
+
+    fn predicates(plan: &SubPlan) -> Vec<String> {
+        match plan.pattern() {
+            Some([_, predicate, _]) => vec![predicate],
+            None => plan.children().iter().flat_map(predicates).collect(),
+        }
+    }
+
+    impl Estimator for Chain {
+        fn cardinality(&self, plan: &SubPlan, _: &EstimationContext) -> EstimationResult {
+            let mut predicates = predicates(plan);
+            predicates.sort();
+
+            Ok(match predicates.concat().as_str() {
+                "<p>" | "<r>" => 100.0,
+                "<q>" => 10.0,
+                "<p><q>" => 10.0,
+                "<q><r>" => 1000.0,
+                _ => 1e6,
+            })
+        }
+    }
+
+    /// ?a <p> ?b . ?b <q> ?c . ?c <r> ?d
+    fn chain_query() -> Query {
+        select(
+            &["?a", "?d"],
+            Expression::And(
+                Box::new(Expression::And(
+                    triple("?a", "<p>", "?b"),
+                    triple("?b", "<q>", "?c"),
+                )),
+                triple("?c", "<r>", "?d"),
+            ),
+        )
+    }
+
+    fn join<'a>(left: Operation<'a>, right: Operation<'a>) -> Operation<'a> {
+        Operation::Join(Join::new(left, right))
+    }
+
+    fn options() -> EvalOptions {
+        EvalOptions::default().with_optimizer(Optimizer::Fixed)
+    }
+
+    fn assert_same_solutions(query: Query) {
+        let db = db();
+        let greedy: MappingSet = stream(&db, &query, Some(options())).unwrap().collect();
+        let dp: MappingSet = stream(&db, &query, Some(options().with_join_order(JoinOrder::Dp)))
+            .unwrap()
+            .collect();
+
+        // A different join order returns the same solutions in a different order
+        assert_same(greedy, dp);
+    }
+
+    example_queries!(assert_same_solutions);
+
+    #[test]
+    fn chain() {
+        estimator::register("chain", Chain);
+
+        let db = db();
+        let query = chain_query().expand().unwrap();
+        let info = ConditionAnalyzer::new().visit(&query);
+        let scans = Bgps::new()
+            .visit(&Planner::new(&db).visit(&query))
+            .remove(0);
+        let [a, b, c] = [0, 1, 2].map(|i| Operation::Scan(scans[i].clone()));
+
+        let options = options().with_estimator(Some("chain".to_owned()));
+        let estimator = semantics::estimator(&db, &options, &info).unwrap();
+        let ordered = DpOptimize::new(estimator, db.triples().len())
+            .visit(&join(join(a.clone(), b.clone()), c.clone()))
+            .unwrap();
+
+        // Scans cost the same in every plan. Joining the small result of a and b first costs
+        // 2·10 + 100 + 10 for the join, with b as the smaller input to build from, and 2·10 + 100
+        // + 1000 to join c. Joining b and c first already costs 2·10 + 100 + 1000 for their join
+        // and 2·100 + 1000 + 1000 to join a.
+        assert_eq!(ordered, join(join(b, a), c));
+    }
+}
+
+mod characteristic_sets {
+    use crate::examples::databases::example1;
+    use crate::semantics::{
+        mapping::MappingSet,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::{
+        database::{Database, Predicate},
+        query::Query,
+    };
+
+    use super::assert_same_solutions as assert_same;
+
+    fn db() -> Database {
+        let mut db = example1();
+        db.compute_statistics();
+        db
+    }
+
+    fn assert_same_solutions(query: Query) {
+        let db = db();
+        let fixed: MappingSet = stream(
+            &db,
+            &query,
+            Some(EvalOptions::default().with_optimizer(Optimizer::Fixed)),
+        )
+        .unwrap()
+        .collect();
+        let cs: MappingSet = stream(
+            &db,
+            &query,
+            Some(EvalOptions::default().with_optimizer(Optimizer::Arqcs)),
+        )
+        .unwrap()
+        .collect();
+
+        assert_same(fixed, cs);
+    }
+
+    #[test]
+    fn star_cardinality() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+        let email = Predicate::I("<email>".into());
+
+        assert_eq!(db.summary().c_s(&[age.clone(), email.clone()]), 1.0);
+        assert_eq!(db.summary().c_s(&[email.clone(), email]), 1.0);
+        assert_eq!(db.summary().c_s(&[age]), 3.0);
+        assert_eq!(db.summary().c_s(&[Predicate::I("<name>".into())]), 0.0);
+    }
+
+    example_queries!(assert_same_solutions);
+}
+
+mod join_statistics {
+    use crate::examples::databases::example1;
+    use crate::syntax::database::{Database, Object, Predicate, Subject, Triple};
+
+    fn db() -> Database {
+        let mut db = example1();
+        db.compute_statistics();
+        db
+    }
+
+    #[test]
+    fn subject_subject() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+        let email = Predicate::I("<email>".into());
+
+        assert_eq!(db.summary().s_p(age.clone(), age.clone()), 3.0);
+        assert_eq!(db.summary().s_p(age, email), 1.0);
+    }
+
+    #[test]
+    fn subject_object() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+
+        assert_eq!(db.summary().s_o(age.clone(), age), 0.0);
+    }
+
+    #[test]
+    fn object_object() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+        let email = Predicate::I("<email>".into());
+
+        assert_eq!(db.summary().o_o(age.clone(), age.clone()), 5.0);
+        assert_eq!(db.summary().o_o(age, email), 0.0);
+    }
+
+    #[test]
+    fn chain() {
+        let knows = Predicate::I("<knows>".into());
+        let mut db: Database = (1..4)
+            .map(|i| {
+                Triple::new(
+                    Subject::I(format!("<P{i}>").into()),
+                    knows.clone(),
+                    Object::I(format!("<P{}>", i + 1).into()),
+                )
+            })
+            .collect();
+        db.compute_statistics();
+
+        assert_eq!(db.summary().s_o(knows.clone(), knows.clone()), 2.0);
+        assert_eq!(db.summary().s_p(knows.clone(), knows.clone()), 3.0);
+        assert_eq!(db.summary().o_o(knows.clone(), knows), 3.0);
+    }
+}
+
+mod histograms {
+    use std::collections::HashMap;
+
+    use crate::examples::databases::example1;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        mapping::MappingSet,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::{
+        database::{Database, Object, Predicate, Subject, Triple},
+        query::{self, Condition, Expression, Query, SolutionModifier, Type, Variables},
+    };
+
+    use super::assert_same_solutions;
+
+    const DATE: &str = "^^<http://www.w3.org/2001/XMLSchema#date>";
+
+    /// People born on the first of each month of 2000 and named with two letters per month
+    fn db() -> Database {
+        let mut db: Database = (1..=12)
+            .flat_map(|month| {
+                let person = Subject::I(format!("<P{month}>").into());
+
+                [
+                    Triple::new(
+                        person.clone(),
+                        Predicate::I("<born>".into()),
+                        Object::L(format!("\"2000-{month:02}-01\"{DATE}").into()),
+                    ),
+                    Triple::new(
+                        person,
+                        Predicate::I("<name>".into()),
+                        Object::L(format!("\"{}a\"", (b'a' + month as u8) as char).into()),
+                    ),
+                ]
+            })
+            .collect();
+        db.compute_statistics();
+        db
+    }
+
+    #[test]
+    fn numbers() {
+        let mut db = example1();
+        db.compute_statistics();
+        let age = Predicate::I("<age>".into());
+
+        assert_eq!(db.summary().p_l(&age, None, None), 1.0);
+        assert_eq!(db.summary().p_l(&age, Some(0.0), Some(100.0)), 1.0);
+        assert_eq!(db.summary().p_l(&age, Some(40.0), None), 0.0);
+        assert!(db.summary().p_l(&age, None, Some(29.5)) < 1.0);
+        assert_eq!(db.summary().d_p(&age), 2.0);
+    }
+
+    #[test]
+    fn dates() {
+        let db = db();
+        let born = Predicate::I("<born>".into());
+        // 2000-01-01 and 2000-07-01 in days since 1970-01-01
+        let (january, july) = (10957.0, 11139.0);
+
+        assert_eq!(db.summary().p_d(&born, Some(january), None), 1.0);
+        assert_eq!(db.summary().p_d(&born, None, Some(january - 1.0)), 0.0);
+
+        let first_half = db.summary().p_d(&born, None, Some(july));
+        assert!(first_half > 0.25 && first_half < 0.75);
+
+        // Dates are not numbers
+        assert_eq!(db.summary().p_l(&born, Some(0.0), Some(1.0)), 1.0);
+        assert_eq!(db.summary().d_p(&born), 12.0);
+    }
+
+    #[test]
+    fn strings() {
+        let db = db();
+        let name = Predicate::I("<name>".into());
+
+        assert_eq!(db.summary().p_s(&name, None, None), 1.0);
+        assert_eq!(db.summary().p_s(&name, Some("a"), Some("z")), 1.0);
+        assert_eq!(db.summary().p_s(&name, Some("z"), None), 0.0);
+
+        let first_half = db.summary().p_s(&name, None, Some("ga"));
+        assert!(first_half > 0.25 && first_half < 0.75);
+    }
+
+    #[test]
+    fn filters() {
+        let db = db();
+
+        // Born in the first half of 2000, and named after the letter g
+        for (predicate, condition) in [
+            (
+                "<born>",
+                Condition::LT(
+                    query::Object::V("?o".into()),
+                    query::Object::L(format!("\"2000-07-01\"{DATE}").into()),
+                ),
+            ),
+            (
+                "<name>",
+                Condition::GT(
+                    query::Object::V("?o".into()),
+                    query::Object::L("\"ga\"".into()),
+                ),
+            ),
+        ] {
+            let query = Query {
+                prologue: HashMap::new(),
+                kind: Type::SelectQuery(
+                    Variables::new(vec!["?p".into()]),
+                    Expression::Filter(
+                        Box::new(Expression::Triple(
+                            Box::new(query::Subject::V("?p".into())),
+                            Box::new(query::Predicate::I(predicate.into())),
+                            Box::new(query::Object::V("?o".into())),
+                        )),
+                        Box::new(condition),
+                    ),
+                    SolutionModifier::default(),
+                ),
+            };
+
+            // Estimating the filters by histograms of dates and strings keeps their results
+            let off = EvalOptions::default().with_optimizer(Optimizer::Off);
+            let arqpfc = EvalOptions::default().with_optimizer(Optimizer::Arqpfc);
+
+            assert_eq!(
+                evaluate(&db, query.clone(), Some(arqpfc)).unwrap(),
+                evaluate(&db, query, Some(off)).unwrap(),
+                "{predicate}"
+            );
+        }
+    }
+
+    #[test]
+    fn same_results() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        for query in [queries::example1(), queries::example5()] {
+            let expected: MappingSet = stream(
+                &db,
+                &query,
+                Some(EvalOptions::default().with_optimizer(Optimizer::Off)),
+            )
+            .unwrap()
+            .collect();
+
+            for optimizer in [Optimizer::Arqpfc, Optimizer::Arqpfjc] {
+                assert_same_solutions(
+                    expected.clone(),
+                    stream(
+                        &db,
+                        &query,
+                        Some(EvalOptions::default().with_optimizer(optimizer)),
+                    )
+                    .unwrap()
+                    .collect(),
+                );
+            }
+        }
+    }
+}
+
+mod correlations {
+    use std::collections::HashMap;
+
+    use crate::semantics::{
+        mapping::MappingSet,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::{
+        database::{Database, Object, Predicate, Subject, Triple},
+        histogram::{Key, Restriction},
+        query::{self, Condition, Expression, Query, SolutionModifier, Type, Variables},
+        Iri,
+    };
+
+    use super::assert_same_solutions;
+
+    /// Eight people in their twenties in department A, eight in their fifties in department B
+    fn db() -> Database {
+        let mut db: Database = (0..16)
+            .flat_map(|i| {
+                let person = Subject::I(format!("<P{i}>").into());
+                let (department, age) = if i < 8 {
+                    ("<A>", 20 + i)
+                } else {
+                    ("<B>", 42 + i)
+                };
+
+                [
+                    Triple::new(
+                        person.clone(),
+                        Predicate::I("<age>".into()),
+                        Object::L(age.to_string().into()),
+                    ),
+                    Triple::new(
+                        person,
+                        Predicate::I("<department>".into()),
+                        Object::I(department.into()),
+                    ),
+                ]
+            })
+            .collect();
+        db.compute_statistics();
+        db
+    }
+
+    fn department(d: &str) -> Restriction {
+        Restriction::Equals(Key::from(&Iri::from(d)))
+    }
+
+    #[test]
+    fn joint_fraction() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+        let dept = Predicate::I("<department>".into());
+        let young = Restriction::Range(None, Some(30.0));
+
+        let a = db.summary().p_j(&age, &dept, &young, &department("<A>"));
+        let b = db.summary().p_j(&age, &dept, &young, &department("<B>"));
+
+        assert_eq!(a, Some(0.5));
+        // Independent filters would leave a quarter of the pairs
+        assert_eq!(b, Some(0.0));
+
+        // Either order of the predicates
+        assert_eq!(db.summary().p_j(&dept, &age, &department("<B>"), &young), b);
+        assert_eq!(
+            db.summary()
+                .p_j(&age, &Predicate::I("<name>".into()), &young, &young),
+            None
+        );
+    }
+
+    #[test]
+    fn same_results() {
+        let db = db();
+        let query = Query {
+            prologue: HashMap::new(),
+            kind: Type::SelectQuery(
+                Variables::new(vec!["?p".into()]),
+                Expression::Filter(
+                    Box::new(Expression::And(
+                        Box::new(Expression::Triple(
+                            Box::new(query::Subject::V("?p".into())),
+                            Box::new(query::Predicate::I("<age>".into())),
+                            Box::new(query::Object::V("?a".into())),
+                        )),
+                        Box::new(Expression::Triple(
+                            Box::new(query::Subject::V("?p".into())),
+                            Box::new(query::Predicate::I("<department>".into())),
+                            Box::new(query::Object::V("?d".into())),
+                        )),
+                    )),
+                    Box::new(Condition::And(
+                        Box::new(Condition::LT(
+                            query::Object::V("?a".into()),
+                            query::Object::L("55".into()),
+                        )),
+                        Box::new(Condition::Equals(
+                            query::Object::V("?d".into()),
+                            query::Object::I("<B>".into()),
+                        )),
+                    )),
+                ),
+                SolutionModifier::default(),
+            ),
+        };
+
+        let expected: MappingSet = stream(
+            &db,
+            &query,
+            Some(EvalOptions::default().with_optimizer(Optimizer::Off)),
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(expected.len(), 5);
+
+        for optimizer in [Optimizer::Arqpfc, Optimizer::Arqpfjc] {
+            assert_same_solutions(
+                expected.clone(),
+                stream(
+                    &db,
+                    &query,
+                    Some(EvalOptions::default().with_optimizer(optimizer)),
+                )
+                .unwrap()
+                .collect(),
+            );
+        }
+    }
+}
+
+mod summary_cache {
+    use std::{fs, path::PathBuf};
+
+    use crate::examples::databases::example1;
+    use crate::syntax::database::Database;
+
+    /// A data file named `name` in the temporary directory, and the summary file next to it
+    fn files(name: &str) -> (PathBuf, PathBuf) {
+        let data = std::env::temp_dir().join(format!("thesis-{}-{name}.nt", std::process::id()));
+
+        (data.clone(), data.with_extension("summary"))
+    }
+
+    #[test]
+    fn reads_fresh_summary() {
+        let (data, summary) = files("fresh");
+        fs::write(&data, "<P1> <age> 30 .").unwrap();
+
+        example1().build_statistics(&data).unwrap();
+        assert!(summary.exists());
+
+        // An empty database only has the summary of example 1 if it was read from the file
+        let mut db = Database::new();
+        db.build_statistics(&data).unwrap();
+        assert_eq!(db.summary().t(), 4.0);
+
+        fs::remove_file(data).unwrap();
+        fs::remove_file(summary).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_stale_summary() {
+        let (data, summary) = files("stale");
+        fs::write(&data, "<P1> <age> 30 .").unwrap();
+
+        example1().build_statistics(&data).unwrap();
+        fs::write(&data, "<P1> <age> 31 .").unwrap();
+
+        let mut db = Database::new();
+        db.build_statistics(&data).unwrap();
+        assert_eq!(db.summary().t(), 0.0);
+
+        fs::remove_file(data).unwrap();
+        fs::remove_file(summary).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_summary_with_other_seed() {
+        let (data, summary) = files("seed");
+        fs::write(&data, "<P1> <age> 30 .").unwrap();
+
+        example1().with_seed(1).build_statistics(&data).unwrap();
+
+        let mut db = Database::new().with_seed(1);
+        db.build_statistics(&data).unwrap();
+        assert_eq!(db.summary().t(), 4.0);
+
+        let mut db = Database::new().with_seed(2);
+        db.build_statistics(&data).unwrap();
+        assert_eq!(db.summary().t(), 0.0);
+        assert_eq!(db.summary().seed(), 2);
+
+        fs::remove_file(data).unwrap();
+        fs::remove_file(summary).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_summary_of_older_version() {
+        let (data, summary) = files("version");
+        fs::write(&data, "<P1> <age> 30 .").unwrap();
+
+        example1().build_statistics(&data).unwrap();
+
+        // The file starts with its version, earlier summaries lack statistics that were added since
+        let mut contents = fs::read(&summary).unwrap();
+        contents[..4].copy_from_slice(&2u32.to_le_bytes());
+        fs::write(&summary, contents).unwrap();
+
+        let mut db = Database::new();
+        db.build_statistics(&data).unwrap();
+        assert_eq!(db.summary().t(), 0.0);
+
+        fs::remove_file(data).unwrap();
+        fs::remove_file(summary).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_unreadable_summary() {
+        let (data, summary) = files("unreadable");
+        fs::write(&data, "<P1> <age> 30 .").unwrap();
+
+        for contents in [&b"garbage"[..], &[0xff; 12][..]] {
+            fs::write(&summary, contents).unwrap();
+
+            let mut db = example1();
+            db.build_statistics(&data).unwrap();
+            assert_eq!(db.summary().t(), 4.0);
+
+            let mut db = Database::new();
+            db.build_statistics(&data).unwrap();
+            assert_eq!(db.summary().t(), 4.0);
+        }
+
+        fs::remove_file(data).unwrap();
+        fs::remove_file(summary).unwrap();
+    }
+}
+
+mod incremental {
+    use crate::examples::databases::example1;
+    use crate::semantics::selectivity::q_error;
+    use crate::syntax::{
+        database::{Database, Object, Predicate, Subject, Triple},
+        terms::Position,
+        values::ValueRange,
+    };
+
+    fn triple(subject: &str, predicate: &str, object: Object) -> Triple {
+        Triple::new(
+            Subject::I(subject.into()),
+            Predicate::I(predicate.into()),
+            object,
+        )
+    }
+
+    fn db() -> Database {
+        let mut db = example1();
+        db.compute_statistics();
+        db
+    }
+
+    /// Compare the summary with the one computed from scratch for the same triples
+    fn assert_same_summary(db: &Database) {
+        let mut rebuilt: Database = db.triples().iter().cloned().collect();
+        rebuilt.compute_statistics();
+
+        let (a, b) = (db.summary(), rebuilt.summary());
+        let predicates: Vec<Predicate> = ["<age>", "<email>", "<knows>"]
+            .into_iter()
+            .map(|p| Predicate::I(p.into()))
+            .collect();
+
+        assert_eq!(a.t(), b.t());
+        assert_eq!(a.r(), b.r());
+
+        for p1 in predicates.iter() {
+            assert_eq!(a.t_p(p1), b.t_p(p1));
+            assert_eq!(a.d_p(p1), b.d_p(p1));
+            assert_eq!(
+                a.c_s(std::slice::from_ref(p1)),
+                b.c_s(std::slice::from_ref(p1))
+            );
+
+            for p2 in predicates.iter() {
+                assert_eq!(a.s_p(p1.clone(), p2.clone()), b.s_p(p1.clone(), p2.clone()));
+                assert_eq!(a.s_o(p1.clone(), p2.clone()), b.s_o(p1.clone(), p2.clone()));
+                assert_eq!(a.o_o(p1.clone(), p2.clone()), b.o_o(p1.clone(), p2.clone()));
+                assert_eq!(
+                    a.c_s(&[p1.clone(), p2.clone()]),
+                    b.c_s(&[p1.clone(), p2.clone()])
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn add() {
+        let mut db = db();
+
+        db.add(triple("<P4>", "<age>", Object::L("31".into())));
+        db.add(triple("<P4>", "<email>", Object::L("ann@tld.com".into())));
+        db.add(triple("<P1>", "<email>", Object::L("bob@tld.com".into())));
+        db.add(triple("<P1>", "<knows>", Object::I("<P2>".into())));
+        db.add(triple("<P2>", "<knows>", Object::I("<P2>".into())));
+        db.add(triple("<P2>", "<knows>", Object::I("<P3>".into())));
+
+        assert_same_summary(&db);
+    }
+
+    #[test]
+    fn remove() {
+        let mut db = db();
+        db.add(triple("<P1>", "<knows>", Object::I("<P3>".into())));
+
+        assert!(db.remove(&triple("<P3>", "<email>", Object::L("joe@tld.com".into()))));
+        assert!(db.remove(&triple("<P1>", "<age>", Object::L("30".into()))));
+        assert!(!db.remove(&triple("<P1>", "<age>", Object::L("30".into()))));
+
+        assert_same_summary(&db);
+        assert_eq!(db.triples().len(), 3);
+    }
+
+    #[test]
+    fn histograms() {
+        let mut db = db();
+        let age = Predicate::I("<age>".into());
+        let old = triple("<P4>", "<age>", Object::L("80".into()));
+
+        assert_eq!(db.summary().p_l(&age, Some(50.0), None), 0.0);
+
+        // The last bucket is widened to the new value, so the estimate is approximate
+        db.add(old.clone());
+        let estimate = db.summary().p_l(&age, Some(50.0), None);
+        assert!(estimate > 0.0 && estimate < 0.5);
+
+        // The bucket keeps its bounds, but no longer counts the value
+        db.remove(&old);
+        assert!(db.summary().p_l(&age, Some(50.0), None) < estimate);
+    }
+
+    #[test]
+    fn without_statistics() {
+        let mut db = example1();
+        db.add(triple("<P4>", "<age>", Object::L("31".into())));
+
+        assert_eq!(db.summary().t(), 0.0);
+    }
+
+    #[test]
+    fn indexes() {
+        let mut db = db();
+        db.encoded();
+        db.values();
+        db.terms();
+
+        db.add(triple("<P4>", "<age>", Object::L("31".into())));
+        db.add(triple("<P4>", "<email>", Object::L("ann@tld.com".into())));
+        assert!(db.remove(&triple("<P1>", "<age>", Object::L("30".into()))));
+        db.add(triple("<P1>", "<age>", Object::L("29".into())));
+
+        // The indexes were updated rather than built again for the changed triples
+        let rebuilt: Database = db.triples().iter().cloned().collect();
+
+        for t in db.triples() {
+            for position in [Position::Subject, Position::Predicate, Position::Object] {
+                let term = position.term(t);
+
+                assert_eq!(
+                    db.terms().positions(position, &term),
+                    rebuilt.terms().positions(position, &term)
+                );
+            }
+        }
+
+        let age = Predicate::I("<age>".into());
+        for predicate in [None, Some(&age)] {
+            assert_eq!(
+                db.values().positions(predicate, &ValueRange::new()),
+                rebuilt.values().positions(predicate, &ValueRange::new())
+            );
+        }
+
+        let decoded = |db: &Database| {
+            let encoded = db.encoded();
+
+            encoded
+                .triples
+                .iter()
+                .map(|ids| ids.map(|id| encoded.dictionary.term(id).cloned()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decoded(&db), decoded(&rebuilt));
+    }
+
+    #[test]
+    fn larger_than_sample() {
+        // Twice as many triples as the join statistics are counted on
+        let mut db: Database = (0..20_000)
+            .map(|i| {
+                triple(
+                    &format!("<S{i}>"),
+                    "<a>",
+                    Object::I(format!("<O{i}>").into()),
+                )
+            })
+            .collect();
+        db.compute_statistics();
+
+        let added: Vec<Triple> = (0..100)
+            .map(|i| triple("<S0>", "<b>", Object::I(format!("<X{i}>").into())))
+            .collect();
+        for t in added.iter() {
+            db.add(t.clone());
+        }
+
+        let mut rebuilt: Database = db.triples().iter().cloned().collect();
+        rebuilt.compute_statistics();
+
+        // The 100 new triples make 10000 pairs, which both count as about half as many like the
+        // sampled build does
+        let b = Predicate::I("<b>".into());
+        let incremental = db.summary().s_p(b.clone(), b.clone());
+        let sampled = rebuilt.summary().s_p(b.clone(), b.clone());
+        assert!(
+            q_error(incremental, sampled) < 1.5,
+            "{incremental} {sampled}"
+        );
+
+        for t in added.iter() {
+            assert!(db.remove(t));
+        }
+        assert!(db.summary().s_p(b.clone(), b).abs() < 1e-6);
+    }
+}
+
+mod seeds {
+    use crate::examples::databases::example1;
+    use crate::examples::queries;
+    use crate::semantics::{options::Optimizer, qerror::qerror};
+
+    fn random_estimates(seed: u64) -> Vec<Option<f64>> {
+        let db = example1();
+
+        qerror(queries::example1(), &db, 2, None, seed)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.estimator == Optimizer::Random.to_string())
+            .map(|e| e.estimate)
+            .collect()
+    }
+
+    #[test]
+    fn estimates() {
+        assert_eq!(random_estimates(7), random_estimates(7));
+        assert_ne!(random_estimates(7), random_estimates(8));
+    }
+
+    #[test]
+    fn statistics() {
+        let sample = |seed| example1().with_seed(seed).sample(2).to_string();
+
+        assert_eq!(sample(7), sample(7));
+
+        let mut db = example1().with_seed(7);
+        db.compute_statistics();
+        assert_eq!(db.summary().seed(), 7);
+    }
+}
+
+mod report {
+    use crate::examples::databases::example1;
+
+    #[test]
+    fn predicates() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let report = db.summary().report(1);
+        let age = &report.predicates[0];
+
+        assert_eq!(report.triples, 4);
+        assert_eq!(report.subjects, 3);
+        assert_eq!(age.predicate, "<age>");
+        assert_eq!((age.triples, age.subjects, age.objects), (3, Some(3), 2));
+        assert_eq!(age.top_objects, vec![("30".to_owned(), 2)]);
+        assert_eq!(age.numbers.as_ref().map(|h| h.values), Some(3));
+        assert_eq!(report.predicates[1].prefixes.len(), 1);
+    }
+
+    #[test]
+    fn joins() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let report = db.summary().report(10);
+
+        // P1 and P3 share the age 30, every age joins with itself
+        assert_eq!(report.joins[0].kind, "O_O");
+        assert_eq!(report.joins[0].triples, 5);
+        assert!(report.joins.iter().all(|j| j.triples > 0));
+        assert!(report
+            .joins
+            .windows(2)
+            .all(|w| w[0].triples >= w[1].triples));
+    }
+
+    #[test]
+    fn json() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let json = serde_json::to_value(db.summary().report(10)).unwrap();
+
+        assert_eq!(json["predicates"][0]["predicate"], "<age>");
+        assert_eq!(json["predicates"][0]["top_objects"][0][1], 2);
+        assert_eq!(json["predicates"][1]["numbers"], serde_json::Value::Null);
+    }
+}
+
+mod pushdown {
+    use std::ops::Bound;
+
+    use crate::examples::databases::example1 as db;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, Optimizer},
+    };
+    use crate::syntax::{
+        database::Predicate,
+        query::{Condition, Expression, Object, Query},
+        values::ValueRange,
+    };
+
+    use super::{assert_same_solutions, select, solutions, triple};
+
+    fn age(condition: Condition) -> Query {
+        select(
+            &["?p", "?a"],
+            Expression::Filter(triple("?p", "<age>", "?a"), Box::new(condition)),
+        )
+    }
+
+    fn options(condition: bool) -> EvalOptions {
+        EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_condition(condition)
+            .with_explain(true)
+    }
+
+    fn assert_pushed(query: Query, pushed: &str, size: usize) {
+        let expected = solutions(&query, options(false));
+        assert_eq!(expected.len(), size);
+
+        assert_same_solutions(expected.clone(), solutions(&query, options(true)));
+        assert_same_solutions(
+            expected.clone(),
+            solutions(&query, options(true).with_batch(true)),
+        );
+        assert_same_solutions(expected, solutions(&query, options(true).with_threads(2)));
+
+        let result = evaluate(&db(), query, Some(options(true))).unwrap();
+        let explain = result.explain().unwrap();
+
+        assert!(explain.contains(pushed), "{explain}");
+        assert!(!explain.contains("FILTER"), "{explain}");
+    }
+
+    #[test]
+    fn range() {
+        assert_pushed(
+            age(Condition::GT(
+                Object::V("?a".into()),
+                Object::L("29".into()),
+            )),
+            "[?a in (29, inf)]",
+            2,
+        );
+    }
+
+    #[test]
+    fn reversed_range() {
+        assert_pushed(
+            age(Condition::And(
+                Box::new(Condition::GT(
+                    Object::L("31".into()),
+                    Object::V("?a".into()),
+                )),
+                Box::new(Condition::LT(
+                    Object::L("29".into()),
+                    Object::V("?a".into()),
+                )),
+            )),
+            "[?a in (29, 31)]",
+            2,
+        );
+    }
+
+    #[test]
+    fn equality() {
+        assert_pushed(
+            age(Condition::Equals(
+                Object::V("?p".into()),
+                Object::I("<P3>".into()),
+            )),
+            "SCAN { <P3> <age> ?a } [?p = <P3>]",
+            1,
+        );
+    }
+
+    #[test]
+    fn remaining_conditions() {
+        // ?a <= 29 also holds for objects that are not numbers, so it stays in the filter
+        let query = age(Condition::And(
+            Box::new(Condition::Equals(
+                Object::V("?a".into()),
+                Object::L("30".into()),
+            )),
+            Box::new(Condition::Not(Box::new(Condition::GT(
+                Object::V("?p".into()),
+                Object::L("29".into()),
+            )))),
+        ));
+
+        let expected = solutions(&query, options(false));
+        assert_eq!(expected.len(), 2);
+        assert_same_solutions(expected, solutions(&query, options(true)));
+
+        let result = evaluate(&db(), query, Some(options(true))).unwrap();
+        let explain = result.explain().unwrap();
+
+        assert!(explain.contains("FILTER"), "{explain}");
+        assert!(explain.contains("[?a = 30]"), "{explain}");
+    }
+
+    #[test]
+    fn value_index() {
+        let db = db();
+        let age = Predicate::I("<age>".into());
+        let above = |lower| ValueRange::new().above(lower);
+
+        assert_eq!(
+            db.values()
+                .positions(Some(&age), &above(Bound::Included(30.0))),
+            vec![0, 2]
+        );
+        assert_eq!(
+            db.values().positions(None, &above(Bound::Excluded(30.0))),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            db.values()
+                .positions(Some(&Predicate::I("<email>".into())), &ValueRange::new()),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            db.values()
+                .positions(Some(&age), &ValueRange::new().below(Bound::Included(29.0))),
+            vec![1]
+        );
+    }
+}
+
+mod rewrite {
+    use clap::ValueEnum;
+
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, Optimizer, Rewrite},
+    };
+    use crate::syntax::query::{Condition, Expression, Object, Query};
+
+    use super::{assert_same_solutions, select, solutions, triple};
+
+    /// People older than 29 with an email
+    fn filtered_join() -> Query {
+        select(
+            &["?p", "?e"],
+            Expression::Filter(
+                Box::new(Expression::And(
+                    triple("?p", "<email>", "?e"),
+                    triple("?p", "<age>", "?a"),
+                )),
+                Box::new(Condition::And(
+                    Box::new(Condition::GT(
+                        Object::V("?a".into()),
+                        Object::L("29".into()),
+                    )),
+                    Box::new(Condition::Bound("?e".into())),
+                )),
+            ),
+        )
+    }
+
+    /// People with an age and an optional email, keeping only those with an email
+    fn filtered_optional() -> Query {
+        select(
+            &["?p", "?e"],
+            Expression::Filter(
+                Box::new(Expression::Optional(
+                    triple("?p", "<age>", "?a"),
+                    triple("?p", "<email>", "?e"),
+                )),
+                Box::new(Condition::Bound("?e".into())),
+            ),
+        )
+    }
+
+    fn nested_union() -> Query {
+        select(
+            &["?p", "?a", "?e"],
+            Expression::Union(
+                Box::new(Expression::Union(
+                    triple("?p", "<age>", "?a"),
+                    triple("?p", "<email>", "?e"),
+                )),
+                triple("?p", "<age>", "?a"),
+            ),
+        )
+    }
+
+    fn constant(condition: Condition) -> Query {
+        select(
+            &["?p", "?a"],
+            Expression::Filter(triple("?p", "<age>", "?a"), Box::new(condition)),
+        )
+    }
+
+    fn options(rewrites: &[Rewrite]) -> EvalOptions {
+        EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_rewrites(rewrites.to_vec())
+            .with_explain(true)
+    }
+
+    fn explain(query: Query, rewrites: &[Rewrite]) -> String {
+        let result = evaluate(&db(), query, Some(options(rewrites))).unwrap();
+
+        result.explain().unwrap().to_owned()
+    }
+
+    fn queries() -> Vec<Query> {
+        vec![
+            queries::example1(),
+            queries::example2(),
+            queries::example4(),
+            queries::example5(),
+            queries::example6(),
+            queries::example7(),
+            queries::example8(),
+            filtered_join(),
+            filtered_optional(),
+            nested_union(),
+        ]
+    }
+
+    #[test]
+    fn same_results() {
+        let mut rules: Vec<Vec<Rewrite>> =
+            Rewrite::value_variants().iter().map(|r| vec![*r]).collect();
+        rules.push(Rewrite::value_variants().to_vec());
+
+        for query in queries() {
+            let expected = solutions(&query, options(&[]));
+
+            for rewrites in rules.iter() {
+                assert_same_solutions(expected.clone(), solutions(&query, options(rewrites)));
+
+                let batch = options(rewrites).with_batch(true);
+                assert_same_solutions(expected.clone(), solutions(&query, batch));
+            }
+        }
+    }
+
+    #[test]
+    fn push_filter() {
+        // The condition needs variables of both sides of the join until it is split
+        let unchanged = explain(filtered_join(), &[Rewrite::PushFilter]);
+        assert!(
+            unchanged.lines().nth(1).unwrap().contains("FILTER"),
+            "{unchanged}"
+        );
+
+        let pushed = explain(
+            filtered_join(),
+            &[Rewrite::SplitFilter, Rewrite::PushFilter],
+        );
+        let lines: Vec<&str> = pushed.lines().collect();
+
+        assert!(lines[1].contains("JOIN"), "{pushed}");
+        assert_eq!(lines.iter().filter(|l| l.contains("FILTER")).count(), 2);
+    }
+
+    #[test]
+    fn optional_to_join() {
+        let rewritten = explain(filtered_optional(), &[Rewrite::OptionalToJoin]);
+
+        assert!(!rewritten.contains("LEFTJOIN"), "{rewritten}");
+        assert!(rewritten.contains("JOIN"), "{rewritten}");
+
+        // An unbound email does not reject any result
+        let optional = select(
+            &["?p", "?e"],
+            Expression::Filter(
+                Box::new(Expression::Optional(
+                    triple("?p", "<age>", "?a"),
+                    triple("?p", "<email>", "?e"),
+                )),
+                Box::new(Condition::Not(Box::new(Condition::Bound("?e".into())))),
+            ),
+        );
+
+        assert!(explain(optional, &[Rewrite::OptionalToJoin]).contains("LEFTJOIN"));
+    }
+
+    #[test]
+    fn flatten_union() {
+        let flattened = explain(nested_union(), &[Rewrite::FlattenUnion]);
+        let lines: Vec<&str> = flattened.lines().collect();
+
+        assert!(lines[1].contains("UNION"), "{flattened}");
+        assert!(lines[2].contains("SCAN"), "{flattened}");
+        assert!(lines[3].contains("UNION"), "{flattened}");
+    }
+
+    #[test]
+    fn push_projection() {
+        let emails = select(
+            &["?e"],
+            Expression::And(triple("?p", "<email>", "?e"), triple("?p", "<age>", "?a")),
+        );
+
+        let pushed = explain(emails, &[Rewrite::PushProjection]);
+
+        assert_eq!(pushed.matches("PROJECTION [?p]").count(), 1, "{pushed}");
+    }
+
+    #[test]
+    fn fold_constants() {
+        let always = constant(Condition::Equals(
+            Object::L("1".into()),
+            Object::L("1".into()),
+        ));
+        let never = constant(Condition::And(
+            Box::new(Condition::GT(Object::L("1".into()), Object::L("2".into()))),
+            Box::new(Condition::Bound("?p".into())),
+        ));
+
+        assert_eq!(
+            solutions(&always, options(&[Rewrite::FoldConstants])).len(),
+            3
+        );
+        assert!(!explain(always, &[Rewrite::FoldConstants]).contains("FILTER"));
+
+        assert!(solutions(&never, options(&[Rewrite::FoldConstants])).is_empty());
+        assert!(explain(never, &[Rewrite::FoldConstants]).contains("LIMIT 0"));
+    }
+}
+
+mod reorder {
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, JoinOrder, Optimizer},
+    };
+    use crate::syntax::query::{Condition, Expression, Object, Query};
+
+    use super::{assert_same_solutions, select, solutions, triple};
+
+    /// People of the same age, with the optional email of the first
+    fn join_optional() -> Query {
+        select(
+            &["?p", "?q", "?e"],
+            Expression::And(
+                Box::new(Expression::Optional(
+                    triple("?p", "<age>", "?a"),
+                    triple("?p", "<email>", "?e"),
+                )),
+                triple("?q", "<age>", "?a"),
+            ),
+        )
+    }
+
+    /// The join shares `?q` with the optional part only
+    fn join_not_well_designed() -> Query {
+        select(
+            &["?p", "?q", "?e"],
+            Expression::And(
+                Box::new(Expression::Optional(
+                    triple("?p", "<age>", "?a"),
+                    triple("?q", "<email>", "?e"),
+                )),
+                triple("?q", "<age>", "?b"),
+            ),
+        )
+    }
+
+    fn join_union() -> Query {
+        select(
+            &["?p", "?a", "?e", "?b"],
+            Expression::And(
+                triple("?p", "<age>", "?a"),
+                Box::new(Expression::Union(
+                    triple("?p", "<email>", "?e"),
+                    triple("?p", "<age>", "?b"),
+                )),
+            ),
+        )
+    }
+
+    fn join_filter() -> Query {
+        select(
+            &["?p", "?a", "?e"],
+            Expression::And(
+                Box::new(Expression::Filter(
+                    triple("?p", "<age>", "?a"),
+                    Box::new(Condition::GT(
+                        Object::V("?a".into()),
+                        Object::L("29".into()),
+                    )),
+                )),
+                triple("?p", "<email>", "?e"),
+            ),
+        )
+    }
+
+    fn options() -> EvalOptions {
+        EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_explain(true)
+    }
+
+    fn explain(query: Query) -> Vec<String> {
+        let result = evaluate(&db(), query, Some(options())).unwrap();
+
+        result
+            .explain()
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn same_results() {
+        for query in [
+            queries::example7(),
+            queries::example8(),
+            join_optional(),
+            join_not_well_designed(),
+            join_union(),
+            join_filter(),
+        ] {
+            let expected = solutions(
+                &query,
+                EvalOptions::default().with_optimizer(Optimizer::Off),
+            );
+
+            assert_same_solutions(expected.clone(), solutions(&query, options()));
+            assert_same_solutions(
+                expected.clone(),
+                solutions(&query, options().with_join_order(JoinOrder::Dp)),
+            );
+            assert_same_solutions(expected, solutions(&query, options().with_batch(true)));
+        }
+    }
+
+    #[test]
+    fn across_optional() {
+        let lines = explain(join_optional());
+
+        // The left join is evaluated as the union of a join and a minus of both inputs
+        assert!(lines[1].contains("LEFTJOIN"), "{lines:#?}");
+        assert!(lines[4].contains("JOIN [?a]"), "{lines:#?}");
+
+        let lines = explain(join_not_well_designed());
+
+        assert!(!lines[1].contains("LEFTJOIN"), "{lines:#?}");
+        assert!(lines[1].contains("JOIN"), "{lines:#?}");
+    }
+
+    #[test]
+    fn into_union() {
+        let lines = explain(join_union());
+
+        assert!(lines[1].contains("UNION"), "{lines:#?}");
+        assert_eq!(lines.iter().filter(|l| l.contains("JOIN")).count(), 2);
+    }
+
+    #[test]
+    fn across_filter() {
+        let lines = explain(join_filter());
+
+        assert!(lines[1].contains("FILTER"), "{lines:#?}");
+        assert!(lines[2].contains("JOIN"), "{lines:#?}");
+    }
+}
+
+mod adaptive {
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, Optimizer},
+    };
+    use crate::syntax::query::{Expression, Query};
+
+    use super::{assert_same_solutions, select, solutions, triple};
+
+    /// People of the same age as someone with an email
+    fn chain() -> Query {
+        select(
+            &["?p", "?q", "?e"],
+            Expression::And(
+                Box::new(Expression::And(
+                    triple("?p", "<age>", "?a"),
+                    triple("?q", "<age>", "?a"),
+                )),
+                triple("?q", "<email>", "?e"),
+            ),
+        )
+    }
+
+    fn options(threshold: f64) -> EvalOptions {
+        EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_adaptive(Some(threshold))
+            .with_explain(true)
+    }
+
+    #[test]
+    fn same_results() {
+        for query in [
+            queries::example1(),
+            queries::example2(),
+            queries::example4(),
+            queries::example5(),
+            queries::example6(),
+            queries::example7(),
+            queries::example8(),
+            chain(),
+        ] {
+            let off = EvalOptions::default().with_optimizer(Optimizer::Off);
+            let expected = solutions(&query, off);
+
+            for threshold in [1.0, 2.0, f64::INFINITY] {
+                assert_same_solutions(expected.clone(), solutions(&query, options(threshold)));
+            }
+        }
+    }
+
+    #[test]
+    fn reoptimizes() {
+        // Every join of the same age finds 5 results and the join with emails 1, not 16
+        let result = evaluate(&db(), chain(), Some(options(2.0))).unwrap();
+        assert_eq!(result.size(), 2);
+        assert!(result.reoptimizations() > 0);
+
+        let result = evaluate(&db(), chain(), Some(options(f64::INFINITY))).unwrap();
+        assert_eq!(result.size(), 2);
+        assert_eq!(result.reoptimizations(), 0);
+    }
+
+    #[test]
+    fn within_memory_budget() {
+        // No join fits into the budget, so none is materialized and the plan is kept
+        let opts = options(2.0).with_memory_budget(Some(1));
+        let result = evaluate(&db(), chain(), Some(opts)).unwrap();
+        assert_eq!(result.size(), 2);
+        assert_eq!(result.reoptimizations(), 0);
+        assert!(!result.explain().unwrap().contains("MATERIALIZED"));
+    }
+
+    #[test]
+    fn materialized() {
+        let result = evaluate(&db(), chain(), Some(options(f64::INFINITY))).unwrap();
+        let explain = result.explain().unwrap();
+
+        let line = explain
+            .lines()
+            .find(|l| l.contains("MATERIALIZED"))
+            .unwrap_or_else(|| panic!("{explain}"));
+        assert!(line.contains("est. rows 16"), "{explain}");
+
+        // Without adaptive optimization nothing is materialized
+        let result = evaluate(&db(), chain(), Some(options(2.0).with_adaptive(None))).unwrap();
+        assert!(!result.explain().unwrap().contains("MATERIALIZED"));
+    }
+}
+
+mod explain {
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, Optimizer},
+        selectivity::q_error,
+    };
+
+    fn options() -> EvalOptions {
+        EvalOptions::default().with_optimizer(Optimizer::Fixed)
+    }
+
+    #[test]
+    fn disabled() {
+        let result = evaluate(&db(), queries::example2(), Some(options())).unwrap();
+
+        assert!(result.explain().is_none());
+    }
+
+    #[test]
+    fn same_results() {
+        let expected = evaluate(&db(), queries::example2(), Some(options())).unwrap();
+        let explained = evaluate(
+            &db(),
+            queries::example2(),
+            Some(options().with_explain(true)),
+        )
+        .unwrap();
+
+        assert_eq!(expected, explained);
+    }
+
+    #[test]
+    fn actual_rows() {
+        for query in [
+            queries::example1(),
+            queries::example2(),
+            queries::example5(),
+        ] {
+            let result = evaluate(&db(), query, Some(options().with_explain(true))).unwrap();
+            let explain = result.explain().unwrap();
+
+            // The root of the plan produces all results of the query
+            let root = explain.lines().next().unwrap();
+            assert!(root.contains(&format!("actual rows {}", result.size())));
+        }
+    }
+
+    #[test]
+    fn q_errors() {
+        assert_eq!(q_error(10.0, 5.0), 2.0);
+        assert_eq!(q_error(5.0, 10.0), 2.0);
+        assert_eq!(q_error(0.0, 0.0), 1.0);
+    }
+}
+
+mod export {
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        evaluate,
+        options::{EvalOptions, Optimizer, PlanFormat},
+    };
+
+    fn options(format: PlanFormat) -> EvalOptions {
+        EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_export(Some(format))
+    }
+
+    #[test]
+    fn json() {
+        let result = evaluate(&db(), queries::example1(), Some(options(PlanFormat::Json))).unwrap();
+        let plan: serde_json::Value = serde_json::from_str(result.plan().unwrap()).unwrap();
+
+        assert_eq!(plan["operator"], "PROJECTION");
+        assert_eq!(plan["actual_rows"], result.size());
+
+        let join = &plan["children"][0];
+        assert_eq!(join["operator"], "JOIN");
+        assert!(join["estimated_rows"].is_number());
+        assert!(!join["join_vars"].as_array().unwrap().is_empty());
+        assert_eq!(join["children"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn dot() {
+        let result = evaluate(&db(), queries::example1(), Some(options(PlanFormat::Dot))).unwrap();
+        let plan = result.plan().unwrap();
+
+        assert!(plan.starts_with("digraph plan {"));
+        assert!(plan.ends_with('}'));
+        assert!(plan.contains("n0 -> n1;"));
+        assert!(plan.contains(&format!("actual rows {}", result.size())));
+        assert!(result.explain().is_none());
+    }
+}
+
+mod qerror {
+    use crate::examples::databases::example1;
+    use crate::examples::queries;
+    use crate::semantics::{options::Optimizer, qerror::qerror};
+    use crate::syntax::query::{Condition, Expression, Object, Type};
+
+    #[test]
+    fn connected_parts() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let estimates = qerror(queries::example1(), &db, 2, None, 0).unwrap();
+
+        let mut parts: Vec<Vec<usize>> = estimates.iter().map(|e| e.patterns.clone()).collect();
+        parts.dedup();
+        assert_eq!(parts, vec![vec![0], vec![1], vec![0, 1]]);
+
+        let joined = estimates
+            .iter()
+            .find(|e| e.patterns.len() == 2 && e.estimator == Optimizer::Fixed.to_string())
+            .unwrap();
+        assert_eq!(joined.actual, Some(1));
+
+        assert!(estimates
+            .iter()
+            .all(|e| e.estimator != Optimizer::Off.to_string()));
+        assert!(estimates
+            .iter()
+            .filter_map(|e| e.q_error())
+            .all(|q| q >= 1.0));
+    }
+
+    #[test]
+    fn single_patterns() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let estimates = qerror(queries::example1(), &db, 1, None, 0).unwrap();
+
+        assert!(estimates.iter().all(|e| e.patterns.len() == 1));
+    }
+
+    #[test]
+    fn ignores_filters() {
+        let mut db = example1();
+        db.compute_statistics();
+
+        let mut query = queries::example1();
+        if let Type::SelectQuery(_, expression, _) = &mut query.kind {
+            // No one is that old
+            *expression = Expression::Filter(
+                Box::new(expression.clone()),
+                Box::new(Condition::GT(
+                    Object::V("?a".into()),
+                    Object::L("99".into()),
+                )),
+            );
+        }
+
+        let filtered = qerror(query, &db, 2, None, 0).unwrap();
+        let unfiltered = qerror(queries::example1(), &db, 2, None, 0).unwrap();
+
+        for (filtered, unfiltered) in filtered.iter().zip(&unfiltered) {
+            assert_eq!(filtered.actual, unfiltered.actual);
+            assert_eq!(filtered.estimate, unfiltered.estimate);
+        }
+    }
+}
+
+mod sampling {
+    use std::time::Duration;
+
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        mapping::MappingSet,
+        options::{EvalOptions, JoinOrder, Optimizer},
+        qerror::qerror,
+        stream,
+    };
+    use crate::syntax::database::{Database, Object, Predicate, Subject, Triple};
+
+    use super::assert_same_solutions;
+
+    #[test]
+    fn same_results() {
+        for query in [
+            queries::example1(),
+            queries::example2(),
+            queries::example4(),
+            queries::example5(),
+            queries::example6(),
+            queries::example7(),
+            queries::example8(),
+        ] {
+            let expected: MappingSet = stream(
+                &db(),
+                &query,
+                Some(EvalOptions::default().with_optimizer(Optimizer::Off)),
+            )
+            .unwrap()
+            .collect();
+
+            for options in [
+                EvalOptions::default().with_optimizer(Optimizer::Sampling),
+                EvalOptions::default()
+                    .with_optimizer(Optimizer::Sampling)
+                    .with_join_order(JoinOrder::Dp),
+                // Every estimate makes at least one walk, however short its budget
+                EvalOptions::default()
+                    .with_optimizer(Optimizer::Sampling)
+                    .with_sampling(1, Some(Duration::ZERO)),
+            ] {
+                assert_same_solutions(
+                    expected.clone(),
+                    stream(&db(), &query, Some(options)).unwrap().collect(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn estimates() {
+        let mut db = db();
+        db.compute_statistics();
+
+        let estimates: Vec<_> = qerror(queries::example1(), &db, 2, None, 0)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.estimator == Optimizer::Sampling.to_string())
+            .collect();
+        assert_eq!(estimates.len(), 3);
+
+        // Scans with fewer candidates than samples are counted exactly, joins are close
+        for estimate in estimates.iter() {
+            let q_error = estimate.q_error().unwrap();
+
+            if estimate.patterns.len() == 1 {
+                assert_eq!(q_error, 1.0, "{estimate:?}");
+            } else {
+                assert!(q_error < 1.5, "{estimate:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn larger_than_sample() {
+        // Ten times as many people as samples and walks
+        let mut db: Database = (0..1000)
+            .flat_map(|i| {
+                [
+                    Triple::new(
+                        Subject::I(format!("<P{i}>").into()),
+                        Predicate::I("<age>".into()),
+                        Object::L(format!("{}", i % 50).into()),
+                    ),
+                    Triple::new(
+                        Subject::I(format!("<P{i}>").into()),
+                        Predicate::I("<email>".into()),
+                        Object::L(format!("\"p{i}@example.org\"").into()),
+                    ),
+                ]
+            })
+            .collect();
+        db.compute_statistics();
+
+        let estimates = qerror(queries::example1(), &db, 2, None, 0).unwrap();
+        let sampled: Vec<_> = estimates
+            .iter()
+            .filter(|e| e.estimator == Optimizer::Sampling.to_string())
+            .collect();
+        assert_eq!(sampled.len(), 3);
+
+        // Every candidate of a scan matches, the walks only miss among the triples of a person
+        for estimate in sampled {
+            let q_error = estimate.q_error().unwrap();
+
+            if estimate.patterns.len() == 1 {
+                assert_eq!(q_error, 1.0, "{estimate:?}");
+            } else {
+                assert!(q_error < 1.5, "{estimate:?}");
+            }
+        }
+    }
+}
+
+mod estimator {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::examples::databases::example1 as db;
+    use crate::examples::queries;
+    use crate::semantics::{
+        estimator::{self, EstimationContext, EstimationResult, Estimator, SubPlan},
+        evaluate,
+        mapping::MappingSet,
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+
+    use super::assert_same_solutions;
+
+    /// Estimates scans by the length of their pattern, at most the number of triples, and other
+    /// operations by the product of their inputs, counting its estimates
+    struct Length(Arc<AtomicUsize>);
+
+    impl Estimator for Length {
+        fn cardinality(&self, plan: &SubPlan, ctx: &EstimationContext) -> EstimationResult {
+            self.0.fetch_add(1, Ordering::SeqCst);
+
+            match plan.pattern() {
+                Some(pattern) => Ok(pattern.concat().len().min(ctx.triples()) as f64),
+                None => plan
+                    .children()
+                    .iter()
+                    .map(|c| self.cardinality(c, ctx))
+                    .product(),
+            }
+        }
+    }
+
+    #[test]
+    fn builtins() {
+        let names = estimator::names();
+
+        assert!(names.contains(&Optimizer::Arqpf.name()));
+        assert!(names.contains(&Optimizer::Sampling.name()));
+        assert!(!names.contains(&Optimizer::Off.name()));
+    }
+
+    #[test]
+    fn registered() {
+        let estimates = Arc::new(AtomicUsize::new(0));
+        estimator::register("length", Length(estimates.clone()));
+
+        for query in [
+            queries::example1(),
+            queries::example2(),
+            queries::example5(),
+        ] {
+            let expected: MappingSet = stream(
+                &db(),
+                &query,
+                Some(EvalOptions::default().with_optimizer(Optimizer::Off)),
+            )
+            .unwrap()
+            .collect();
+
+            let options = EvalOptions::default().with_estimator(Some("length".to_owned()));
+
+            assert_same_solutions(
+                expected,
+                stream(&db(), &query, Some(options)).unwrap().collect(),
+            );
+        }
+
+        assert!(estimates.load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    fn by_name() {
+        let options = EvalOptions::default()
+            .with_estimator(Some(Optimizer::Fixed.name()))
+            .with_explain(true);
+        let result = evaluate(&db(), queries::example1(), Some(options)).unwrap();
+
+        let expected = evaluate(
+            &db(),
+            queries::example1(),
+            Some(
+                EvalOptions::default()
+                    .with_optimizer(Optimizer::Fixed)
+                    .with_explain(true),
+            ),
+        )
+        .unwrap();
+
+        // Both are estimated the same, only the times differ
+        let estimates = |explain: Option<&str>| -> Vec<String> {
+            explain
+                .unwrap()
+                .lines()
+                .map(|l| l.split(", time").next().unwrap().to_owned())
+                .collect()
+        };
+        assert_eq!(estimates(result.explain()), estimates(expected.explain()));
+    }
+
+    #[test]
+    fn unknown() {
+        let options = EvalOptions::default().with_estimator(Some("unknown".to_owned()));
+
+        let error = evaluate(&db(), queries::example1(), Some(options)).unwrap_err();
+        assert!(error.to_string().contains("unknown"));
+    }
+}
+
+mod result_cache {
+    use std::{collections::HashMap, io};
+
+    use crate::examples::{databases::example1 as db, queries};
+    use crate::semantics::{
+        cache::ResultCache,
+        mapping::MappingSet,
+        operations::{
+            context::Context,
+            visitors::{planner::Planner, reuse::Reuse},
+            Operation, OperationVisitor,
+        },
+        options::{EvalOptions, Optimizer},
+        stream,
+    };
+    use crate::syntax::query::{
+        self, Expression, Query, QueryVisitor, SolutionModifier, Type, Variable, Variables,
+    };
+
+    use super::assert_same_solutions;
+
+    /// The first example query with other names for its variables
+    fn renamed(p: &str, e: &str, a: &str) -> Query {
+        let triple = |predicate: &str, object: &str| {
+            Box::new(Expression::Triple(
+                Box::new(query::Subject::V(p.into())),
+                Box::new(query::Predicate::I(predicate.into())),
+                Box::new(query::Object::V(object.into())),
+            ))
+        };
+
+        Query {
+            prologue: HashMap::new(),
+            kind: Type::SelectQuery(
+                Variables::new(vec![p.into(), e.into(), a.into()]),
+                Expression::And(triple("<email>", e), triple("<age>", a)),
+                SolutionModifier::default(),
+            ),
+        }
+    }
+
+    fn solutions(query: &Query, cache: Option<&ResultCache>) -> MappingSet {
+        let options = EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_cache(cache.cloned());
+
+        stream(&db(), query, Some(options)).unwrap().collect()
+    }
+
+    #[test]
+    fn reuses_results() {
+        let cache = ResultCache::new(1 << 20);
+
+        let first = solutions(&queries::example1(), Some(&cache));
+        let missed = cache.stats();
+        assert_eq!(missed.hits, 0);
+        assert!(missed.misses > 0 && missed.entries > 0);
+
+        let second = solutions(&queries::example1(), Some(&cache));
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, missed.misses);
+
+        let expected = solutions(&queries::example1(), None);
+        assert_same_solutions(expected.clone(), first);
+        assert_same_solutions(expected, second);
+    }
+
+    #[test]
+    fn ignores_variable_names() {
+        let cache = ResultCache::new(1 << 20);
+
+        solutions(&renamed("?p", "?e", "?a"), Some(&cache));
+        let cached = solutions(&renamed("?x", "?y", "?z"), Some(&cache));
+        assert_eq!(cache.stats().hits, 1);
+
+        let expected = solutions(&renamed("?x", "?y", "?z"), None);
+        assert!(expected[0].get(&Variable::from("?x")).is_some());
+        assert_same_solutions(expected, cached);
+    }
+
+    #[test]
+    fn expands_prefixed_names() {
+        let cache = ResultCache::new(1 << 20);
+
+        let mut prefixed = renamed("?p", "?e", "?a");
+        prefixed.prologue.insert("ex".into(), "<>".into());
+        if let Type::SelectQuery(_, Expression::And(email, _), _) = &mut prefixed.kind {
+            **email = Expression::Triple(
+                Box::new(query::Subject::V("?p".into())),
+                Box::new(query::Predicate::I("ex:email".into())),
+                Box::new(query::Object::V("?e".into())),
+            );
+        }
+
+        solutions(&renamed("?p", "?e", "?a"), Some(&cache));
+        let cached = solutions(&prefixed, Some(&cache));
+        assert_eq!(cache.stats().hits, 1);
+
+        assert_same_solutions(solutions(&prefixed, None), cached);
+    }
+
+    #[test]
+    fn stays_within_budget() {
+        let cache = ResultCache::new(0);
+
+        let cached = solutions(&queries::example1(), Some(&cache));
+        solutions(&queries::example1(), Some(&cache));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.entries, stats.bytes), (0, 0, 0));
+        assert_same_solutions(solutions(&queries::example1(), None), cached);
+    }
+
+    #[test]
+    fn stays_within_memory_budget() {
+        let cache = ResultCache::new(1 << 20);
+        let options = EvalOptions::default()
+            .with_optimizer(Optimizer::Fixed)
+            .with_cache(Some(cache.clone()))
+            .with_memory_budget(Some(0));
+
+        let streamed: MappingSet = stream(&db(), &queries::example1(), Some(options))
+            .unwrap()
+            .collect();
+
+        assert_eq!(cache.stats().entries, 0);
+        assert_same_solutions(solutions(&queries::example1(), None), streamed);
+    }
+
+    #[test]
+    fn skips_failed_results() {
+        let db = db();
+        let query = queries::example1().expand().unwrap();
+        let plan = Planner::new(&db).visit(&query);
+        let cache = ResultCache::new(1 << 20);
+
+        // As if a spill could not be written while evaluating the plan
+        let ctx = Context::new();
+        ctx.fail(io::Error::other("injected"));
+
+        let reused = Reuse::new(cache.clone(), ctx).visit(&plan);
+        assert!(!matches!(reused, Operation::Materialized(_)));
+        assert_eq!(cache.stats().entries, 0);
+
+        Reuse::new(cache.clone(), Context::new()).visit(&plan);
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[test]
+    fn streams_below_limit() {
+        let cache = ResultCache::new(1 << 20);
+
+        let mut query = renamed("?p", "?e", "?a");
+        if let Type::SelectQuery(_, _, modifier) = &mut query.kind {
+            modifier.with_limit(1);
+        }
+
+        assert_eq!(solutions(&query, Some(&cache)).len(), 1);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
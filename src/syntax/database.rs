@@ -1,30 +1,45 @@
+use dpc_pariter::IteratorExt;
+use fnv::FnvHasher;
 use iter_progress::ProgressableIter;
 use itertools::Itertools;
-use ndhistogram::{axis::Uniform, sparsehistogram, AxesTuple, HashHistogram, Histogram};
-use rand::{seq::IteratorRandom, thread_rng};
-use ron::Result;
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
     collections::{HashMap, HashSet},
     fmt::Display,
-    fs::{self, File},
-    hash::Hash,
-    io::Write,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
+    sync::OnceLock,
+    thread,
 };
 
-use crate::semantics::{
-    evaluate,
-    options::{EvalOptions, Optimizer},
+use super::{
+    degrees::Degrees,
+    dictionary::EncodedTriples,
+    histogram::{Axis, EquiDepth, Joint, Key, Prefixes, Restriction},
+    report::{JoinReport, PredicateReport, Report},
+    terms::{Position, TermIndex},
+    values::ValueIndex,
+    Iri, Literal,
 };
-use crate::syntax::query::{self, Expression, Query, Type::SelectQuery, Variable, Variables};
 
-use super::{Iri, Literal};
+/// Number of predicate pairs with joint histograms, the ones most often on the same subject
+const CORRELATED_PAIRS: usize = 64;
 
 #[derive(Debug)]
 pub struct Database {
     triples: Vec<Triple>,
     summary: Summary,
+    encoded: OnceLock<EncodedTriples>,
+    values: OnceLock<ValueIndex>,
+    terms: OnceLock<TermIndex>,
+    /// Whether the summary describes the triples and is kept in sync as they change
+    statistics: bool,
+    /// Seed of the random decisions while building the summary
+    seed: u64,
 }
 
 impl Database {
@@ -32,17 +47,137 @@ impl Database {
         Self {
             triples: Vec::new(),
             summary: Summary::new(),
+            encoded: OnceLock::new(),
+            values: OnceLock::new(),
+            terms: OnceLock::new(),
+            statistics: false,
+            seed: 0,
         }
     }
 
+    /// Build the summary with `seed` for every random decision, for a reproducible summary
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /**
+     * Add a triple, and account for it in the summary if the statistics were built. The indexes
+     * that were already built are updated.
+     */
     pub fn add(&mut self, triple: Triple) {
+        if self.statistics {
+            // The summary changes by the triples the new one joins with
+            let terms = term_index(&self.terms, &self.triples);
+
+            self.summary
+                .insert(&triple, &Degrees::new(&self.triples, terms));
+        }
+
+        let i = self.triples.len();
+
+        if let Some(encoded) = self.encoded.get_mut() {
+            encoded.push(&triple);
+        }
+        if let Some(values) = self.values.get_mut() {
+            values.insert(&triple, i);
+        }
+        if let Some(terms) = self.terms.get_mut() {
+            terms.insert(&triple, i);
+        }
+
         self.triples.push(triple);
     }
 
+    /**
+     * Remove one occurrence of a triple, and account for it in the summary if the statistics were
+     * built. Returns whether the database had the triple.
+     *
+     * The triple is looked up in the term index, and the last triple takes its position.
+     */
+    pub fn remove(&mut self, triple: &Triple) -> bool {
+        let bound = |position: Position| self.terms().positions(position, &position.term(triple));
+        let candidates = [Position::Subject, Position::Predicate, Position::Object]
+            .map(bound)
+            .into_iter()
+            .min_by_key(|positions| positions.len())
+            .unwrap_or_default();
+
+        let Some(i) = candidates.iter().copied().find(|i| {
+            let t = &self.triples[*i];
+
+            t.subject == triple.subject
+                && t.predicate == triple.predicate
+                && t.object == triple.object
+        }) else {
+            return false;
+        };
+
+        let last = self.triples.len() - 1;
+
+        if let Some(encoded) = self.encoded.get_mut() {
+            encoded.swap_remove(i);
+        }
+        if let Some(values) = self.values.get_mut() {
+            values.remove(&self.triples[i], i);
+
+            if i != last {
+                values.relocate(&self.triples[last], last, i);
+            }
+        }
+        if let Some(terms) = self.terms.get_mut() {
+            terms.remove(&self.triples[i], i);
+
+            if i != last {
+                terms.relocate(&self.triples[last], last, i);
+            }
+        }
+
+        let removed = self.triples.swap_remove(i);
+
+        if self.statistics {
+            let terms = term_index(&self.terms, &self.triples);
+
+            self.summary
+                .remove(&removed, &Degrees::new(&self.triples, terms));
+        }
+
+        true
+    }
+
     pub fn triples(&self) -> &Vec<Triple> {
         &self.triples
     }
 
+    /// Dictionary encoded triples, which are built on first use
+    pub(crate) fn encoded(&self) -> &EncodedTriples {
+        self.encoded.get_or_init(|| {
+            log::info!("Encoding {} triples...", self.triples.len());
+
+            let encoded: EncodedTriples = self.triples.iter().collect();
+
+            log::info!("Encoded {} distinct terms", encoded.dictionary.len());
+
+            encoded
+        })
+    }
+
+    /// Index of the numeric literal objects for range lookups, which is built on first use
+    pub(crate) fn values(&self) -> &ValueIndex {
+        self.values.get_or_init(|| {
+            log::info!(
+                "Indexing the literal values of {} triples...",
+                self.triples.len()
+            );
+
+            self.triples.iter().collect()
+        })
+    }
+
+    /// Index of the triples by each of their terms, which is built on first use
+    pub(crate) fn terms(&self) -> &TermIndex {
+        term_index(&self.terms, &self.triples)
+    }
+
     pub fn summary(&self) -> &Summary {
         &self.summary
     }
@@ -55,111 +190,409 @@ impl Database {
                 .triples
                 .iter()
                 .cloned()
-                .choose_multiple(&mut thread_rng(), n),
+                .choose_multiple(&mut StdRng::seed_from_u64(self.seed), n),
             summary: self.summary.clone(),
+            encoded: OnceLock::new(),
+            values: OnceLock::new(),
+            terms: OnceLock::new(),
+            statistics: false,
+            seed: self.seed,
         }
     }
 
-    pub fn build_statistics(&mut self, database_path: &Path) -> Result<()> {
+    /**
+     * Load the summary of this database from the summary file next to `database_path`, or compute
+     * it and write the file if there is none. A file written for other data, with another seed, by
+     * another version of the summary or that cannot be read is replaced.
+     */
+    pub fn build_statistics(&mut self, database_path: &Path) -> io::Result<()> {
         let mut summary_path = database_path.to_path_buf();
 
-        summary_path.set_extension("ron");
+        summary_path.set_extension("summary");
 
-        if summary_path.exists() {
-            log::info!("Reading database summary from file...");
-            let ron = fs::read_to_string(summary_path).expect("Unable to read file");
+        let hash = content_hash(database_path)?;
 
-            self.summary = ron::from_str(&ron)?;
-            log::info!("Done!");
-        } else {
-            log::info!("Building database statistics...");
+        match read_summary(&summary_path, hash, self.seed) {
+            Ok(summary) => {
+                log::info!("Read database summary from {}", summary_path.display());
 
-            self.summary = Summary::new();
+                self.summary = summary;
+                self.statistics = true;
+            }
+            Err(reason) => {
+                log::info!("Rebuilding database summary, {reason}");
 
-            for (state, triple) in self.triples.iter().progress() {
-                state.do_every_n_sec(1., |s| {
-                    log::info!("Building database statistics, {:.2} per sec.", s.rate());
-                });
+                self.compute_statistics();
+
+                let mut file = BufWriter::new(File::create(&summary_path)?);
 
-                self.summary.update(triple);
+                bincode::serialize_into(&mut file, &(SUMMARY_VERSION, hash))
+                    .and_then(|_| bincode::serialize_into(&mut file, &self.summary))
+                    .map_err(io::Error::other)?;
+
+                file.flush()?;
             }
+        }
 
-            log::info!("Computing joined triple pattern stats...");
+        Ok(())
+    }
+
+    /// Compute the summary of this database in memory, without reading or writing a file
+    pub(crate) fn compute_statistics(&mut self) {
+        log::info!("Building database statistics...");
 
-            self.build_joined_statistics();
+        self.summary = Summary::new();
+        self.summary.seed = self.seed;
+        self.statistics = true;
 
-            log::info!("Database statistics done, {} triples", self.triples.len());
+        for (state, triple) in self.triples.iter().progress() {
+            state.do_every_n_sec(1., |s| {
+                log::info!("Building database statistics, {:.2} per sec.", s.rate());
+            });
 
-            let ron = ron::to_string(&self.summary)?;
-            let mut file = File::create(summary_path)?;
-            writeln!(file, "{ron}")?;
+            self.summary.update(triple);
         }
 
-        Ok(())
+        log::info!("Computing histograms...");
+
+        self.build_histograms();
+
+        log::info!("Computing characteristic sets...");
+
+        self.build_characteristic_sets();
+
+        // The frequent predicate pairs are found in the characteristic sets
+        log::info!("Computing joint histograms...");
+
+        self.build_correlations();
+
+        log::info!("Computing joined triple pattern stats...");
+
+        self.build_joined_statistics();
+
+        log::info!("Database statistics done, {} triples", self.triples.len());
     }
 
-    fn build_joined_statistics(&mut self) {
-        let n = 10000.0_f64.max(self.triples.len() as f64 * 0.01).floor();
+    /// Histograms of the literal objects of each predicate, by whether they are numbers, dates or
+    /// other literals
+    fn build_histograms(&mut self) {
+        let mut numbers: HashMap<&Predicate, Vec<f64>> = HashMap::new();
+        let mut dates: HashMap<&Predicate, Vec<f64>> = HashMap::new();
+        let mut strings: HashMap<&Predicate, Prefixes> = HashMap::new();
+
+        for triple in self.triples.iter() {
+            let Object::L(literal) = &triple.object else {
+                continue;
+            };
+
+            if let Some(value) = literal.parsed {
+                numbers.entry(&triple.predicate).or_default().push(value);
+            } else if let Some(date) = literal.date() {
+                dates.entry(&triple.predicate).or_default().push(date);
+            } else {
+                strings
+                    .entry(&triple.predicate)
+                    .or_default()
+                    .insert(literal.lexical());
+            }
+        }
+
+        let histograms = |values: HashMap<&Predicate, Vec<f64>>| {
+            values
+                .into_iter()
+                .map(|(p, values)| (p.to_owned(), EquiDepth::new(values)))
+                .collect()
+        };
 
-        let sample = self.sample(n as usize);
+        self.summary.h_l = histograms(numbers);
+        self.summary.h_d = histograms(dates);
+        self.summary.h_s = strings
+            .into_iter()
+            .map(|(p, prefixes)| (p.to_owned(), prefixes))
+            .collect();
+    }
+
+    /**
+     * Joint histograms of the objects of the predicate pairs that most often occur on the same
+     * subject.
+     *
+     * The pairs are counted from the characteristic sets, so only the objects of the predicates of
+     * the chosen pairs are collected per subject.
+     */
+    fn build_correlations(&mut self) {
+        let mut pairs: HashMap<(&Predicate, &Predicate), usize> = HashMap::new();
+
+        for set in self.summary.c_s.iter() {
+            let sorted = set.occurrences.keys().sorted_by_key(|p| p.to_string());
+
+            for pair in sorted.tuple_combinations() {
+                *pairs.entry(pair).or_default() += set.distinct;
+            }
+        }
+
+        let frequent: Vec<(Predicate, Predicate)> = pairs
+            .into_iter()
+            .sorted_by_key(|((p1, p2), count)| (Reverse(*count), p1.to_string(), p2.to_string()))
+            .take(CORRELATED_PAIRS)
+            .map(|((p1, p2), _)| (p1.to_owned(), p2.to_owned()))
+            .collect();
+
+        let chosen: HashSet<&Predicate> = frequent.iter().flat_map(|(p1, p2)| [p1, p2]).collect();
+        let mut subjects: HashMap<&Subject, HashMap<&Predicate, Vec<Key>>> = HashMap::new();
+
+        for triple in self.triples.iter() {
+            if !chosen.contains(&triple.predicate) {
+                continue;
+            }
+
+            subjects
+                .entry(&triple.subject)
+                .or_default()
+                .entry(&triple.predicate)
+                .or_default()
+                .push(Key::from(&triple.object));
+        }
+
+        let mut axes: HashMap<&Predicate, Axis> = HashMap::new();
+
+        for p in chosen {
+            axes.entry(p).or_insert_with(|| {
+                let keys: Vec<Key> = subjects
+                    .values()
+                    .filter_map(|predicates| predicates.get(p))
+                    .flatten()
+                    .cloned()
+                    .collect();
+
+                Axis::new(&keys)
+            });
+        }
+
+        log::info!("Found {} frequent predicate pairs", frequent.len());
+
+        self.summary.j_c = frequent
+            .iter()
+            .map(|(p1, p2)| {
+                let mut joint = Joint::new(axes[p1].clone(), axes[p2].clone());
+
+                for predicates in subjects.values() {
+                    if let (Some(xs), Some(ys)) = (predicates.get(p1), predicates.get(p2)) {
+                        for (x, y) in xs.iter().cartesian_product(ys.iter()) {
+                            joint.insert(x, y);
+                        }
+                    }
+                }
+
+                ((p1.to_owned(), p2.to_owned()), joint)
+            })
+            .collect();
+    }
+
+    fn build_characteristic_sets(&mut self) {
+        let mut subjects: HashMap<&Subject, HashMap<&Predicate, usize>> = HashMap::new();
+
+        for triple in self.triples.iter() {
+            *subjects
+                .entry(&triple.subject)
+                .or_default()
+                .entry(&triple.predicate)
+                .or_default() += 1;
+        }
+
+        let mut sets: HashMap<Vec<String>, CharacteristicSet> = HashMap::new();
+
+        for predicates in subjects.into_values() {
+            let key = predicates
+                .keys()
+                .map(|p| p.to_string())
+                .sorted()
+                .collect::<Vec<String>>();
+
+            let set = sets.entry(key).or_insert_with(|| CharacteristicSet {
+                distinct: 0,
+                occurrences: HashMap::new(),
+            });
+
+            set.distinct += 1;
+
+            for (predicate, count) in predicates {
+                *set.occurrences.entry(predicate.to_owned()).or_default() += count;
+            }
+        }
+
+        log::info!("Found {} characteristic sets", sets.len());
+
+        self.summary.c_s = sets.into_values().collect();
+    }
+
+    fn build_joined_statistics(&mut self) {
+        let sample = self.sample(sample_size(self.triples.len()));
 
         log::info!(
-            "Found {} predicates, computing {} combinations",
+            "Found {} predicates, computing join statistics on {} sampled triples",
             self.summary.p.len(),
-            self.summary.p.len() * self.summary.p.len()
+            sample.triples.len()
         );
 
-        self.summary.s_p = self
-            .summary
-            .p
-            .iter()
-            .cartesian_product(self.summary.p.iter())
-            .progress()
-            .map(|(state, (p1, p2))| {
-                state.do_every_n_sec(1., |s| {
-                    log::info!(
-                        "{:.2}% done with join stats, {:.2} per sec.",
-                        s.percent().unwrap(),
-                        s.rate()
-                    );
-                });
+        // Triples join where they share a term, so each term adds the products of the numbers of
+        // its triples per predicate
+        let mut occurrences: HashMap<Object, Occurrences> = HashMap::new();
+
+        for triple in sample.triples.iter() {
+            *occurrences
+                .entry(Position::Subject.term(triple))
+                .or_default()
+                .subject
+                .entry(&triple.predicate)
+                .or_default() += 1;
+
+            *occurrences
+                .entry(triple.object.to_owned())
+                .or_default()
+                .object
+                .entry(&triple.predicate)
+                .or_default() += 1;
+        }
 
-                let query = Query {
-                    prologue: HashMap::new(),
-                    kind: SelectQuery(
-                        Variables::new(vec![Variable::new("?X".to_owned())]),
-                        Expression::And(
-                            Box::new(Expression::Triple(
-                                Box::new(query::Subject::V(Variable::new("?X".to_owned()))),
-                                Box::new(match p1 {
-                                    Predicate::I(i) => query::Predicate::I(i.clone()),
-                                }),
-                                Box::new(query::Object::V(Variable::new("?Y".to_owned()))),
-                            )),
-                            Box::new(Expression::Triple(
-                                Box::new(query::Subject::V(Variable::new("?X".to_owned()))),
-                                Box::new(match p2 {
-                                    Predicate::I(i) => query::Predicate::I(i.clone()),
-                                }),
-                                Box::new(query::Object::V(Variable::new("?Z".to_owned()))),
-                            )),
-                        ),
-                        query::SolutionModifier::default(),
-                    ),
-                };
-
-                let opts = EvalOptions::default()
-                    .with_log(false)
-                    .with_optimizer(Optimizer::Off);
-
-                let mut s_p = evaluate(&sample, query, Some(opts)).unwrap().size() as f64;
-
-                // Normalize by sample size
-                s_p *= self.triples.len() as f64 / n;
-
-                ((p1.to_owned(), p2.to_owned()), s_p.ceil() as usize)
-            })
-            .collect::<HashMap<(Predicate, Predicate), usize>>();
+        let terms: Vec<Occurrences> = occurrences.into_values().collect();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = terms.len().div_ceil(threads * 4).max(1);
+
+        let partials: Vec<[Joined; 3]> = dpc_pariter::scope(|scope| {
+            terms
+                .chunks(chunk_size)
+                .parallel_map_scoped(scope, |chunk| {
+                    let mut joined: [Joined; 3] = Default::default();
+
+                    for term in chunk {
+                        // ?X p1 ?Y . ?X p2 ?Z
+                        join(&term.subject, &term.subject, &mut joined[0]);
+                        // ?X p1 ?Y . ?Z p2 ?X
+                        join(&term.subject, &term.object, &mut joined[1]);
+                        // ?Y p1 ?X . ?Z p2 ?X
+                        join(&term.object, &term.object, &mut joined[2]);
+                    }
+
+                    joined
+                })
+                .threads(threads)
+                .collect()
+        })
+        .expect("Statistics worker thread panicked");
+
+        let mut joined: [Joined; 3] = Default::default();
+
+        for partial in partials {
+            for (total, partial) in joined.iter_mut().zip(partial) {
+                for (pair, count) in partial {
+                    *total.entry(pair).or_default() += count;
+                }
+            }
+        }
+
+        // Normalize by sample size
+        let scale = self.triples.len() as f64 / sample.triples.len().max(1) as f64;
+        let [s_p, s_o, o_o] = joined.map(|joined| {
+            joined
+                .into_iter()
+                .map(|((p1, p2), count)| ((p1.to_owned(), p2.to_owned()), count as f64 * scale))
+                .collect()
+        });
+
+        self.summary.s_p = s_p;
+        self.summary.s_o = s_o;
+        self.summary.o_o = o_o;
+    }
+}
+
+/// Number of sampled triples the join statistics of `triples` triples are counted on
+fn sample_size(triples: usize) -> usize {
+    10000.max(triples / 100)
+}
+
+/// The term index in `terms`, built from `triples` unless it was built before
+fn term_index<'t>(terms: &'t OnceLock<TermIndex>, triples: &[Triple]) -> &'t TermIndex {
+    terms.get_or_init(|| {
+        log::info!("Indexing the terms of {} triples...", triples.len());
+
+        triples.iter().collect()
+    })
+}
+
+/// Version of the summary file format, to be increased whenever `Summary` changes shape, so that
+/// summaries without the new statistics are rebuilt instead of read with them missing
+const SUMMARY_VERSION: u32 = 4;
+
+/// FNV-1a hash of the contents of a file
+fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = FnvHasher::default();
+
+    loop {
+        let buffer = reader.fill_buf()?;
+
+        if buffer.is_empty() {
+            return Ok(hasher.finish());
+        }
+
+        let length = buffer.len();
+
+        hasher.write(buffer);
+        reader.consume(length);
+    }
+}
+
+/// The summary in a summary file, if it was written for data with `hash` and built with `seed` by
+/// this version
+fn read_summary(path: &Path, hash: u64, seed: u64) -> std::result::Result<Summary, String> {
+    let file = File::open(path).map_err(|_| "no summary file".to_owned())?;
+    let mut reader = BufReader::new(file);
+
+    let (version, written): (u32, u64) = bincode::deserialize_from(&mut reader)
+        .map_err(|e| format!("unreadable summary file: {e}"))?;
+
+    if version != SUMMARY_VERSION {
+        return Err(format!(
+            "summary file has version {version} instead of {SUMMARY_VERSION}"
+        ));
+    }
+
+    if written != hash {
+        return Err("the data changed since the summary file was written".to_owned());
+    }
+
+    let summary: Summary = bincode::deserialize_from(&mut reader)
+        .map_err(|e| format!("unreadable summary file: {e}"))?;
+
+    if summary.seed != seed {
+        return Err(format!(
+            "summary file was built with seed {} instead of {seed}",
+            summary.seed
+        ));
+    }
+
+    Ok(summary)
+}
+
+/// Number of joined triples per pair of predicates
+type Joined<'p> = HashMap<(&'p Predicate, &'p Predicate), usize>;
+
+/// Number of triples of a term as a subject and as an object, by predicate
+#[derive(Default)]
+struct Occurrences<'p> {
+    subject: HashMap<&'p Predicate, usize>,
+    object: HashMap<&'p Predicate, usize>,
+}
+
+/// Add the pairs of triples joined on a term, with the term at the position of the numbers of
+/// triples `left` in the first triple and at the position of `right` in the second
+fn join<'p>(
+    left: &HashMap<&'p Predicate, usize>,
+    right: &HashMap<&'p Predicate, usize>,
+    joined: &mut Joined<'p>,
+) {
+    for ((p1, m), (p2, n)) in left.iter().cartesian_product(right.iter()) {
+        *joined.entry((*p1, *p2)).or_default() += m * n;
     }
 }
 
@@ -281,6 +714,17 @@ pub enum Object {
     I(Iri),
 }
 
+impl Object {
+    /// Number of bytes this object occupies on the heap
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            Object::B => 0,
+            Object::L(l) => l.heap_size(),
+            Object::I(i) => i.heap_size(),
+        }
+    }
+}
+
 impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -309,10 +753,45 @@ pub struct Summary {
     p: HashSet<Predicate>,
 
     /// Joined Triple Upper Bounds
-    s_p: HashMap<(Predicate, Predicate), usize>,
+    ///
+    /// Like the other joined triples, they are counted on a sample and scaled to all triples, so
+    /// they are not whole numbers once there are more triples than the sample holds.
+    s_p: HashMap<(Predicate, Predicate), f64>,
+
+    /// Joined triples where the subject of the first predicate is the object of the second
+    s_o: HashMap<(Predicate, Predicate), f64>,
+
+    /// Joined triples where both predicates share the object
+    o_o: HashMap<(Predicate, Predicate), f64>,
+
+    /// Equi-depth histograms of the numeric literal objects per predicate
+    h_l: HashMap<Predicate, EquiDepth>,
+
+    /// Equi-depth histograms of the date literal objects per predicate, in days since 1970-01-01
+    h_d: HashMap<Predicate, EquiDepth>,
+
+    /// Prefix histograms of the other literal objects per predicate
+    h_s: HashMap<Predicate, Prefixes>,
+
+    /// Joint histograms of the objects of frequently co-occurring predicates on the same subject,
+    /// by the predicates in the order of their names
+    j_c: HashMap<(Predicate, Predicate), Joint>,
 
-    /// Sparse Histograms for predicates with numeric values
-    p_l: HashMap<Predicate, HashHistogram<AxesTuple<(Uniform,)>, f64>>,
+    /// Characteristic sets, the combinations of predicates used by the same subject
+    c_s: Vec<CharacteristicSet>,
+
+    /// Seed of the random decisions while building the summary
+    seed: u64,
+}
+
+/// All subjects that use exactly the same set of predicates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacteristicSet {
+    /// Number of subjects with this set of predicates
+    distinct: usize,
+
+    /// Number of triples per predicate of this set
+    occurrences: HashMap<Predicate, usize>,
 }
 
 impl Summary {
@@ -350,53 +829,192 @@ impl Summary {
 
     pub fn s_p(&self, p1: Predicate, p2: Predicate) -> f64 {
         if let Some(val) = self.s_p.get(&(p1, p2)) {
-            *val as f64
+            *val
+        } else {
+            0.0
+        }
+    }
+
+    pub fn s_o(&self, p1: Predicate, p2: Predicate) -> f64 {
+        if let Some(val) = self.s_o.get(&(p1, p2)) {
+            *val
         } else {
             0.0
         }
     }
 
+    pub fn o_o(&self, p1: Predicate, p2: Predicate) -> f64 {
+        if let Some(val) = self.o_o.get(&(p1, p2)) {
+            *val
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of the triples with predicate `p` whose object is a number between `lower` and
+    /// `upper`, where `None` is unbounded
     pub fn p_l(&self, p: &Predicate, lower: Option<f64>, upper: Option<f64>) -> f64 {
+        self.fraction(p, self.h_l.get(p), lower, upper)
+    }
+
+    /// Fraction of the triples with predicate `p` whose object is a date between `lower` and
+    /// `upper` days since 1970-01-01, where `None` is unbounded
+    pub fn p_d(&self, p: &Predicate, lower: Option<f64>, upper: Option<f64>) -> f64 {
+        self.fraction(p, self.h_d.get(p), lower, upper)
+    }
+
+    /// Fraction of the triples with predicate `p` whose object is a literal that is neither a
+    /// number nor a date, and lies lexically between `lower` and `upper`
+    pub fn p_s(&self, p: &Predicate, lower: Option<&str>, upper: Option<&str>) -> f64 {
         if let (None, None) = (lower, upper) {
             return 1.0;
         }
 
-        if let Some(hist) = self.p_l.get(p) {
-            let has_value_in_histogram = hist.iter().any(|b| b.value > &0.0);
+        match self.h_s.get(p) {
+            Some(prefixes) if !prefixes.is_empty() => prefixes.count(lower, upper) / self.t_p(p),
+            _ => 1.0,
+        }
+    }
 
-            log::trace!("Lower: {:?}, Upper {:?}", lower, upper);
+    fn fraction(
+        &self,
+        p: &Predicate,
+        histogram: Option<&EquiDepth>,
+        lower: Option<f64>,
+        upper: Option<f64>,
+    ) -> f64 {
+        if let (None, None) = (lower, upper) {
+            return 1.0;
+        }
 
-            let factor = if has_value_in_histogram {
-                let inside_bounds = hist
-                    .iter()
-                    .filter(|b| match b.bin.end() {
-                        // The bin end must be larger than the lower bound
-                        Some(bin_end) => bin_end > lower.unwrap_or(-f64::INFINITY),
-                        // If there is no bin end, we are in the overflow bin which is always
-                        // considered larger than the lower bound
-                        None => true,
-                    })
-                    .filter(|b| match b.bin.start() {
-                        // The bin start must be smaller than the upper bound
-                        Some(bin_start) => bin_start < upper.unwrap_or(f64::INFINITY),
-                        // If there is no bin start, we are in the underflow bin which is always
-                        // considered smaller than the lower bound
-                        None => true,
-                    })
-                    .map(|b| b.value)
-                    .sum::<f64>();
-
-                inside_bounds / self.t_p(p)
-            } else {
-                1.0
-            };
+        log::trace!("Lower: {:?}, Upper {:?}", lower, upper);
+
+        let factor = match histogram {
+            Some(histogram) if !histogram.is_empty() => histogram.count(lower, upper) / self.t_p(p),
+            _ => 1.0,
+        };
 
-            log::trace!("Factor for predicate {p} {factor}");
+        log::trace!("Factor for predicate {p} {factor}");
 
-            return factor;
+        factor
+    }
+
+    /// Number of distinct objects of the triples with predicate `p`
+    pub fn d_p(&self, p: &Predicate) -> f64 {
+        self.o_c.get(p).map_or(0.0, |objects| objects.len() as f64)
+    }
+
+    /**
+     * Fraction of the pairs of objects of `p1` and `p2` on the same subject that satisfy `r1` and
+     * `r2` respectively, `None` without a joint histogram for the predicates.
+     */
+    pub(crate) fn p_j(
+        &self,
+        p1: &Predicate,
+        p2: &Predicate,
+        r1: &Restriction,
+        r2: &Restriction,
+    ) -> Option<f64> {
+        if let Some(joint) = self.j_c.get(&(p1.to_owned(), p2.to_owned())) {
+            return Some(joint.fraction(r1, r2));
         }
 
-        1.0
+        self.j_c
+            .get(&(p2.to_owned(), p1.to_owned()))
+            .map(|joint| joint.fraction(r2, r1))
+    }
+
+    /// Seed of the random decisions while building this summary
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The statistics of this summary, with the `top` most frequent objects and prefixes of each
+    /// predicate and the `top` largest nonzero numbers of joined triples
+    pub fn report(&self, top: usize) -> Report {
+        let mut predicates: Vec<PredicateReport> = self
+            .t_p
+            .iter()
+            .map(|(p, triples)| PredicateReport {
+                predicate: p.to_string(),
+                triples: *triples,
+                subjects: self.has_c_s().then(|| {
+                    self.c_s
+                        .iter()
+                        .filter(|set| set.occurrences.contains_key(p))
+                        .map(|set| set.distinct)
+                        .sum()
+                }),
+                objects: self.d_p(p) as usize,
+                top_objects: self
+                    .o_c
+                    .get(p)
+                    .into_iter()
+                    .flatten()
+                    .map(|(o, count)| (o.to_string(), *count))
+                    .sorted_by_key(|(o, count)| (Reverse(*count), o.to_owned()))
+                    .take(top)
+                    .collect(),
+                numbers: self.h_l.get(p).map(EquiDepth::report),
+                dates: self.h_d.get(p).map(EquiDepth::report),
+                prefixes: self.h_s.get(p).map_or(Vec::new(), |h| h.top(top)),
+            })
+            .collect();
+
+        predicates.sort_by_key(|p| (Reverse(p.triples), p.predicate.to_owned()));
+
+        let joins = [("S_P", &self.s_p), ("S_O", &self.s_o), ("O_O", &self.o_o)]
+            .into_iter()
+            .flat_map(|(kind, joined)| {
+                joined.iter().map(move |((p1, p2), triples)| JoinReport {
+                    kind: kind.to_owned(),
+                    left: p1.to_string(),
+                    right: p2.to_string(),
+                    triples: triples.round() as usize,
+                })
+            })
+            .filter(|j| j.triples > 0)
+            .sorted_by_key(|j| {
+                (
+                    Reverse(j.triples),
+                    j.kind.to_owned(),
+                    j.left.to_owned(),
+                    j.right.to_owned(),
+                )
+            })
+            .take(top)
+            .collect();
+
+        Report {
+            triples: self.t,
+            subjects: self.r.len(),
+            seed: self.seed,
+            predicates,
+            joins,
+        }
+    }
+
+    /// Whether characteristic sets were computed for this summary
+    pub fn has_c_s(&self) -> bool {
+        !self.c_s.is_empty()
+    }
+
+    /// Estimated number of results of a star of triple patterns with the given predicates on the
+    /// same subject, a predicate may occur more than once
+    pub fn c_s(&self, predicates: &[Predicate]) -> f64 {
+        self.c_s
+            .iter()
+            .filter(|set| predicates.iter().all(|p| set.occurrences.contains_key(p)))
+            .map(|set| {
+                let distinct = set.distinct as f64;
+
+                predicates
+                    .iter()
+                    .map(|p| set.occurrences[p] as f64 / distinct)
+                    .product::<f64>()
+                    * distinct
+            })
+            .sum()
     }
 
     pub fn new() -> Summary {
@@ -407,7 +1025,242 @@ impl Summary {
             t_p: HashMap::new(),
             o_c: HashMap::new(),
             s_p: HashMap::new(),
-            p_l: HashMap::new(),
+            s_o: HashMap::new(),
+            o_o: HashMap::new(),
+            h_l: HashMap::new(),
+            h_d: HashMap::new(),
+            h_s: HashMap::new(),
+            j_c: HashMap::new(),
+            c_s: Vec::new(),
+            seed: 0,
+        }
+    }
+
+    /// Account for `triple` being added to triples with `degrees`
+    pub(crate) fn insert(&mut self, triple: &Triple, degrees: &Degrees) {
+        self.update(triple);
+        self.change(triple, degrees, true);
+    }
+
+    /// Account for `triple` having been removed, leaving triples with `degrees`
+    pub(crate) fn remove(&mut self, triple: &Triple, degrees: &Degrees) {
+        self.t -= 1;
+
+        let subject = Position::Subject.term(triple);
+
+        if degrees.as_subject(&subject).is_empty() {
+            self.r.remove(&triple.subject);
+        }
+
+        if let Some(count) = self.t_p.get_mut(&triple.predicate) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.t_p.remove(&triple.predicate);
+                self.p.remove(&triple.predicate);
+            }
+        }
+
+        if let Some(objects) = self.o_c.get_mut(&triple.predicate) {
+            if let Some(count) = objects.get_mut(&triple.object) {
+                *count -= 1;
+
+                if *count == 0 {
+                    objects.remove(&triple.object);
+                }
+            }
+
+            if objects.is_empty() {
+                self.o_c.remove(&triple.predicate);
+            }
+        }
+
+        self.change(triple, degrees, false);
+    }
+
+    /**
+     * Update the histograms, characteristic sets and join statistics for `triple` being added to
+     * or removed from the triples with `degrees`. The triples that `triple` joins with are the same
+     * before adding and after removing it, so both are the same changes in opposite directions.
+     */
+    fn change(&mut self, triple: &Triple, degrees: &Degrees, insert: bool) {
+        let apply = |count: &mut f64, n: f64| {
+            *count = if insert {
+                *count + n
+            } else {
+                (*count - n).max(0.0)
+            }
+        };
+
+        let predicate = &triple.predicate;
+        let subject = Position::Subject.term(triple);
+        let siblings = degrees.as_subject(&subject);
+
+        // Histograms
+        if let Object::L(literal) = &triple.object {
+            if let Some(value) = literal.parsed {
+                let histogram = self.h_l.entry(predicate.to_owned()).or_default();
+
+                if insert {
+                    histogram.insert(value)
+                } else {
+                    histogram.remove(value)
+                }
+            } else if let Some(date) = literal.date() {
+                let histogram = self.h_d.entry(predicate.to_owned()).or_default();
+
+                if insert {
+                    histogram.insert(date)
+                } else {
+                    histogram.remove(date)
+                }
+            } else {
+                let prefixes = self.h_s.entry(predicate.to_owned()).or_default();
+
+                if insert {
+                    prefixes.insert(literal.lexical())
+                } else {
+                    prefixes.remove(literal.lexical())
+                }
+            }
+        }
+
+        // Joint histograms, with the objects of the other predicates of the subject
+        let object = Key::from(&triple.object);
+
+        for ((p1, p2), joint) in self.j_c.iter_mut() {
+            let pairs: Vec<(Key, Key)> = if p1 == predicate {
+                siblings
+                    .get(p2)
+                    .into_iter()
+                    .flatten()
+                    .map(|y| (object.clone(), Key::from(*y)))
+                    .collect()
+            } else if p2 == predicate {
+                siblings
+                    .get(p1)
+                    .into_iter()
+                    .flatten()
+                    .map(|x| (Key::from(*x), object.clone()))
+                    .collect()
+            } else {
+                continue;
+            };
+
+            for (x, y) in pairs.iter() {
+                if insert {
+                    joint.insert(x, y)
+                } else {
+                    joint.remove(x, y)
+                }
+            }
+        }
+
+        // Characteristic sets, the subject moves from the set without the triple to the one with it
+        let without: HashMap<Predicate, usize> = siblings
+            .iter()
+            .map(|(p, objects)| ((*p).to_owned(), objects.len()))
+            .collect();
+        let mut with = without.clone();
+        *with.entry(predicate.to_owned()).or_default() += 1;
+
+        let (from, to) = if insert {
+            (without, with)
+        } else {
+            (with, without)
+        };
+
+        self.move_subject(&from, false);
+        self.move_subject(&to, true);
+
+        // Joined triples, for each triple the added or removed one joins with and itself. Like
+        // the counts on the sample, pairs with other triples are scaled by the fraction of the
+        // triples in the sample, while each triple always joins with itself
+        let rate = sample_size(self.t).min(self.t.max(1)) as f64 / self.t.max(1) as f64;
+
+        let mut s_p = vec![((predicate, predicate), 1.0)];
+        let mut s_o = Vec::new();
+        let mut o_o = vec![((predicate, predicate), 1.0)];
+
+        for (p, objects) in siblings.iter() {
+            let n = objects.len() as f64 * rate;
+
+            s_p.push(((predicate, *p), n));
+            s_p.push(((*p, predicate), n));
+        }
+
+        for (p, count) in degrees.as_object(&subject) {
+            s_o.push(((predicate, p), count as f64 * rate));
+        }
+
+        for (p, objects) in degrees.as_subject(&triple.object) {
+            s_o.push(((p, predicate), objects.len() as f64 * rate));
+        }
+
+        if subject == triple.object {
+            s_o.push(((predicate, predicate), 1.0));
+        }
+
+        for (p, count) in degrees.as_object(&triple.object) {
+            o_o.push(((predicate, p), count as f64 * rate));
+            o_o.push(((p, predicate), count as f64 * rate));
+        }
+
+        for (joined, changes) in [
+            (&mut self.s_p, s_p),
+            (&mut self.s_o, s_o),
+            (&mut self.o_o, o_o),
+        ] {
+            for ((p1, p2), n) in changes {
+                apply(joined.entry((p1.to_owned(), p2.to_owned())).or_default(), n);
+            }
+        }
+    }
+
+    /// Add a subject with the given number of triples per predicate to its characteristic set, or
+    /// remove it from the set
+    fn move_subject(&mut self, predicates: &HashMap<Predicate, usize>, insert: bool) {
+        if predicates.is_empty() {
+            return;
+        }
+
+        let position = self.c_s.iter().position(|set| {
+            set.occurrences.len() == predicates.len()
+                && predicates.keys().all(|p| set.occurrences.contains_key(p))
+        });
+
+        let i = match (position, insert) {
+            (Some(i), _) => i,
+            (None, true) => {
+                self.c_s.push(CharacteristicSet {
+                    distinct: 0,
+                    occurrences: predicates.keys().map(|p| (p.to_owned(), 0)).collect(),
+                });
+                self.c_s.len() - 1
+            }
+            (None, false) => return,
+        };
+
+        let set = &mut self.c_s[i];
+
+        for (p, count) in predicates {
+            let occurrences = set.occurrences.entry(p.to_owned()).or_default();
+
+            *occurrences = if insert {
+                *occurrences + count
+            } else {
+                occurrences.saturating_sub(*count)
+            };
+        }
+
+        if insert {
+            set.distinct += 1;
+        } else {
+            set.distinct = set.distinct.saturating_sub(1);
+
+            if set.distinct == 0 {
+                self.c_s.remove(i);
+            }
         }
     }
 
@@ -441,22 +1294,6 @@ impl Summary {
                 HashMap::from([(triple.object.to_owned(), 1)]),
             );
         }
-
-        // Update p_l
-        if let Some(hist) = self.p_l.get_mut(&triple.predicate) {
-            if let Object::L(literal) = &triple.object {
-                if let Some(value) = literal.parsed {
-                    if !value.is_nan() {
-                        hist.fill(&value);
-                    }
-                }
-            }
-        } else {
-            self.p_l.insert(
-                triple.predicate.to_owned(),
-                sparsehistogram!(Uniform::new(200_000, -1_000_000.0, 1_000_000.0)),
-            );
-        }
     }
 }
 
@@ -480,14 +1317,15 @@ impl Display for Summary {
         f.write_str("\n")?;
 
         f.write_str(&format!("S_P: {}\n", self.s_p.len()))?;
-
-        f.write_str(&format!(
-            "P_L: {}\n",
-            self.p_l
-                .iter()
-                .filter(|(_, h)| h.values().next().is_some())
-                .count()
-        ))?;
+        f.write_str(&format!("S_O: {}\n", self.s_o.len()))?;
+        f.write_str(&format!("O_O: {}\n", self.o_o.len()))?;
+        f.write_str(&format!("C_S: {}\n", self.c_s.len()))?;
+
+        f.write_str(&format!("H_L: {}\n", self.h_l.len()))?;
+        f.write_str(&format!("H_D: {}\n", self.h_d.len()))?;
+        f.write_str(&format!("H_S: {}\n", self.h_s.len()))?;
+        f.write_str(&format!("J_C: {}\n", self.j_c.len()))?;
+        f.write_str(&format!("Seed: {}\n", self.seed))?;
 
         Ok(())
     }
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use super::{
+    database::{Object, Predicate, Triple},
+    terms::{Position, TermIndex},
+};
+
+/**
+ * The triples of each term as a subject and their number for each term as an object, by
+ * predicate, read from the term index of the triples. The summary uses them to work out what
+ * adding or removing a triple changes.
+ *
+ * Terms are looked up as they are bound to variables, so that a subject and an object can be
+ * compared.
+ */
+pub(crate) struct Degrees<'d> {
+    triples: &'d [Triple],
+    terms: &'d TermIndex,
+}
+
+impl<'d> Degrees<'d> {
+    pub(crate) fn new(triples: &'d [Triple], terms: &'d TermIndex) -> Self {
+        Self { triples, terms }
+    }
+
+    fn triples(&self, position: Position, term: &Object) -> impl Iterator<Item = &'d Triple> {
+        let triples = self.triples;
+
+        self.terms
+            .positions(position, term)
+            .iter()
+            .map(move |i| &triples[*i])
+    }
+
+    /// Objects of the triples with `term` as subject, by predicate
+    pub(crate) fn as_subject(&self, term: &Object) -> HashMap<&'d Predicate, Vec<&'d Object>> {
+        let mut predicates: HashMap<&Predicate, Vec<&Object>> = HashMap::new();
+
+        for triple in self.triples(Position::Subject, term) {
+            predicates
+                .entry(&triple.predicate)
+                .or_default()
+                .push(&triple.object);
+        }
+
+        predicates
+    }
+
+    /// Number of triples with `term` as object, by predicate
+    pub(crate) fn as_object(&self, term: &Object) -> HashMap<&'d Predicate, usize> {
+        let mut predicates: HashMap<&Predicate, usize> = HashMap::new();
+
+        for triple in self.triples(Position::Object, term) {
+            *predicates.entry(&triple.predicate).or_default() += 1;
+        }
+
+        predicates
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use super::database::{self, Object, Triple};
+
+/// Compact identifier of a term in a [`Dictionary`]
+pub(crate) type TermId = u32;
+
+/// Marker for a variable that is not bound in a row
+pub(crate) const UNBOUND: TermId = TermId::MAX;
+
+/// All blank nodes share the same term, which is always encoded first
+pub(crate) const BLANK: TermId = 0;
+
+/// Bidirectional mapping between the terms of a database and their identifiers
+#[derive(Debug, Clone)]
+pub(crate) struct Dictionary {
+    terms: Vec<Object>,
+    ids: HashMap<Object, TermId>,
+}
+
+impl Dictionary {
+    pub(crate) fn new() -> Self {
+        let mut dictionary = Self {
+            terms: Vec::new(),
+            ids: HashMap::new(),
+        };
+
+        dictionary.encode(Object::B);
+
+        dictionary
+    }
+
+    pub(crate) fn encode(&mut self, term: Object) -> TermId {
+        if let Some(id) = self.ids.get(&term) {
+            return *id;
+        }
+
+        let id: TermId = self
+            .terms
+            .len()
+            .try_into()
+            .expect("Too many distinct terms for the dictionary");
+
+        self.terms.push(term.clone());
+        self.ids.insert(term, id);
+
+        id
+    }
+
+    /// Identifier of a term, if the term occurs in the database
+    pub(crate) fn id(&self, term: &Object) -> Option<TermId> {
+        self.ids.get(term).copied()
+    }
+
+    pub(crate) fn term(&self, id: TermId) -> Option<&Object> {
+        self.terms.get(id as usize)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.terms.len()
+    }
+}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The triples of a database with subject, predicate and object replaced by term identifiers
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EncodedTriples {
+    pub(crate) dictionary: Dictionary,
+    pub(crate) triples: Vec<[TermId; 3]>,
+}
+
+impl EncodedTriples {
+    fn encode(dictionary: &mut Dictionary, triple: &Triple) -> [TermId; 3] {
+        [
+            dictionary.encode(match &triple.subject {
+                database::Subject::B => Object::B,
+                database::Subject::I(i) => Object::I(i.to_owned()),
+            }),
+            dictionary.encode(match &triple.predicate {
+                database::Predicate::I(i) => Object::I(i.to_owned()),
+            }),
+            dictionary.encode(triple.object.to_owned()),
+        ]
+    }
+
+    /// Encode `triple` after all others
+    pub(crate) fn push(&mut self, triple: &Triple) {
+        let encoded = Self::encode(&mut self.dictionary, triple);
+        self.triples.push(encoded);
+    }
+
+    /**
+     * Remove the triple at position `i` and move the last triple to its position, like
+     * `Vec::swap_remove`. Its terms stay in the dictionary.
+     */
+    pub(crate) fn swap_remove(&mut self, i: usize) {
+        self.triples.swap_remove(i);
+    }
+}
+
+impl<'a> FromIterator<&'a Triple> for EncodedTriples {
+    fn from_iter<T: IntoIterator<Item = &'a Triple>>(iter: T) -> Self {
+        let mut encoded = Self::default();
+
+        for triple in iter {
+            encoded.push(triple);
+        }
+
+        encoded
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    database::Object,
+    report::{BucketReport, HistogramReport},
+    Iri, Literal,
+};
+
+/// Most buckets an equi-depth histogram is divided into
+const MAX_BUCKETS: usize = 1024;
+
+/// Number of characters of the values a prefix histogram counts
+const PREFIX_LENGTH: usize = 2;
+
+/// Most buckets along each axis of a joint histogram
+const AXIS_BUCKETS: usize = 32;
+
+/// Values of an equi-depth histogram between two bounds, both included
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Bucket {
+    lower: f64,
+    upper: f64,
+    count: usize,
+    /// Number of distinct values
+    #[serde(default)]
+    distinct: usize,
+}
+
+impl Bucket {
+    /// Fraction of the values between `lower` and `upper`, where `None` is unbounded
+    fn overlap(&self, lower: Option<f64>, upper: Option<f64>) -> f64 {
+        let lower = lower.unwrap_or(f64::NEG_INFINITY);
+        let upper = upper.unwrap_or(f64::INFINITY);
+
+        if self.upper < lower || self.lower > upper {
+            return 0.0;
+        }
+
+        let width = self.upper - self.lower;
+
+        if width > 0.0 {
+            ((upper.min(self.upper) - lower.max(self.lower)) / width).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Buckets of about the same number of values, about `sqrt(n)` of them for `n` values
+fn equi_depth(mut values: Vec<f64>, max: usize) -> Vec<Bucket> {
+    values.retain(|v| !v.is_nan());
+    values.sort_by(f64::total_cmp);
+
+    let buckets = ((values.len() as f64).sqrt().ceil() as usize).clamp(1, max);
+    let depth = values.len().div_ceil(buckets).max(1);
+
+    values
+        .chunks(depth)
+        .map(|chunk| Bucket {
+            lower: chunk[0],
+            upper: chunk[chunk.len() - 1],
+            count: chunk.len(),
+            distinct: chunk.iter().dedup().count(),
+        })
+        .collect()
+}
+
+/**
+ * Histogram whose buckets hold about the same number of values each, so that dense ranges of a
+ * skewed distribution get narrow buckets.
+ *
+ * A predicate with `n` values gets about `sqrt(n)` buckets. Values within a bucket are assumed to
+ * be spread evenly between its lowest and its highest value.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct EquiDepth {
+    buckets: Vec<Bucket>,
+}
+
+impl EquiDepth {
+    pub(crate) fn new(values: Vec<f64>) -> Self {
+        Self {
+            buckets: equi_depth(values, MAX_BUCKETS),
+        }
+    }
+
+    /// Estimated number of values between `lower` and `upper`, where `None` is unbounded
+    pub(crate) fn count(&self, lower: Option<f64>, upper: Option<f64>) -> f64 {
+        self.buckets
+            .iter()
+            .map(|b| b.count as f64 * b.overlap(lower, upper))
+            .sum()
+    }
+
+    /**
+     * Add a value to the bucket it falls into, widening the nearest bucket for values between or
+     * beyond the buckets. The buckets are not rebalanced, so a histogram that changed much should
+     * be built anew.
+     */
+    pub(crate) fn insert(&mut self, x: f64) {
+        if x.is_nan() {
+            return;
+        }
+
+        let i = self
+            .buckets
+            .partition_point(|b| b.upper < x)
+            .min(self.buckets.len().saturating_sub(1));
+
+        let Some(bucket) = self.buckets.get_mut(i) else {
+            self.buckets.push(Bucket {
+                lower: x,
+                upper: x,
+                count: 1,
+                distinct: 1,
+            });
+            return;
+        };
+
+        if x < bucket.lower || x > bucket.upper {
+            bucket.lower = bucket.lower.min(x);
+            bucket.upper = bucket.upper.max(x);
+            bucket.distinct += 1;
+        }
+
+        bucket.count += 1;
+    }
+
+    /// Remove a value from the bucket it falls into, and the bucket once it is empty
+    pub(crate) fn remove(&mut self, x: f64) {
+        let Some(i) = self
+            .buckets
+            .iter()
+            .position(|b| b.lower <= x && x <= b.upper)
+        else {
+            return;
+        };
+
+        let bucket = &mut self.buckets[i];
+        bucket.count -= 1;
+        bucket.distinct = bucket.distinct.min(bucket.count);
+
+        if bucket.count == 0 {
+            self.buckets.remove(i);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    pub(crate) fn report(&self) -> HistogramReport {
+        HistogramReport {
+            values: self.buckets.iter().map(|b| b.count).sum(),
+            buckets: self
+                .buckets
+                .iter()
+                .map(|b| BucketReport {
+                    lower: b.lower,
+                    upper: b.upper,
+                    count: b.count,
+                    distinct: b.distinct,
+                })
+                .collect(),
+        }
+    }
+}
+
+/**
+ * Number of values per prefix of their first characters, for literals that are neither numbers
+ * nor dates and are compared lexically.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Prefixes {
+    counts: BTreeMap<String, usize>,
+}
+
+fn prefix(value: &str) -> String {
+    value.chars().take(PREFIX_LENGTH).collect()
+}
+
+impl Prefixes {
+    pub(crate) fn insert(&mut self, value: &str) {
+        *self.counts.entry(prefix(value)).or_default() += 1;
+    }
+
+    pub(crate) fn remove(&mut self, value: &str) {
+        let prefix = prefix(value);
+
+        if let Some(count) = self.counts.get_mut(&prefix) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.counts.remove(&prefix);
+            }
+        }
+    }
+
+    /**
+     * Estimated number of values between `lower` and `upper`, where `None` is unbounded.
+     *
+     * The values with the same prefix as a bound are assumed to lie on either side of it equally.
+     */
+    pub(crate) fn count(&self, lower: Option<&str>, upper: Option<&str>) -> f64 {
+        let lower = lower.map(prefix);
+        let upper = upper.map(prefix);
+
+        self.counts
+            .iter()
+            .filter(|(p, _)| lower.as_ref().is_none_or(|l| *p >= l))
+            .filter(|(p, _)| upper.as_ref().is_none_or(|u| *p <= u))
+            .map(|(p, count)| {
+                let boundaries = [&lower, &upper]
+                    .iter()
+                    .filter(|b| b.as_ref() == Some(p))
+                    .count();
+
+                *count as f64 / 2.0_f64.powi(boundaries as i32)
+            })
+            .sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The `k` prefixes of the most values with their number of values
+    pub(crate) fn top(&self, k: usize) -> Vec<(String, usize)> {
+        self.counts
+            .iter()
+            .map(|(prefix, count)| (prefix.to_owned(), *count))
+            .sorted_by_key(|(prefix, count)| (Reverse(*count), prefix.to_owned()))
+            .take(k)
+            .collect()
+    }
+}
+
+/// A value as joint histograms see it: numbers and dates are ordered, other values only compared
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Key {
+    /// The number, or the days since 1970-01-01 of a date
+    number: Option<f64>,
+    lexical: String,
+}
+
+impl From<&Literal> for Key {
+    fn from(l: &Literal) -> Self {
+        Self {
+            number: l.parsed.or_else(|| l.date()),
+            lexical: l.lexical().to_owned(),
+        }
+    }
+}
+
+impl From<&Iri> for Key {
+    fn from(i: &Iri) -> Self {
+        Self {
+            number: None,
+            lexical: i.to_string(),
+        }
+    }
+}
+
+impl From<&Object> for Key {
+    fn from(o: &Object) -> Self {
+        match o {
+            Object::L(l) => l.into(),
+            Object::I(i) => i.into(),
+            Object::B => Self {
+                number: None,
+                lexical: o.to_string(),
+            },
+        }
+    }
+}
+
+/// Values of an object that satisfy the filters on it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Restriction {
+    Any,
+    /// Numbers or dates between two bounds, where `None` is unbounded
+    Range(Option<f64>, Option<f64>),
+    Equals(Key),
+}
+
+/// How a joint histogram divides the values of one predicate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum Axis {
+    /// Equi-depth buckets, if all values are numbers or dates
+    Ranges(Vec<Bucket>),
+    /// The most frequent values, and a last bucket for the `others` distinct values
+    Values { values: Vec<String>, others: usize },
+}
+
+impl Axis {
+    pub(crate) fn new(keys: &[Key]) -> Self {
+        if let Some(numbers) = keys.iter().map(|k| k.number).collect::<Option<Vec<_>>>() {
+            return Axis::Ranges(equi_depth(numbers, AXIS_BUCKETS));
+        }
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+
+        for key in keys {
+            *counts.entry(&key.lexical).or_default() += 1;
+        }
+
+        let mut values: Vec<_> = counts.into_iter().collect();
+        values.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+
+        let frequent = values.len().min(AXIS_BUCKETS - 1);
+
+        Axis::Values {
+            values: values[..frequent]
+                .iter()
+                .map(|(v, _)| v.to_string())
+                .collect(),
+            others: values.len() - frequent,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Axis::Ranges(buckets) => buckets.len(),
+            Axis::Values { values, .. } => values.len() + 1,
+        }
+    }
+
+    /// Bucket of `key`, `None` for a value of another kind than the axis divides
+    fn bucket(&self, key: &Key) -> Option<usize> {
+        match self {
+            Axis::Ranges(buckets) => {
+                let x = key.number?;
+                let i = buckets.partition_point(|b| b.upper < x);
+
+                Some(i.min(buckets.len().checked_sub(1)?))
+            }
+            Axis::Values { values, .. } => Some(
+                values
+                    .iter()
+                    .position(|v| *v == key.lexical)
+                    .unwrap_or(values.len()),
+            ),
+        }
+    }
+
+    /// Fraction of the values in bucket `i` that satisfy `restriction`
+    fn fraction(&self, i: usize, restriction: &Restriction) -> f64 {
+        match (self, restriction) {
+            (_, Restriction::Any) => 1.0,
+            (Axis::Ranges(buckets), Restriction::Range(lower, upper)) => {
+                buckets[i].overlap(*lower, *upper)
+            }
+            (Axis::Ranges(buckets), Restriction::Equals(key)) => match key.number {
+                Some(x) if buckets[i].overlap(Some(x), Some(x)) > 0.0 => {
+                    1.0 / buckets[i].distinct.max(1) as f64
+                }
+                _ => 0.0,
+            },
+            // Ranges of values that are not all numbers are not tracked
+            (Axis::Values { .. }, Restriction::Range(..)) => 1.0,
+            (Axis::Values { values, others }, Restriction::Equals(key)) => match values.get(i) {
+                Some(value) => (*value == key.lexical) as usize as f64,
+                None if values.contains(&key.lexical) => 0.0,
+                None => 1.0 / (*others).max(1) as f64,
+            },
+        }
+    }
+}
+
+/**
+ * Histogram of the pairs of objects of two predicates on the same subject, so that filters on
+ * both objects of a star are estimated together when their values are correlated.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Joint {
+    x: Axis,
+    y: Axis,
+    /// Number of pairs per combination of buckets, row by row along `x`
+    counts: Vec<usize>,
+    total: usize,
+}
+
+impl Joint {
+    pub(crate) fn new(x: Axis, y: Axis) -> Self {
+        Self {
+            counts: vec![0; x.len() * y.len()],
+            x,
+            y,
+            total: 0,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, x: &Key, y: &Key) {
+        if let (Some(i), Some(j)) = (self.x.bucket(x), self.y.bucket(y)) {
+            self.counts[i * self.y.len() + j] += 1;
+            self.total += 1;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, x: &Key, y: &Key) {
+        if let (Some(i), Some(j)) = (self.x.bucket(x), self.y.bucket(y)) {
+            let count = &mut self.counts[i * self.y.len() + j];
+
+            if *count > 0 {
+                *count -= 1;
+                self.total -= 1;
+            }
+        }
+    }
+
+    /// Fraction of the pairs whose objects satisfy `x` and `y` respectively
+    pub(crate) fn fraction(&self, x: &Restriction, y: &Restriction) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+
+        let matching: f64 = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(cell, count)| {
+                let (i, j) = (cell / self.y.len(), cell % self.y.len());
+
+                *count as f64 * self.x.fraction(i, x) * self.y.fraction(j, y)
+            })
+            .sum();
+
+        matching / self.total as f64
+    }
+}
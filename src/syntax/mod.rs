@@ -2,8 +2,14 @@ use serde::{Deserialize, Serialize};
 use std::{fmt::Display, hash::Hash};
 
 pub mod database;
+pub(crate) mod degrees;
+pub(crate) mod dictionary;
 pub(crate) mod expand;
+pub(crate) mod histogram;
 pub mod query;
+pub mod report;
+pub(crate) mod terms;
+pub(crate) mod values;
 
 #[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub enum Iri {
@@ -37,6 +43,29 @@ impl Iri {
     }
 }
 
+impl Iri {
+    /// The IRI as it is written in full, which is the same for a prefixed name and the IRI it
+    /// expands to
+    pub(crate) fn normalized(&self) -> String {
+        match self {
+            Iri::IRIREF(iri) => iri.to_owned(),
+            Iri::PrefixedName(name) => name.expanded.clone().unwrap_or_else(|| self.to_string()),
+        }
+    }
+
+    /// Number of bytes this IRI occupies on the heap
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            Iri::IRIREF(iri) => iri.capacity(),
+            Iri::PrefixedName(name) => {
+                name.ns.capacity()
+                    + name.local.capacity()
+                    + name.expanded.as_ref().map_or(0, |e| e.capacity())
+            }
+        }
+    }
+}
+
 impl From<String> for Iri {
     fn from(s: String) -> Self {
         Iri::new(s)
@@ -149,6 +178,70 @@ impl Literal {
     }
 }
 
+impl Literal {
+    /// Value of the literal without quotes, datatype and language
+    pub(crate) fn lexical(&self) -> &str {
+        self.value.trim_matches('"')
+    }
+
+    /**
+     * Days since 1970-01-01 of a date or date and time literal like `2001-05-03` or
+     * `2001-05-03T12:00:00`, `None` for numbers and other literals
+     */
+    pub(crate) fn date(&self) -> Option<f64> {
+        if self.parsed.is_some() {
+            return None;
+        }
+
+        let (date, time) = match self.lexical().split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (self.lexical(), None),
+        };
+
+        let mut parts = date.rsplitn(3, '-');
+        let (day, month) = (parts.next()?, parts.next()?);
+        let year: i64 = parts.next()?.parse().ok()?;
+
+        if day.len() != 2 || month.len() != 2 {
+            return None;
+        }
+
+        let (day, month): (i64, i64) = (day.parse().ok()?, month.parse().ok()?);
+
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        // Days from the civil calendar, counting years from March so that leap days come last
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let fraction = time
+            .and_then(|t| {
+                let mut parts = t.get(..8)?.split(':');
+                let hours: f64 = parts.next()?.parse().ok()?;
+                let minutes: f64 = parts.next()?.parse().ok()?;
+                let seconds: f64 = parts.next()?.parse().ok()?;
+
+                Some((hours * 3600.0 + minutes * 60.0 + seconds) / 86_400.0)
+            })
+            .unwrap_or_default();
+
+        Some(days as f64 + fraction)
+    }
+
+    /// Number of bytes this literal occupies on the heap
+    pub(crate) fn heap_size(&self) -> usize {
+        self.value.capacity()
+            + self.datatype.as_ref().map_or(0, |d| d.capacity())
+            + self.language.as_ref().map_or(0, |l| l.capacity())
+    }
+}
+
 impl Eq for Literal {}
 impl PartialEq for Literal {
     fn eq(&self, other: &Self) -> bool {
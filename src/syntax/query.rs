@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::syntax::Iri;
 use std::{
@@ -37,6 +38,10 @@ impl Display for Query {
 
                 f.write_str("\n}\n")?;
 
+                if !modifier.order.is_empty() {
+                    f.write_str(&format!("ORDER BY {}\n", modifier.order.iter().join(" ")))?;
+                }
+
                 if let Some(limit) = modifier.limit {
                     f.write_str(&format!("LIMIT {limit}\n"))?;
                 }
@@ -82,11 +87,16 @@ pub(crate) enum Type {
 
 #[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub(crate) struct SolutionModifier {
+    pub(crate) order: Vec<OrderCondition>,
     pub(crate) limit: Option<usize>,
     pub(crate) offset: Option<usize>,
 }
 
 impl SolutionModifier {
+    pub(crate) fn with_order(&mut self, condition: OrderCondition) {
+        self.order.push(condition);
+    }
+
     pub(crate) fn with_limit(&mut self, limit: usize) {
         self.limit = Some(limit);
     }
@@ -96,7 +106,33 @@ impl SolutionModifier {
     }
 }
 
+/// A variable to order solutions by, in ascending order unless `descending`
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub(crate) struct OrderCondition {
+    pub(crate) variable: Variable,
+    pub(crate) descending: bool,
+}
+
+impl OrderCondition {
+    pub(crate) fn new(variable: Variable, descending: bool) -> Self {
+        Self {
+            variable,
+            descending,
+        }
+    }
+}
+
+impl Display for OrderCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.descending {
+            f.write_str(&format!("DESC({})", self.variable.name))
+        } else {
+            f.write_str(&self.variable.name)
+        }
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub(crate) struct Variable {
     pub(crate) name: String,
     position: Option<usize>,
@@ -0,0 +1,125 @@
+//! The report module describes the statistics of a database summary in a form that can be read or
+//! exported as JSON, to explain the estimates the estimators make on a dataset.
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// Statistics of a database summary, with the most frequent values of each kind
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub triples: usize,
+    pub subjects: usize,
+    pub seed: u64,
+    /// Statistics of each predicate, the ones with the most triples first
+    pub predicates: Vec<PredicateReport>,
+    /// The largest numbers of joined triples, the largest first
+    pub joins: Vec<JoinReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PredicateReport {
+    pub predicate: String,
+    pub triples: usize,
+    /// Number of distinct subjects, `None` without characteristic sets
+    pub subjects: Option<usize>,
+    pub objects: usize,
+    /// The most frequent objects with their number of triples
+    pub top_objects: Vec<(String, usize)>,
+    /// Histogram of the numeric objects
+    pub numbers: Option<HistogramReport>,
+    /// Histogram of the date objects, in days since 1970-01-01
+    pub dates: Option<HistogramReport>,
+    /// The most frequent prefixes of the other literal objects with their number of triples
+    pub prefixes: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramReport {
+    pub values: usize,
+    pub buckets: Vec<BucketReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketReport {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub distinct: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JoinReport {
+    /// `S_P` for joins on the subjects, `S_O` of the subject of the first with the object of the
+    /// second and `O_O` on the objects
+    pub kind: String,
+    pub left: String,
+    pub right: String,
+    pub triples: usize,
+}
+
+fn pairs(pairs: &[(String, usize)]) -> String {
+    pairs
+        .iter()
+        .map(|(value, count)| format!("{value} ({count})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Display for HistogramReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.buckets.first(), self.buckets.last()) {
+            (Some(first), Some(last)) => f.write_str(&format!(
+                "{} values in {} buckets from {} to {}",
+                self.values,
+                self.buckets.len(),
+                first.lower,
+                last.upper
+            )),
+            _ => f.write_str("no values"),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "Triples: {}, subjects: {}, seed: {}\n",
+            self.triples, self.subjects, self.seed
+        ))?;
+
+        for p in self.predicates.iter() {
+            f.write_str(&format!(
+                "\n{}: {} triples, {} subjects, {} objects\n",
+                p.predicate,
+                p.triples,
+                p.subjects.map_or("unknown".to_owned(), |s| s.to_string()),
+                p.objects
+            ))?;
+            f.write_str(&format!("  Top objects: {}\n", pairs(&p.top_objects)))?;
+
+            if let Some(numbers) = &p.numbers {
+                f.write_str(&format!("  Numbers: {numbers}\n"))?;
+            }
+
+            if let Some(dates) = &p.dates {
+                f.write_str(&format!("  Dates: {dates}\n"))?;
+            }
+
+            if !p.prefixes.is_empty() {
+                f.write_str(&format!("  Prefixes: {}\n", pairs(&p.prefixes)))?;
+            }
+        }
+
+        f.write_str("\nJoins:\n")?;
+
+        for j in self.joins.iter() {
+            f.write_str(&format!(
+                "  {} {} {}: {}\n",
+                j.kind, j.left, j.right, j.triples
+            ))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use super::database::{Object, Predicate, Subject, Triple};
+
+/// Position of a term in a triple
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Position {
+    Subject,
+    Predicate,
+    Object,
+}
+
+impl Position {
+    /// The term of a triple at this position, as it is bound to a variable
+    pub(crate) fn term(&self, triple: &Triple) -> Object {
+        match self {
+            Position::Subject => match &triple.subject {
+                Subject::B => Object::B,
+                Subject::I(i) => Object::I(i.to_owned()),
+            },
+            Position::Predicate => match &triple.predicate {
+                Predicate::I(i) => Object::I(i.to_owned()),
+            },
+            Position::Object => triple.object.to_owned(),
+        }
+    }
+}
+
+/// Positions of the triples with a given term, for each position of the term in a triple
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TermIndex {
+    subjects: HashMap<Object, Vec<usize>>,
+    predicates: HashMap<Object, Vec<usize>>,
+    objects: HashMap<Object, Vec<usize>>,
+}
+
+impl TermIndex {
+    /// Positions of the triples with `term` at `position`, in ascending order
+    pub(crate) fn positions(&self, position: Position, term: &Object) -> &[usize] {
+        let terms = match position {
+            Position::Subject => &self.subjects,
+            Position::Predicate => &self.predicates,
+            Position::Object => &self.objects,
+        };
+
+        terms.get(term).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn terms(&mut self) -> [(Position, &mut HashMap<Object, Vec<usize>>); 3] {
+        [
+            (Position::Subject, &mut self.subjects),
+            (Position::Predicate, &mut self.predicates),
+            (Position::Object, &mut self.objects),
+        ]
+    }
+
+    /// Index `triple` at position `i`, which follows all positions in the index
+    pub(crate) fn insert(&mut self, triple: &Triple, i: usize) {
+        for (position, terms) in self.terms() {
+            terms.entry(position.term(triple)).or_default().push(i);
+        }
+    }
+
+    /// Remove `triple` at position `i` from the index
+    pub(crate) fn remove(&mut self, triple: &Triple, i: usize) {
+        for (position, terms) in self.terms() {
+            let term = position.term(triple);
+
+            if let Some(positions) = terms.get_mut(&term) {
+                if let Ok(j) = positions.binary_search(&i) {
+                    positions.remove(j);
+                }
+
+                if positions.is_empty() {
+                    terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Index `triple` at position `to` instead of `from`
+    pub(crate) fn relocate(&mut self, triple: &Triple, from: usize, to: usize) {
+        for (position, terms) in self.terms() {
+            if let Some(positions) = terms.get_mut(&position.term(triple)) {
+                if let Ok(j) = positions.binary_search(&from) {
+                    positions.remove(j);
+                }
+
+                if let Err(j) = positions.binary_search(&to) {
+                    positions.insert(j, to);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a Triple> for TermIndex {
+    fn from_iter<T: IntoIterator<Item = &'a Triple>>(iter: T) -> Self {
+        let mut index = Self::default();
+
+        for (i, triple) in iter.into_iter().enumerate() {
+            index.insert(triple, i);
+        }
+
+        index
+    }
+}
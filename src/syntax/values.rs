@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    ops::Bound,
+};
+
+use super::database::{Object, Predicate, Triple};
+
+/// Interval of numeric literal values, with open or closed ends
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ValueRange {
+    pub(crate) lower: Bound<f64>,
+    pub(crate) upper: Bound<f64>,
+}
+
+impl ValueRange {
+    pub(crate) fn new() -> Self {
+        Self {
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+        }
+    }
+
+    /// Narrow the range to the values above `lower`
+    pub(crate) fn above(self, lower: Bound<f64>) -> Self {
+        Self {
+            lower: tighter(self.lower, lower, |a, b| a > b),
+            ..self
+        }
+    }
+
+    /// Narrow the range to the values below `upper`
+    pub(crate) fn below(self, upper: Bound<f64>) -> Self {
+        Self {
+            upper: tighter(self.upper, upper, |a, b| a < b),
+            ..self
+        }
+    }
+
+    pub(crate) fn contains(&self, value: f64) -> bool {
+        let lower = match self.lower {
+            Bound::Included(l) => value >= l,
+            Bound::Excluded(l) => value > l,
+            Bound::Unbounded => !value.is_nan(),
+        };
+
+        let upper = match self.upper {
+            Bound::Included(u) => value <= u,
+            Bound::Excluded(u) => value < u,
+            Bound::Unbounded => !value.is_nan(),
+        };
+
+        lower && upper
+    }
+
+    /// Whether an object is a numeric literal within the range
+    pub(crate) fn matches(&self, object: &Object) -> bool {
+        match object {
+            Object::L(l) => l.parsed.is_some_and(|v| self.contains(v)),
+            _ => false,
+        }
+    }
+}
+
+/// The more restrictive of two bounds, where `inner` tells whether a value lies inside another
+fn tighter(a: Bound<f64>, b: Bound<f64>, inner: fn(f64, f64) -> bool) -> Bound<f64> {
+    match (a, b) {
+        (Bound::Unbounded, b) => b,
+        (a, Bound::Unbounded) => a,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            if inner(x, y) || (x == y && matches!(a, Bound::Excluded(_))) {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+impl Default for ValueRange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Eq for ValueRange {}
+impl PartialEq for ValueRange {
+    fn eq(&self, other: &Self) -> bool {
+        let bits = |b: Bound<f64>| b.map(f64::to_bits);
+
+        bits(self.lower) == bits(other.lower) && bits(self.upper) == bits(other.upper)
+    }
+}
+
+impl Hash for ValueRange {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lower.map(f64::to_bits).hash(state);
+        self.upper.map(f64::to_bits).hash(state);
+    }
+}
+
+impl Display for ValueRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.lower {
+            Bound::Included(l) => f.write_str(&format!("[{l}, "))?,
+            Bound::Excluded(l) => f.write_str(&format!("({l}, "))?,
+            Bound::Unbounded => f.write_str("(-inf, ")?,
+        }
+
+        match self.upper {
+            Bound::Included(u) => f.write_str(&format!("{u}]")),
+            Bound::Excluded(u) => f.write_str(&format!("{u})")),
+            Bound::Unbounded => f.write_str("inf)"),
+        }
+    }
+}
+
+/// Positions of the triples with a numeric literal object, sorted by the value of the object
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ValueIndex {
+    predicates: HashMap<Predicate, Vec<(f64, usize)>>,
+    all: Vec<(f64, usize)>,
+}
+
+impl ValueIndex {
+    /**
+     * Positions of the triples with predicate `predicate`, or any predicate if it is `None`, whose
+     * object lies within `range`
+     *
+     * The positions are in ascending order, so that they are visited in the order of a full scan.
+     */
+    pub(crate) fn positions(
+        &self,
+        predicate: Option<&Predicate>,
+        range: &ValueRange,
+    ) -> Vec<usize> {
+        let values = match predicate {
+            Some(p) => match self.predicates.get(p) {
+                Some(values) => values,
+                None => return Vec::new(),
+            },
+            None => &self.all,
+        };
+
+        let start = match range.lower {
+            Bound::Included(l) => values.partition_point(|(v, _)| *v < l),
+            Bound::Excluded(l) => values.partition_point(|(v, _)| *v <= l),
+            Bound::Unbounded => 0,
+        };
+
+        let end = match range.upper {
+            Bound::Included(u) => values.partition_point(|(v, _)| *v <= u),
+            Bound::Excluded(u) => values.partition_point(|(v, _)| *v < u),
+            Bound::Unbounded => values.len(),
+        };
+
+        let mut positions: Vec<usize> = values[start..end.max(start)]
+            .iter()
+            .map(|(_, i)| *i)
+            .collect();
+        positions.sort_unstable();
+
+        positions
+    }
+}
+
+impl ValueIndex {
+    /// Value of the object of `triple` in the index, if it is a number
+    fn value(triple: &Triple) -> Option<f64> {
+        match &triple.object {
+            Object::L(l) => l.parsed.filter(|v| !v.is_nan()),
+            _ => None,
+        }
+    }
+
+    /// Index `triple` at position `i`
+    pub(crate) fn insert(&mut self, triple: &Triple, i: usize) {
+        let Some(value) = Self::value(triple) else {
+            return;
+        };
+
+        let predicate = self.predicates.entry(triple.predicate.clone()).or_default();
+
+        for values in [predicate, &mut self.all] {
+            let j = values.partition_point(|(v, _)| v.total_cmp(&value).is_le());
+            values.insert(j, (value, i));
+        }
+    }
+
+    /// Remove `triple` at position `i` from the index
+    pub(crate) fn remove(&mut self, triple: &Triple, i: usize) {
+        let Some(value) = Self::value(triple) else {
+            return;
+        };
+
+        if let Some(values) = self.predicates.get_mut(&triple.predicate) {
+            if let Some(j) = find(values, value, i) {
+                values.remove(j);
+            }
+
+            if values.is_empty() {
+                self.predicates.remove(&triple.predicate);
+            }
+        }
+
+        if let Some(j) = find(&self.all, value, i) {
+            self.all.remove(j);
+        }
+    }
+
+    /// Index `triple` at position `to` instead of `from`
+    pub(crate) fn relocate(&mut self, triple: &Triple, from: usize, to: usize) {
+        let Some(value) = Self::value(triple) else {
+            return;
+        };
+
+        let predicate = self.predicates.get_mut(&triple.predicate);
+
+        for values in predicate.into_iter().chain([&mut self.all]) {
+            if let Some(j) = find(values, value, from) {
+                values[j].1 = to;
+            }
+        }
+    }
+}
+
+/// Index of the entry for `value` at position `i` among `values` sorted by value
+fn find(values: &[(f64, usize)], value: f64, i: usize) -> Option<usize> {
+    let start = values.partition_point(|(v, _)| v.total_cmp(&value).is_lt());
+
+    values[start..]
+        .iter()
+        .take_while(|(v, _)| v.total_cmp(&value).is_eq())
+        .position(|(_, j)| *j == i)
+        .map(|j| start + j)
+}
+
+impl<'a> FromIterator<&'a Triple> for ValueIndex {
+    fn from_iter<T: IntoIterator<Item = &'a Triple>>(iter: T) -> Self {
+        let mut index = Self::default();
+
+        for (i, triple) in iter.into_iter().enumerate() {
+            if let Some(value) = Self::value(triple) {
+                index
+                    .predicates
+                    .entry(triple.predicate.clone())
+                    .or_default()
+                    .push((value, i));
+                index.all.push((value, i));
+            }
+        }
+
+        for values in index.predicates.values_mut() {
+            values.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        index.all.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        index
+    }
+}